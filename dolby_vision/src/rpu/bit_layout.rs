@@ -0,0 +1,318 @@
+use anyhow::{ensure, Result};
+use bitvec_helpers::bitvec_reader::BitVecReader;
+
+use super::dovi_rpu::DoviRpu;
+use super::rpu_data_mapping::RpuDataMapping;
+use super::rpu_data_nlq::RpuDataNlq;
+use super::NUM_COMPONENTS;
+
+/// Field of the RPU payload, located by its bit offset.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitField {
+    /// Offset in bits from the start of the RPU payload
+    pub offset: usize,
+
+    /// Width in bits
+    pub width: usize,
+
+    pub name: String,
+
+    /// Value of the field.
+    /// `None` for regions grouping several fields, such as the mapping data.
+    pub value: Option<u64>,
+}
+
+/// Reader recording the location of every field read
+struct LayoutReader {
+    reader: BitVecReader,
+    len: usize,
+    fields: Vec<BitField>,
+}
+
+impl LayoutReader {
+    fn position(&self) -> usize {
+        self.len - self.reader.available()
+    }
+
+    fn push(&mut self, name: &str, offset: usize, value: Option<u64>) {
+        self.fields.push(BitField {
+            offset,
+            width: self.position() - offset,
+            name: name.to_string(),
+            value,
+        });
+    }
+
+    fn get_n(&mut self, name: &str, n: usize) -> u64 {
+        let offset = self.position();
+        let value = self.reader.get_n(n);
+
+        self.push(name, offset, Some(value));
+
+        value
+    }
+
+    fn get(&mut self, name: &str) -> Result<bool> {
+        let offset = self.position();
+        let value = self.reader.get()?;
+
+        self.push(name, offset, Some(value as u64));
+
+        Ok(value)
+    }
+
+    fn get_ue(&mut self, name: &str) -> Result<u64> {
+        let offset = self.position();
+        let value = self.reader.get_ue()?;
+
+        self.push(name, offset, Some(value));
+
+        Ok(value)
+    }
+
+    /// Records everything read by `f` as a single region
+    fn region<F>(&mut self, name: &str, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut BitVecReader) -> Result<()>,
+    {
+        let offset = self.position();
+        f(&mut self.reader)?;
+
+        self.push(name, offset, None);
+
+        Ok(())
+    }
+
+    fn skip(&mut self, name: &str, bits: usize) -> Result<()> {
+        ensure!(
+            bits <= self.reader.available(),
+            "{}: {} bits extend past the end of the RPU",
+            name,
+            bits
+        );
+
+        self.region(name, |reader| {
+            for _ in 0..bits {
+                reader.get()?;
+            }
+
+            Ok(())
+        })
+    }
+
+    fn alignment_zero_bits(&mut self, name: &str) -> Result<()> {
+        let offset = self.position();
+
+        while !self.reader.is_aligned() {
+            ensure!(!self.reader.get()?, "{} != 0", name);
+        }
+
+        if self.position() > offset {
+            self.push(name, offset, Some(0));
+        }
+
+        Ok(())
+    }
+}
+
+/// Field by field bit layout of the RPU payload, as written by `DoviRpu::write_rpu`.
+///
+/// The header and DM fields are listed individually.
+/// The mapping, NLQ and extension block payloads are listed as regions.
+pub fn bit_layout(rpu: &DoviRpu) -> Result<Vec<BitField>> {
+    let data = rpu.write_rpu()?;
+    let len = data.len() * 8;
+
+    let mut reader = LayoutReader {
+        reader: BitVecReader::new(data),
+        len,
+        fields: Vec::new(),
+    };
+
+    read_header_layout(&mut reader)?;
+
+    // The written header is the RPU's header
+    let mut header = rpu.header.clone();
+
+    if header.rpu_type == 2 {
+        if !header.use_prev_vdr_rpu_flag {
+            reader.region("rpu_data_mapping", |r| {
+                RpuDataMapping::parse(r, &mut header).map(|_| ())
+            })?;
+
+            if header.nlq_method_idc.is_some() {
+                reader.region("rpu_data_nlq", |r| {
+                    RpuDataNlq::parse(r, &mut header).map(|_| ())
+                })?;
+            }
+        }
+
+        if header.vdr_dm_metadata_present_flag {
+            read_vdr_dm_data_layout(&mut reader, rpu.last_byte)?;
+        }
+
+        reader.alignment_zero_bits("rpu_alignment_zero_bit")?;
+
+        let final_length = if rpu.last_byte == 0 { 48 } else { 40 };
+        let remaining = reader.reader.available() - final_length;
+
+        if remaining > 0 {
+            reader.skip("remaining", remaining)?;
+        }
+
+        reader.get_n("rpu_data_crc32", 32);
+        reader.get_n("rbsp_trailing_bits", 8);
+
+        if rpu.last_byte == 0 {
+            reader.get_n("trailing_zero_byte", 8);
+        }
+    }
+
+    Ok(reader.fields)
+}
+
+fn read_header_layout(reader: &mut LayoutReader) -> Result<()> {
+    if reader.get_n("rpu_nal_prefix", 8) != 25 {
+        return Ok(());
+    }
+
+    let rpu_type = reader.get_n("rpu_type", 6);
+    let rpu_format = reader.get_n("rpu_format", 11);
+
+    if rpu_type != 2 {
+        return Ok(());
+    }
+
+    reader.get_n("vdr_rpu_profile", 4);
+    reader.get_n("vdr_rpu_level", 4);
+
+    let mut bl_bit_depth_minus8 = 0;
+    let mut disable_residual_flag = false;
+
+    if reader.get("vdr_seq_info_present_flag")? {
+        reader.get("chroma_resampling_explicit_filter_flag")?;
+
+        if reader.get_n("coefficient_data_type", 2) == 0 {
+            reader.get_ue("coefficient_log2_denom")?;
+        }
+
+        reader.get_n("vdr_rpu_normalized_idc", 2);
+        reader.get("bl_video_full_range_flag")?;
+
+        if rpu_format & 0x700 == 0 {
+            bl_bit_depth_minus8 = reader.get_ue("bl_bit_depth_minus8")?;
+            reader.get_ue("el_bit_depth_minus8")?;
+            reader.get_ue("vdr_bit_depth_minus_8")?;
+            reader.get("spatial_resampling_filter_flag")?;
+            reader.get_n("reserved_zero_3bits", 3);
+            reader.get("el_spatial_resampling_filter_flag")?;
+            disable_residual_flag = reader.get("disable_residual_flag")?;
+        }
+    }
+
+    reader.get("vdr_dm_metadata_present_flag")?;
+
+    if reader.get("use_prev_vdr_rpu_flag")? {
+        reader.get_ue("prev_vdr_rpu_id")?;
+    } else {
+        reader.get_ue("vdr_rpu_id")?;
+        reader.get_ue("mapping_color_space")?;
+        reader.get_ue("mapping_chroma_format_idc")?;
+
+        let pivot_bits = (bl_bit_depth_minus8 + 8) as usize;
+
+        for cmp in 0..NUM_COMPONENTS {
+            let num_pivots_minus_2 = reader.get_ue(&format!("num_pivots_minus_2[{}]", cmp))?;
+
+            for pivot_idx in 0..(num_pivots_minus_2 + 2) {
+                let name = format!("pred_pivot_value[{}][{}]", cmp, pivot_idx);
+                reader.get_n(&name, pivot_bits);
+            }
+        }
+
+        // Profile 7 only
+        if rpu_format & 0x700 == 0 && !disable_residual_flag {
+            reader.get_n("nlq_method_idc", 3);
+        }
+
+        reader.get_ue("num_x_partitions_minus1")?;
+        reader.get_ue("num_y_partitions_minus1")?;
+    }
+
+    Ok(())
+}
+
+fn read_vdr_dm_data_layout(reader: &mut LayoutReader, last_byte: u8) -> Result<()> {
+    reader.get_ue("affected_dm_metadata_id")?;
+    reader.get_ue("current_dm_metadata_id")?;
+    reader.get_ue("scene_refresh_flag")?;
+
+    for i in 0..9 {
+        reader.get_n(&format!("ycc_to_rgb_coef{}", i), 16);
+    }
+
+    for i in 0..3 {
+        reader.get_n(&format!("ycc_to_rgb_offset{}", i), 32);
+    }
+
+    for i in 0..9 {
+        reader.get_n(&format!("rgb_to_lms_coef{}", i), 16);
+    }
+
+    reader.get_n("signal_eotf", 16);
+    reader.get_n("signal_eotf_param0", 16);
+    reader.get_n("signal_eotf_param1", 16);
+    reader.get_n("signal_eotf_param2", 32);
+    reader.get_n("signal_bit_depth", 5);
+    reader.get_n("signal_color_space", 2);
+    reader.get_n("signal_chroma_format", 2);
+    reader.get_n("signal_full_range_flag", 2);
+    reader.get_n("source_min_pq", 12);
+    reader.get_n("source_max_pq", 12);
+    reader.get_n("source_diagonal", 10);
+
+    read_dm_data_layout(reader, "cmv29")?;
+
+    // 40 or 48 w/ CRC32 + 16 bits required level 254
+    let final_length = if last_byte == 0 { 48 } else { 40 };
+
+    if reader.reader.available() >= final_length + 16 {
+        read_dm_data_layout(reader, "cmv40")?;
+    }
+
+    Ok(())
+}
+
+fn read_dm_data_layout(reader: &mut LayoutReader, version: &str) -> Result<()> {
+    let num_ext_blocks = reader.get_ue(&format!("{}.num_ext_blocks", version))?;
+    reader.alignment_zero_bits(&format!("{}.dm_alignment_zero_bit", version))?;
+
+    for i in 0..num_ext_blocks {
+        let prefix = format!("{}.ext_metadata_block[{}]", version, i);
+
+        let ext_block_length = reader.get_ue(&format!("{}.ext_block_length", prefix))?;
+        let ext_block_level = reader.get_n(&format!("{}.ext_block_level", prefix), 8);
+
+        reader.skip(
+            &format!("{}.level{}_payload", prefix, ext_block_level),
+            ext_block_length as usize * 8,
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Human readable listing of the fields: offset, width, name and value
+pub fn format_bit_layout(fields: &[BitField]) -> String {
+    fields
+        .iter()
+        .map(|field| {
+            let value = field.value.map_or("-".to_string(), |v| v.to_string());
+
+            format!(
+                "{:>6} {:>5}  {} = {}\n",
+                field.offset, field.width, field.name, value
+            )
+        })
+        .collect()
+}
@@ -3,25 +3,70 @@ use bitvec::prelude::*;
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::compute_crc32;
-use super::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel5};
+use super::extension_metadata::blocks::{
+    reserved::ReservedBlockParsers, ExtMetadataBlock, ExtMetadataBlockLevel5,
+};
+use super::extension_metadata::{CmV29DmData, CmV40DmData, DmData, WithExtMetadataBlocks};
 use super::generate::GenerateConfig;
+use super::profiles::{self, FootprintInfo};
 use super::rpu_data_header::{rpu_data_header, RpuDataHeader};
-use super::rpu_data_mapping::RpuDataMapping;
+use super::rpu_data_mapping::{MappingMethod, RpuDataMapping};
 use super::rpu_data_nlq::RpuDataNlq;
-use super::vdr_dm_data::VdrDmData;
+use super::vdr_dm_data::{CmVersion, VdrDmData};
 
 use crate::rpu::rpu_data_mapping::vdr_rpu_data_payload;
 use crate::rpu::vdr_dm_data::vdr_dm_data_payload;
 
 use crate::utils::{
     add_start_code_emulation_prevention_3_byte, clear_start_code_emulation_prevention_3_byte,
+    nits_to_pq, target_pq_to_nits, WarningSink,
 };
 
+/// Trim parameters applied by a display of a specific peak brightness.
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TrimParams {
+    pub target_max_pq: u16,
+    pub trim_slope: u16,
+    pub trim_offset: u16,
+    pub trim_power: u16,
+    pub trim_chroma_weight: u16,
+    pub trim_saturation_gain: u16,
+    pub ms_weight: i16,
+}
+
+/// Options for parsing RPUs.
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+pub struct RpuParseOpts {
+    /// Keep the blocks with a level defined for the other CM version as reserved blocks,
+    /// preserving their payload, and emit a warning. Otherwise, the parsing fails.
+    pub tolerate_misplaced_blocks: bool,
+
+    /// Parsers for vendor-specific reserved block levels.
+    /// Blocks of other reserved levels are rejected.
+    pub reserved_block_parsers: ReservedBlockParsers,
+
+    /// Receives the warnings, such as misplaced blocks
+    pub warnings: WarningSink,
+}
+
+/// Conversions applied by `DoviRpu::convert_with_mode`, besides the untouched mode 0.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConversionMode {
+    /// Mode 1: profile 7 or 8 to MEL compatible
+    ToMel = 1,
+
+    /// Mode 2: profile 7 to profile 8.1
+    To81 = 2,
+
+    /// Mode 3: profile 5 to profile 8.1
+    P5To81 = 3,
+}
+
+#[derive(Default, Debug, Clone)]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct DoviRpu {
     pub dovi_profile: u8,
     pub header: RpuDataHeader,
@@ -47,18 +92,32 @@ pub struct DoviRpu {
     #[cfg_attr(
         feature = "serde_feature",
         serde(
+            default,
             serialize_with = "crate::utils::bitvec_ser_bits",
+            deserialize_with = "crate::utils::bitvec_de_bits",
             skip_serializing_if = "BitVec::is_empty"
         )
     )]
     pub remaining: BitVec<Msb0, u8>,
     pub rpu_data_crc32: u32,
 
-    #[cfg_attr(feature = "serde_feature", serde(skip_serializing))]
+    /// Byte following the `0x80` final byte, only serialized when not the usual `0x80`
+    #[cfg_attr(
+        feature = "serde_feature",
+        serde(
+            default = "DoviRpu::default_last_byte",
+            skip_serializing_if = "DoviRpu::is_default_last_byte"
+        )
+    )]
     pub last_byte: u8,
 
-    #[cfg_attr(feature = "serde_feature", serde(skip_serializing))]
+    #[cfg_attr(feature = "serde_feature", serde(skip))]
     pub modified: bool,
+
+    /// Write only with the required metadata blocks, such as L5 and L6.
+    /// Set for generated RPUs, parsed RPUs are written as-is by default.
+    #[cfg_attr(feature = "serde_feature", serde(skip))]
+    pub validate_required_blocks: bool,
 }
 
 impl DoviRpu {
@@ -67,6 +126,14 @@ impl DoviRpu {
             bail!("Invalid RPU length: {}", &data.len());
         }
 
+        // Start code followed by the 0x7C01 NAL header, as written by
+        // `write_rpu_nalu_with_emulation_prevention`
+        let data = match data {
+            [0, 0, 0, 1, 124, 1, ..] => &data[4..],
+            [0, 0, 1, 124, 1, ..] => &data[3..],
+            _ => data,
+        };
+
         // Including 0x7C01 prepended
         let trimmed_data = match &data[..5] {
             [0, 0, 0, 1, 25] => &data[4..],
@@ -80,23 +147,106 @@ impl DoviRpu {
         Ok(trimmed_data)
     }
 
+    /// Parses an RPU NAL unit, with either a 3 or 4 bytes start code.
+    /// The NAL header may follow the start code.
+    /// The payload may or may not have had the emulation prevention bytes removed already.
     pub fn parse_unspec62_nalu(data: &[u8]) -> Result<DoviRpu> {
+        DoviRpu::parse_unspec62_nalu_with_opts(data, &RpuParseOpts::default())
+    }
+
+    /// Parses an RPU NAL unit like `parse_unspec62_nalu`, with the parsing options.
+    pub fn parse_unspec62_nalu_with_opts(data: &[u8], opts: &RpuParseOpts) -> Result<DoviRpu> {
         let trimmed_data = DoviRpu::validated_trimmed_data(data)?;
 
         // Clear start code emulation prevention 3 byte
         let bytes: Vec<u8> = clear_start_code_emulation_prevention_3_byte(trimmed_data);
 
-        DoviRpu::parse(&bytes)
+        // Already cleared payload, removing more bytes would corrupt it
+        if bytes.len() != trimmed_data.len()
+            && !DoviRpu::crc32_matches(&bytes)
+            && DoviRpu::crc32_matches(trimmed_data)
+        {
+            return DoviRpu::parse(trimmed_data, opts);
+        }
+
+        DoviRpu::parse(&bytes, opts)
     }
 
     pub fn parse_rpu(data: &[u8]) -> Result<DoviRpu> {
         let trimmed_data = DoviRpu::validated_trimmed_data(data)?;
 
-        DoviRpu::parse(trimmed_data)
+        DoviRpu::parse(trimmed_data, &RpuParseOpts::default())
+    }
+
+    /// Parses a bare RPU payload, as found in the NAL unit after the `0x7C01` header.
+    ///
+    /// The payload must start with the `0x19` RPU prefix and end with the `0x80` final byte,
+    /// or `0x8000` for the final RPU of a stream.
+    /// It must not include a start code or the NAL header, and the emulation prevention bytes
+    /// must already be removed: they are not cleared, unlike `parse_unspec62_nalu`.
+    pub fn parse_rpu_payload(data: &[u8]) -> Result<DoviRpu> {
+        ensure!(
+            data.len() >= 25,
+            "Invalid RPU payload length: {}",
+            data.len()
+        );
+        ensure!(
+            data[0] == 25,
+            "Invalid RPU payload start byte: {}, expected the 0x19 RPU prefix",
+            data[0]
+        );
+
+        DoviRpu::parse(data, &RpuParseOpts::default())
+    }
+
+    /// Deserializes an RPU from its JSON representation, as serialized with `serde`.
+    ///
+    /// The RPU is marked as modified, so that the metadata can be edited:
+    /// the CRC32 is computed again when writing.
+    #[cfg(feature = "serde_feature")]
+    pub fn from_json(value: serde_json::Value) -> Result<DoviRpu> {
+        let mut dovi_rpu: DoviRpu = serde_json::from_value(value)?;
+
+        dovi_rpu.dovi_profile = dovi_rpu.header.get_dovi_profile();
+        dovi_rpu.validate()?;
+
+        dovi_rpu.modified = true;
+
+        Ok(dovi_rpu)
+    }
+
+    #[cfg(feature = "serde_feature")]
+    fn default_last_byte() -> u8 {
+        0x80
+    }
+
+    #[cfg(feature = "serde_feature")]
+    fn is_default_last_byte(last_byte: &u8) -> bool {
+        *last_byte == 0x80
+    }
+
+    fn crc32_matches(data: &[u8]) -> bool {
+        let len = data.len();
+
+        let crc32_end = if data[len - 1] == 0 && data[len - 2] == 0x80 {
+            len - 2
+        } else {
+            len - 1
+        };
+
+        let crc32_start = crc32_end - 4;
+        let received_crc32 = u32::from_be_bytes([
+            data[crc32_start],
+            data[crc32_start + 1],
+            data[crc32_start + 2],
+            data[crc32_start + 3],
+        ]);
+
+        compute_crc32(&data[1..crc32_start]) == received_crc32
     }
 
     #[inline(always)]
-    fn parse(data: &[u8]) -> Result<DoviRpu> {
+    fn parse(data: &[u8], opts: &RpuParseOpts) -> Result<DoviRpu> {
         let len = data.len();
 
         let mut received_crc32 = compute_crc32(&data[1..len - 5]);
@@ -109,7 +259,7 @@ impl DoviRpu {
             bail!("Invalid RPU last byte: {}", last_byte);
         }
 
-        let mut dovi_rpu = DoviRpu::read_rpu_data(data.to_owned(), last_byte)?;
+        let mut dovi_rpu = DoviRpu::read_rpu_data(data.to_owned(), last_byte, opts)?;
 
         if received_crc32 != dovi_rpu.rpu_data_crc32 {
             bail!(
@@ -125,7 +275,7 @@ impl DoviRpu {
     }
 
     #[inline(always)]
-    fn read_rpu_data(bytes: Vec<u8>, end_byte: u8) -> Result<DoviRpu> {
+    fn read_rpu_data(bytes: Vec<u8>, end_byte: u8, opts: &RpuParseOpts) -> Result<DoviRpu> {
         let mut reader = BitVecReader::new(bytes);
         let mut dovi_rpu = DoviRpu {
             last_byte: end_byte,
@@ -148,7 +298,7 @@ impl DoviRpu {
             }
 
             if dovi_rpu.header.vdr_dm_metadata_present_flag {
-                vdr_dm_data_payload(&mut dovi_rpu, &mut reader)?;
+                vdr_dm_data_payload(&mut dovi_rpu, &mut reader, opts)?;
             }
 
             // rpu_alignment_zero_bit
@@ -187,12 +337,77 @@ impl DoviRpu {
         Ok(out)
     }
 
+    /// Writes the RPU as a complete NAL unit, ready to be inserted in an HEVC bitstream.
+    /// Includes the 4 bytes start code, the NAL header and the emulation prevention bytes.
+    pub fn write_rpu_nalu_with_emulation_prevention(&self) -> Result<Vec<u8>> {
+        let mut out = vec![0, 0, 0, 1];
+        out.extend(self.write_hevc_unspec62_nalu()?);
+
+        Ok(out)
+    }
+
+    /// Writes the RPU in the layout of the RPU files read by x265 with `--dolby-vision-rpu`:
+    /// the 4 bytes start code, then the payload with the emulation prevention bytes.
+    /// The `0x7C01` NAL header is omitted, x265 adds it when muxing the NAL unit.
+    ///
+    /// Concatenating the RPUs of every frame gives the same file as `extract-rpu`.
+    pub fn write_x265_rpu(&self) -> Result<Vec<u8>> {
+        let mut out = vec![0, 0, 0, 1];
+        out.extend(&self.write_hevc_unspec62_nalu()?[2..]);
+
+        Ok(out)
+    }
+
+    /// Writes the RPU payload as it was parsed, including any unparsed trailing data.
     pub fn write_rpu(&self) -> Result<Vec<u8>> {
         self.write_rpu_data()
     }
 
-    #[inline(always)]
-    fn write_rpu_data(&self) -> Result<Vec<u8>> {
+    /// Writes the RPU payload, guaranteeing the canonical se(v) encoding.
+    ///
+    /// The written payload is parsed back, and the signed Exp-Golomb coded
+    /// mapping coefficients must decode to the same values.
+    pub fn write_rpu_strict(&self) -> Result<Vec<u8>> {
+        let data = self.write_rpu_data()?;
+
+        let reparsed = DoviRpu::parse_rpu(&data)?;
+        ensure!(
+            reparsed.remaining.len() == self.remaining.len(),
+            "RPU strict write: expected {} bytes of trailing payload, found {} after re-parsing",
+            self.remaining.len() / 8,
+            reparsed.remaining.len() / 8
+        );
+        ensure!(
+            reparsed.remaining == self.remaining,
+            "RPU strict write: trailing payload differs after re-parsing"
+        );
+
+        if let (Some(mapping), Some(reparsed_mapping)) =
+            (&self.rpu_data_mapping, &reparsed.rpu_data_mapping)
+        {
+            ensure!(
+                mapping.poly_coef_int == reparsed_mapping.poly_coef_int
+                    && mapping.mmr_constant_int == reparsed_mapping.mmr_constant_int
+                    && mapping.mmr_coef_int == reparsed_mapping.mmr_coef_int,
+                "RPU strict write: se(v) coded coefficients do not round-trip"
+            );
+        }
+
+        Ok(data)
+    }
+
+    /// CRC32 of the RPU payload, as written by `write_rpu`.
+    ///
+    /// Unlike `write_rpu`, this doesn't require the CRC32 to match `rpu_data_crc32`
+    /// for unmodified RPUs, so the two can be compared before writing.
+    pub fn computed_crc32(&self) -> Result<u32> {
+        let writer = self.write_rpu_payload()?;
+
+        Ok(compute_crc32(&writer.as_slice()[1..]))
+    }
+
+    /// Writes the byte aligned RPU payload, up to the CRC32
+    fn write_rpu_payload(&self) -> Result<BitVecWriter> {
         let mut writer = BitVecWriter::new();
 
         self.validate()?;
@@ -218,6 +433,13 @@ impl DoviRpu {
             writer.write(false);
         }
 
+        Ok(writer)
+    }
+
+    #[inline(always)]
+    fn write_rpu_data(&self) -> Result<Vec<u8>> {
+        let mut writer = self.write_rpu_payload()?;
+
         let computed_crc32 = compute_crc32(&writer.as_slice()[1..]);
 
         if !self.modified {
@@ -253,7 +475,7 @@ impl DoviRpu {
 
     fn write_vdr_dm_data_payload(&self, writer: &mut BitVecWriter) -> Result<()> {
         if let Some(ref vdr_dm_data) = self.vdr_dm_data {
-            vdr_dm_data.write(writer)?;
+            vdr_dm_data.write_validated(writer, self.validate_required_blocks)?;
         }
 
         Ok(())
@@ -269,6 +491,128 @@ impl DoviRpu {
         Ok(())
     }
 
+    /// Blocks whose written payload doesn't have the declared length, as
+    /// `(level, declared length, written length)` in bytes.
+    /// Blocks that fail to be written are not checked.
+    pub fn block_length_issues(&self) -> Vec<(u8, u64, u64)> {
+        let vdr_dm_data = match &self.vdr_dm_data {
+            Some(vdr_dm_data) => vdr_dm_data,
+            None => return Vec::new(),
+        };
+
+        [&vdr_dm_data.cmv29_metadata, &vdr_dm_data.cmv40_metadata]
+            .iter()
+            .filter_map(|dm_data| dm_data.as_ref())
+            .flat_map(|dm_data| match dm_data {
+                DmData::V29(meta) => meta.blocks_ref().iter(),
+                DmData::V40(meta) => meta.blocks_ref().iter(),
+            })
+            .filter_map(|block| {
+                let declared = block.length_bytes();
+                let written = block.written_length_bytes().ok()?;
+
+                if written != declared {
+                    Some((block.level(), declared, written))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Copies of the CM v2.9 and CM v4.0 DM data, when present
+    pub fn split_dm_versions(&self) -> (Option<CmV29DmData>, Option<CmV40DmData>) {
+        let vdr_dm_data = match &self.vdr_dm_data {
+            Some(vdr_dm_data) => vdr_dm_data,
+            None => return (None, None),
+        };
+
+        let cmv29 = match &vdr_dm_data.cmv29_metadata {
+            Some(DmData::V29(cmv29)) => Some(cmv29.clone()),
+            _ => None,
+        };
+
+        let cmv40 = match &vdr_dm_data.cmv40_metadata {
+            Some(DmData::V40(cmv40)) => Some(cmv40.clone()),
+            _ => None,
+        };
+
+        (cmv29, cmv40)
+    }
+
+    /// Metadata actually carried by the RPU, see `profiles::metadata_footprint`
+    pub fn footprint(&self) -> FootprintInfo {
+        let (cmv29, cmv40) = self.split_dm_versions();
+
+        let mut cm_versions = Vec::new();
+        let mut block_levels: Vec<u8> = Vec::new();
+
+        if let Some(cmv29) = &cmv29 {
+            cm_versions.push(CmVersion::V29);
+            block_levels.extend(cmv29.blocks_ref().iter().map(|b| b.level()));
+        }
+
+        if let Some(cmv40) = &cmv40 {
+            cm_versions.push(CmVersion::V40);
+            block_levels.extend(cmv40.blocks_ref().iter().map(|b| b.level()));
+        }
+
+        block_levels.sort_unstable();
+        block_levels.dedup();
+
+        FootprintInfo {
+            profile: self.dovi_profile,
+            el_mapping: self.rpu_data_nlq.is_some(),
+            backwards_compatible: profiles::backwards_compatible(self.dovi_profile),
+            cm_versions,
+            block_levels,
+        }
+    }
+
+    /// Prediction method of the mapping.
+    /// `None` when the RPU reuses the mapping of a previous RPU.
+    pub fn mapping_method(&self) -> Option<MappingMethod> {
+        self.rpu_data_mapping
+            .as_ref()
+            .map(|mapping| mapping.mapping_method())
+    }
+
+    /// Conversion modes valid for the RPU profile, as accepted by `convert_with_mode`
+    pub fn available_conversions(&self) -> Vec<ConversionMode> {
+        match self.dovi_profile {
+            7 => vec![ConversionMode::ToMel, ConversionMode::To81],
+            5 => vec![ConversionMode::P5To81],
+            8 => vec![ConversionMode::ToMel],
+            _ => Vec::new(),
+        }
+    }
+
+    /// Whether the RPU has NLQ parameters adding an EL residual
+    pub fn has_nlq(&self) -> bool {
+        self.rpu_data_nlq
+            .as_ref()
+            .map_or(false, |nlq| !nlq.is_zero_residual())
+    }
+
+    /// Zeroes the NLQ parameters, the EL residual is discarded but the RPU stays profile 7.
+    ///
+    /// Unlike the MEL conversion (`convert_with_mode(1)`), the header is untouched:
+    /// the NLQ method and partitions of the original RPU are kept, only the parameters are zeroed.
+    /// The MEL conversion also sets `vdr_in_max_int` to 1, where this leaves every parameter at 0.
+    pub fn strip_el_residual(&mut self) -> Result<()> {
+        ensure!(
+            self.dovi_profile == 7,
+            "Not profile 7, no EL residual to strip!"
+        );
+
+        if let Some(ref mut rpu_data_nlq) = self.rpu_data_nlq {
+            rpu_data_nlq.zero_residual();
+            self.modified = true;
+        }
+
+        Ok(())
+    }
+
     pub fn convert_with_mode(&mut self, mode: u8) -> Result<()> {
         if mode != 0 {
             self.modified = true;
@@ -284,6 +628,13 @@ impl DoviRpu {
             self.p5_to_p81()?;
         } else if self.dovi_profile == 8 && mode == 1 {
             self.convert_to_mel()?;
+        } else if self.dovi_profile == 4 && mode != 0 {
+            // The BL is SDR, the HDR signal only exists with the EL and the RPU mapping.
+            // Profile 7 and 8 expect a HDR10 compatible BL, so no conversion is valid.
+            bail!(
+                "Profile 4 RPUs cannot be converted (mode {}): the base layer is SDR and requires the enhancement layer",
+                mode
+            );
         } else if mode != 0 {
             bail!("Invalid profile for mode {} conversion!", mode);
         }
@@ -358,11 +709,65 @@ impl DoviRpu {
         Ok(())
     }
 
+    /// Anomalies of a RPU converted from profile 5 to 8.1 (mode 3), as messages.
+    ///
+    /// The converted RPU must be limited range profile 8.1, with an identity mapping
+    /// and no NLQ. The L5 and L6 metadata required by profile 8.1 must be present.
+    pub fn p81_conversion_issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        let header = &self.header;
+
+        if self.dovi_profile != 8 || header.vdr_rpu_profile != 1 {
+            issues.push(format!(
+                "RPU is profile {} (vdr_rpu_profile {}), expected 8.1",
+                self.dovi_profile, header.vdr_rpu_profile
+            ));
+        }
+
+        if header.bl_video_full_range_flag {
+            issues.push(String::from("BL signaled as full range"));
+        }
+
+        let identity_pivots = header.num_pivots_minus_2 == [0, 0, 0]
+            && header.pred_pivot_value.iter().all(|v| v[..] == [0, 1023]);
+
+        if !identity_pivots {
+            issues.push(String::from("Mapping pivots are not the full 10 bit range"));
+        }
+
+        match &self.rpu_data_mapping {
+            Some(mapping) if !mapping.is_identity() => {
+                issues.push(String::from("Mapping coefficients are not the identity"))
+            }
+            None if !header.use_prev_vdr_rpu_flag => issues.push(String::from("Missing mapping")),
+            _ => (),
+        }
+
+        if self.rpu_data_nlq.is_some() || header.nlq_method_idc.is_some() {
+            issues.push(String::from("NLQ parameters present"));
+        }
+
+        if let Some(vdr_dm_data) = &self.vdr_dm_data {
+            for &level in &[5, 6] {
+                if vdr_dm_data.get_block(level).is_none() {
+                    issues.push(format!("Missing L{} metadata", level));
+                }
+            }
+        } else {
+            issues.push(String::from("Missing DM metadata"));
+        }
+
+        issues
+    }
+
     pub fn profile81_config(config: &GenerateConfig) -> Result<Self> {
+        let header = RpuDataHeader::p8_with_bit_depths(config.bl_bit_depth, config.el_bit_depth)?;
+
         Ok(DoviRpu {
             dovi_profile: 8,
             modified: true,
-            header: RpuDataHeader::p8_default(),
+            validate_required_blocks: true,
+            header,
             rpu_data_mapping: Some(RpuDataMapping::p8_default()),
             rpu_data_nlq: None,
             vdr_dm_data: Some(VdrDmData::from_generate_config(config)?),
@@ -385,6 +790,16 @@ impl DoviRpu {
         Ok(())
     }
 
+    /// Bare minimum HDR metadata, without any trim: only the L1, L5 and L6 blocks are kept.
+    /// The CM v4.0 metadata is removed.
+    pub fn minimal_hdr(&mut self) {
+        self.modified = true;
+
+        if let Some(ref mut vdr_dm_data) = self.vdr_dm_data {
+            vdr_dm_data.retain_minimal_hdr();
+        }
+    }
+
     pub fn remove_mapping(&mut self) {
         self.modified = true;
 
@@ -399,6 +814,147 @@ impl DoviRpu {
         }
     }
 
+    /// Mutable iterator over the metadata blocks of a specific level.
+    /// The RPU is considered modified.
+    pub fn level_blocks_iter_mut(
+        &mut self,
+        level: u8,
+    ) -> impl Iterator<Item = &mut ExtMetadataBlock> {
+        self.modified = true;
+
+        self.vdr_dm_data
+            .iter_mut()
+            .flat_map(move |vdr_dm_data| vdr_dm_data.level_blocks_iter_mut(level))
+    }
+
+    /// Highest target display peak brightness with a trim, in nits.
+    ///
+    /// Uses the L2 trims, and the L8 trims for which a L10 block defines the target display.
+    /// Returns 0 without any trim.
+    pub fn peak_target_nits(&self) -> u16 {
+        let vdr_dm_data = match &self.vdr_dm_data {
+            Some(vdr_dm_data) => vdr_dm_data,
+            None => return 0,
+        };
+
+        let level2_targets = vdr_dm_data
+            .level_blocks_iter(2)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level2(b) => Some(b.target_max_pq),
+                _ => None,
+            });
+
+        let level8_targets = vdr_dm_data.level_blocks_iter(8).filter_map(|block| {
+            if let ExtMetadataBlock::Level8(b) = block {
+                vdr_dm_data.level_blocks_iter(10).find_map(|l10| match l10 {
+                    ExtMetadataBlock::Level10(l10)
+                        if l10.target_display_index == b.target_display_index =>
+                    {
+                        Some(l10.target_max_pq)
+                    }
+                    _ => None,
+                })
+            } else {
+                None
+            }
+        });
+
+        level2_targets
+            .chain(level8_targets)
+            .max()
+            .map_or(0, target_pq_to_nits)
+    }
+
+    /// Trim that a display of `nits` peak brightness would apply.
+    ///
+    /// Uses the L2 trims, and the L8 trims for which a L10 block defines the target display.
+    /// The trim is interpolated between the two closest targets, and clamped to the
+    /// lowest or highest target outside of the defined range.
+    pub fn effective_trim_for_nits(&self, nits: u16) -> Option<TrimParams> {
+        let vdr_dm_data = self.vdr_dm_data.as_ref()?;
+
+        let mut trims: Vec<TrimParams> = vdr_dm_data
+            .level_blocks_iter(2)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level2(b) => Some(TrimParams {
+                    target_max_pq: b.target_max_pq,
+                    trim_slope: b.trim_slope,
+                    trim_offset: b.trim_offset,
+                    trim_power: b.trim_power,
+                    trim_chroma_weight: b.trim_chroma_weight,
+                    trim_saturation_gain: b.trim_saturation_gain,
+                    ms_weight: b.ms_weight,
+                }),
+                _ => None,
+            })
+            .collect();
+
+        let level8_trims = vdr_dm_data.level_blocks_iter(8).filter_map(|block| {
+            if let ExtMetadataBlock::Level8(b) = block {
+                let target_max_pq =
+                    vdr_dm_data
+                        .level_blocks_iter(10)
+                        .find_map(|l10| match l10 {
+                            ExtMetadataBlock::Level10(l10)
+                                if l10.target_display_index == b.target_display_index =>
+                            {
+                                Some(l10.target_max_pq)
+                            }
+                            _ => None,
+                        })?;
+
+                Some(TrimParams {
+                    target_max_pq,
+                    trim_slope: b.trim_slope,
+                    trim_offset: b.trim_offset,
+                    trim_power: b.trim_power,
+                    trim_chroma_weight: b.trim_chroma_weight,
+                    trim_saturation_gain: b.trim_saturation_gain,
+                    ms_weight: b.ms_weight as i16,
+                })
+            } else {
+                None
+            }
+        });
+
+        trims.extend(level8_trims);
+        trims.sort_by_key(|t| t.target_max_pq);
+
+        let target_pq = (nits_to_pq(nits.into()) * 4095.0).round() as u16;
+
+        let upper_idx = trims.iter().position(|t| t.target_max_pq >= target_pq);
+
+        let trim = match upper_idx {
+            None => trims.last()?.clone(),
+            Some(0) => trims[0].clone(),
+            Some(i) => {
+                let lower = &trims[i - 1];
+                let upper = &trims[i];
+
+                let t = (target_pq - lower.target_max_pq) as f64
+                    / (upper.target_max_pq - lower.target_max_pq) as f64;
+                let lerp = |a: u16, b: u16| (a as f64 + (b as f64 - a as f64) * t).round() as u16;
+
+                TrimParams {
+                    target_max_pq: target_pq,
+                    trim_slope: lerp(lower.trim_slope, upper.trim_slope),
+                    trim_offset: lerp(lower.trim_offset, upper.trim_offset),
+                    trim_power: lerp(lower.trim_power, upper.trim_power),
+                    trim_chroma_weight: lerp(lower.trim_chroma_weight, upper.trim_chroma_weight),
+                    trim_saturation_gain: lerp(
+                        lower.trim_saturation_gain,
+                        upper.trim_saturation_gain,
+                    ),
+                    ms_weight: (lower.ms_weight as f64
+                        + (upper.ms_weight - lower.ms_weight) as f64 * t)
+                        .round() as i16,
+                }
+            }
+        };
+
+        Some(trim)
+    }
+
     pub fn parse_list_of_unspec62_nalus(data: &[Vec<u8>]) -> Vec<DoviRpu> {
         data.iter()
             .map(|rpu| DoviRpu::parse_unspec62_nalu(rpu))
@@ -406,3 +962,17 @@ impl DoviRpu {
             .collect()
     }
 }
+
+impl ConversionMode {
+    pub fn mode(self) -> u8 {
+        self as u8
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            ConversionMode::ToMel => "MEL compatible",
+            ConversionMode::To81 => "profile 8.1",
+            ConversionMode::P5To81 => "profile 5 to 8.1",
+        }
+    }
+}
@@ -4,7 +4,7 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
+use super::{clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo};
 
 /// cbindgen:ignore
 pub const L1_MIN_PQ_MAX_VALUE: u16 = 12;
@@ -52,6 +52,49 @@ impl ExtMetadataBlockLevel1 {
         Ok(())
     }
 
+    /// Clamps the values to 12 bits, and orders them as min <= avg <= max.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+
+        clamp_field(
+            &mut corrections,
+            1,
+            "min_pq",
+            &mut self.min_pq,
+            L1_MAX_PQ_MAX_VALUE,
+        );
+        clamp_field(
+            &mut corrections,
+            1,
+            "max_pq",
+            &mut self.max_pq,
+            L1_MAX_PQ_MAX_VALUE,
+        );
+        clamp_field(
+            &mut corrections,
+            1,
+            "avg_pq",
+            &mut self.avg_pq,
+            L1_MAX_PQ_MAX_VALUE,
+        );
+
+        if self.min_pq > self.max_pq {
+            corrections.push(format!(
+                "L1: min_pq {} and max_pq {} swapped",
+                self.min_pq, self.max_pq
+            ));
+            std::mem::swap(&mut self.min_pq, &mut self.max_pq);
+        }
+
+        let avg_pq = self.avg_pq.clamp(self.min_pq, self.max_pq);
+        if avg_pq != self.avg_pq {
+            corrections.push(format!("L1: avg_pq {} clamped to {}", self.avg_pq, avg_pq));
+            self.avg_pq = avg_pq;
+        }
+
+        corrections
+    }
+
     pub fn from_stats(min_pq: u16, max_pq: u16, avg_pq: u16) -> ExtMetadataBlockLevel1 {
         let min_pq = min_pq.clamp(0, L1_MIN_PQ_MAX_VALUE);
         let max_pq = max_pq.clamp(L1_MAX_PQ_MIN_VALUE, L1_MAX_PQ_MAX_VALUE);
@@ -2,16 +2,18 @@ use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
+use super::level9::{nearest_predefined_primaries, primaries_from_signed_values};
 use super::{level6::MAX_PQ_LUMINANCE, ExtMetadataBlock, ExtMetadataBlockInfo};
 
 pub const PRESET_TARGET_DISPLAYS: &[u8] = &[1, 16, 18, 21, 27, 28, 37, 38, 42, 48, 49];
 
 /// Custom target display information
 #[repr(C)]
-#[derive(Debug, Default, Clone)]
+#[derive(Debug, Default, Clone, PartialEq)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize))]
+#[cfg_attr(feature = "serde_feature", serde(default))]
 pub struct ExtMetadataBlockLevel10 {
     pub target_display_index: u8,
     pub target_max_pq: u16,
@@ -32,7 +34,7 @@ impl Serialize for ExtMetadataBlockLevel10 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
-    { 
+    {
         let mut state = serializer.serialize_struct("ExtMetadataBlockLevel9", 12)?;
         state.serialize_field("target_display_index", &self.target_display_index)?;
         state.serialize_field("target_max_pq", &self.target_max_pq)?;
@@ -41,7 +43,7 @@ impl Serialize for ExtMetadataBlockLevel10 {
 
         if self.target_primary_index == 255 {
             state.serialize_field("target_primary_red_x", &self.target_primary_red_x)?;
-            state.serialize_field("target_primary_red_y", &self.target_primary_white_y)?;
+            state.serialize_field("target_primary_red_y", &self.target_primary_red_y)?;
             state.serialize_field("target_primary_green_x", &self.target_primary_green_x)?;
             state.serialize_field("target_primary_green_y", &self.target_primary_green_y)?;
             state.serialize_field("target_primary_blue_x", &self.target_primary_blue_x)?;
@@ -77,6 +79,39 @@ impl ExtMetadataBlockLevel10 {
         return ExtMetadataBlock::Level10(block);
     }
 
+    /// Custom primaries matching a predefined index are replaced by the index,
+    /// and the primaries of a predefined index are reset to their default values.
+    /// The block is then written in its shortest form, as reparsed.
+    pub fn canonicalize(&mut self) {
+        let primary_index = if self.target_primary_index == 255 {
+            let primaries = primaries_from_signed_values(&[
+                self.target_primary_red_x,
+                self.target_primary_red_y,
+                self.target_primary_green_x,
+                self.target_primary_green_y,
+                self.target_primary_blue_x,
+                self.target_primary_blue_y,
+                self.target_primary_white_x,
+                self.target_primary_white_y,
+            ]);
+
+            match nearest_predefined_primaries(&primaries) {
+                Some(index) => index,
+                None => return,
+            }
+        } else {
+            self.target_primary_index
+        };
+
+        *self = Self {
+            target_display_index: self.target_display_index,
+            target_max_pq: self.target_max_pq,
+            target_min_pq: self.target_min_pq,
+            target_primary_index: primary_index,
+            ..Default::default()
+        };
+    }
+
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         self.validate()?;
 
@@ -93,14 +128,18 @@ impl ExtMetadataBlockLevel10 {
             writer.write_n(&self.target_primary_blue_x.to_be_bytes(), 16);
             writer.write_n(&self.target_primary_blue_y.to_be_bytes(), 16);
             writer.write_n(&self.target_primary_white_x.to_be_bytes(), 16);
-            writer.write_n(&self.target_primary_white_y.to_be_bytes(), 16);  
+            writer.write_n(&self.target_primary_white_y.to_be_bytes(), 16);
         }
 
         Ok(())
     }
 
     pub fn validate(&self) -> Result<()> {
-        ensure!(!PRESET_TARGET_DISPLAYS.contains(&self.target_display_index));
+        ensure!(
+            !PRESET_TARGET_DISPLAYS.contains(&self.target_display_index),
+            "L10: target display index {} is a preset target display, it cannot be redefined",
+            self.target_display_index
+        );
         ensure!(self.target_max_pq <= MAX_PQ_LUMINANCE);
         ensure!(self.target_min_pq <= MAX_PQ_LUMINANCE);
 
@@ -123,7 +162,7 @@ impl ExtMetadataBlockInfo for ExtMetadataBlockLevel10 {
         if self.target_primary_index == 255 {
             fields_flag |= last_field_flag;
         }
-        
+
         return fields_flag;
     }
 
@@ -8,6 +8,9 @@ use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
 
 const MAX_WHITEPOINT_VALUE: u8 = 15;
 
+/// Defined content types: cinema, games, sports and user generated content
+pub const DEFINED_CONTENT_TYPES: std::ops::RangeInclusive<u8> = 1..=4;
+
 /// Content type metadata level
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
@@ -60,13 +63,26 @@ impl ExtMetadataBlockLevel11 {
 
     pub fn validate(&self) -> Result<()> {
         ensure!(self.content_type <= 15);
-        ensure!(self.whitepoint <= 15);
+        ensure!(self.whitepoint <= MAX_WHITEPOINT_VALUE);
         ensure!(self.reserved_byte2 == 0);
         ensure!(self.reserved_byte3 == 0);
 
         Ok(())
     }
 
+    /// Checked only for generated blocks: the reference mode is set for a defined content type,
+    /// as the display would otherwise have no intent to enforce.
+    /// Parsed RPUs are not rejected, the combination not being restricted by the bitstream syntax.
+    pub fn validate_reference_mode(&self) -> Result<()> {
+        ensure!(
+            !self.reference_mode_flag || DEFINED_CONTENT_TYPES.contains(&self.content_type),
+            "L11: reference mode is invalid for content type {}",
+            self.content_type
+        );
+
+        Ok(())
+    }
+
     /// Cinema, reference mode, D65 whitepoint
     pub fn default_reference_cinema() -> Self {
         Self {
@@ -4,13 +4,16 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
+use crate::rpu::trims::apply_trim;
 use crate::utils::nits_to_pq;
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
+use super::{
+    clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo, ExtMetadataBlockLevel1, MAX_12_BIT_VALUE,
+};
 
 /// Creative intent trim passes per target display peak brightness
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct ExtMetadataBlockLevel2 {
     pub target_max_pq: u16,
@@ -19,6 +22,7 @@ pub struct ExtMetadataBlockLevel2 {
     pub trim_power: u16,
     pub trim_chroma_weight: u16,
     pub trim_saturation_gain: u16,
+    /// 13 bits signed, from -1 to 4095
     pub ms_weight: i16,
 }
 
@@ -67,12 +71,146 @@ impl ExtMetadataBlockLevel2 {
         Ok(())
     }
 
+    /// Clamps the values to 12 bits, and the `ms_weight` to [-1, 4095].
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+        let max = MAX_12_BIT_VALUE;
+
+        clamp_field(
+            &mut corrections,
+            2,
+            "target_max_pq",
+            &mut self.target_max_pq,
+            max,
+        );
+        clamp_field(&mut corrections, 2, "trim_slope", &mut self.trim_slope, max);
+        clamp_field(
+            &mut corrections,
+            2,
+            "trim_offset",
+            &mut self.trim_offset,
+            max,
+        );
+        clamp_field(&mut corrections, 2, "trim_power", &mut self.trim_power, max);
+        clamp_field(
+            &mut corrections,
+            2,
+            "trim_chroma_weight",
+            &mut self.trim_chroma_weight,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            2,
+            "trim_saturation_gain",
+            &mut self.trim_saturation_gain,
+            max,
+        );
+
+        let ms_weight = self.ms_weight.clamp(-1, max as i16);
+        if ms_weight != self.ms_weight {
+            corrections.push(format!(
+                "L2: ms_weight {} clamped to {}",
+                self.ms_weight, ms_weight
+            ));
+            self.ms_weight = ms_weight;
+        }
+
+        corrections
+    }
+
     pub fn from_nits(target_nits: u16) -> ExtMetadataBlockLevel2 {
         ExtMetadataBlockLevel2 {
             target_max_pq: (nits_to_pq(target_nits.into()) * 4095.0).round() as u16,
             ..Default::default()
         }
     }
+
+    /// SDR (100 nits) trim derived from the L1 metadata of a shot.
+    /// The slope maps the L1 `max_pq` to the 100 nits target, the offset and power are neutral.
+    /// Shots not exceeding 100 nits are left neutral, the trim only compresses the highlights.
+    /// The slope is limited to its minimum of 0.5.
+    pub fn sdr_trim_from_level1(level1: &ExtMetadataBlockLevel1) -> Self {
+        let mut level2 = Self::from_nits(100);
+
+        if level1.max_pq > level2.target_max_pq {
+            let slope = level2.target_max_pq as f64 / level1.max_pq as f64;
+            level2.trim_slope = ((slope - 0.5) * 4096.0).round().max(0.0) as u16;
+        }
+
+        level2
+    }
+
+    /// Trim fitted to the measured response of a target display.
+    ///
+    /// The samples are `(input, output)` pairs normalized to [0.0, 1.0], as PQ:
+    /// the signal sent to the display, and the measured output.
+    /// The trim maps the input to `(input * slope + offset) ^ power`.
+    ///
+    /// Every 12 bit power is tried: the slope and offset are fitted by least squares
+    /// on `output ^ (1 / power)`, and the trim with the lowest squared error is kept.
+    /// A response following the model is fitted within 1/4096, the 12 bit precision of the trims.
+    ///
+    /// The trim is neutral with less than two distinct inputs.
+    pub fn from_eotf_samples(target_nits: u16, samples: &[(f32, f32)]) -> Self {
+        let mut level2 = Self::from_nits(target_nits);
+
+        let first_input = samples.first().map(|(x, _)| *x);
+        if samples.iter().all(|(x, _)| Some(*x) == first_input) {
+            return level2;
+        }
+
+        let mut best_error = f64::INFINITY;
+
+        for power_code in 0..=MAX_12_BIT_VALUE {
+            let power = power_code as f64 / 4096.0 + 0.5;
+
+            let points: Vec<(f64, f64)> = samples
+                .iter()
+                .map(|&(x, y)| (x as f64, (y.max(0.0) as f64).powf(1.0 / power)))
+                .collect();
+
+            let (slope, offset) = least_squares_line(&points);
+
+            let slope_code = ((slope - 0.5) * 4096.0).round().clamp(0.0, 4095.0) as u16;
+            let offset_code = ((offset + 0.5) * 4096.0).round().clamp(0.0, 4095.0) as u16;
+
+            let error: f64 = samples
+                .iter()
+                .map(|&(x, y)| {
+                    let trimmed = apply_trim(x as f64, slope_code, offset_code, power_code);
+                    (trimmed - y as f64).powi(2)
+                })
+                .sum();
+
+            if error < best_error {
+                best_error = error;
+
+                level2.trim_slope = slope_code;
+                level2.trim_offset = offset_code;
+                level2.trim_power = power_code;
+            }
+        }
+
+        level2
+    }
+}
+
+/// Slope and offset of the line fitting the points, the inputs must not all be equal
+fn least_squares_line(points: &[(f64, f64)]) -> (f64, f64) {
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+
+    let covariance: f64 = points
+        .iter()
+        .map(|(x, y)| (x - mean_x) * (y - mean_y))
+        .sum();
+    let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+
+    let slope = covariance / variance;
+
+    (slope, mean_y - slope * mean_x)
 }
 
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel2 {
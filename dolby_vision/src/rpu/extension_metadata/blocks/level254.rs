@@ -36,6 +36,19 @@ impl ExtMetadataBlockLevel254 {
             dm_version_index: 2,
         }
     }
+
+    /// Metadata levels required in the CM v4.0 DM data for the DM mode.
+    /// `None` when the DM mode has no known block set.
+    ///
+    /// - Mode 0, default processing: every CM v4.0 level can be present, L254 is required.
+    ///
+    /// The other DM modes have no published definition, so their block set is not checked.
+    pub fn dm_mode_required_levels(&self) -> Option<&'static [u8]> {
+        match self.dm_mode {
+            0 => Some(&[254]),
+            _ => None,
+        }
+    }
 }
 
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel254 {
@@ -4,7 +4,16 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
+use crate::rpu::trims::apply_trim;
+
+use super::{
+    clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo, ExtMetadataBlockLevel1,
+    ExtMetadataBlockLevel8, MAX_12_BIT_VALUE,
+};
+
+/// Offset value for no change
+/// cbindgen:ignore
+pub const L3_NEUTRAL_OFFSET: u16 = 2048;
 
 /// Level 1 offsets.
 #[repr(C)]
@@ -42,6 +51,84 @@ impl ExtMetadataBlockLevel3 {
 
         Ok(())
     }
+
+    /// Clamps the offsets to 12 bits.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+        let max = MAX_12_BIT_VALUE;
+
+        clamp_field(
+            &mut corrections,
+            3,
+            "min_pq_offset",
+            &mut self.min_pq_offset,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            3,
+            "max_pq_offset",
+            &mut self.max_pq_offset,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            3,
+            "avg_pq_offset",
+            &mut self.avg_pq_offset,
+            max,
+        );
+
+        corrections
+    }
+
+    /// Applies the offsets to the L1 values, clamped to the 12 bit PQ range.
+    ///
+    /// The offsets are coded with a bias of 2048, in units of 1/2048 of the PQ range.
+    pub fn apply_to(&self, level1: &ExtMetadataBlockLevel1) -> ExtMetadataBlockLevel1 {
+        ExtMetadataBlockLevel1 {
+            min_pq: apply_offset(level1.min_pq, self.min_pq_offset),
+            max_pq: apply_offset(level1.max_pq, self.max_pq_offset),
+            avg_pq: apply_offset(level1.avg_pq, self.avg_pq_offset),
+        }
+    }
+
+    /// Offsets representing the effect of a L8 trim on the L1 values.
+    ///
+    /// Every L1 value, normalized to [0.0, 1.0], is trimmed to `(value * slope + offset) ^ power`.
+    /// The offset is the difference between the trimmed and original values, coded with the bias.
+    /// Only the slope, offset and power of the trim are used.
+    pub fn from_level1_trim(
+        level1: &ExtMetadataBlockLevel1,
+        level8: &ExtMetadataBlockLevel8,
+    ) -> ExtMetadataBlockLevel3 {
+        let trim_offset = |pq: u16| {
+            let value = pq as f64 / MAX_12_BIT_VALUE as f64;
+            let trimmed = apply_trim(
+                value,
+                level8.trim_slope,
+                level8.trim_offset,
+                level8.trim_power,
+            );
+
+            let offset = ((trimmed - value) * 2048.0).round() + L3_NEUTRAL_OFFSET as f64;
+
+            offset.clamp(0.0, MAX_12_BIT_VALUE as f64) as u16
+        };
+
+        ExtMetadataBlockLevel3 {
+            min_pq_offset: trim_offset(level1.min_pq),
+            max_pq_offset: trim_offset(level1.max_pq),
+            avg_pq_offset: trim_offset(level1.avg_pq),
+        }
+    }
+}
+
+fn apply_offset(pq: u16, offset: u16) -> u16 {
+    let delta = (offset as f64 - L3_NEUTRAL_OFFSET as f64) * MAX_12_BIT_VALUE as f64 / 2048.0;
+    let pq = (pq as f64 + delta).round();
+
+    pq.clamp(0.0, MAX_12_BIT_VALUE as f64) as u16
 }
 
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel3 {
@@ -4,9 +4,7 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
-
-const MAX_RESOLUTION_13_BITS: u16 = 8191;
+use super::{clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo, MAX_13_BIT_VALUE};
 
 /// Active area of the picture (letterbox, aspect ratio)
 #[repr(C)]
@@ -41,14 +39,51 @@ impl ExtMetadataBlockLevel5 {
     }
 
     pub fn validate(&self) -> Result<()> {
-        ensure!(self.active_area_left_offset <= MAX_RESOLUTION_13_BITS);
-        ensure!(self.active_area_right_offset <= MAX_RESOLUTION_13_BITS);
-        ensure!(self.active_area_top_offset <= MAX_RESOLUTION_13_BITS);
-        ensure!(self.active_area_bottom_offset <= MAX_RESOLUTION_13_BITS);
+        ensure!(self.active_area_left_offset <= MAX_13_BIT_VALUE);
+        ensure!(self.active_area_right_offset <= MAX_13_BIT_VALUE);
+        ensure!(self.active_area_top_offset <= MAX_13_BIT_VALUE);
+        ensure!(self.active_area_bottom_offset <= MAX_13_BIT_VALUE);
 
         Ok(())
     }
 
+    /// Clamps the offsets to 13 bits.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+        let max = MAX_13_BIT_VALUE;
+
+        clamp_field(
+            &mut corrections,
+            5,
+            "active_area_left_offset",
+            &mut self.active_area_left_offset,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            5,
+            "active_area_right_offset",
+            &mut self.active_area_right_offset,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            5,
+            "active_area_top_offset",
+            &mut self.active_area_top_offset,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            5,
+            "active_area_bottom_offset",
+            &mut self.active_area_bottom_offset,
+            max,
+        );
+
+        corrections
+    }
+
     pub fn get_offsets(&self) -> (u16, u16, u16, u16) {
         (
             self.active_area_left_offset,
@@ -58,6 +93,47 @@ impl ExtMetadataBlockLevel5 {
         )
     }
 
+    /// Offsets as signed values (left, right, top, bottom), to compute deltas.
+    pub fn get_offsets_signed(&self) -> (i32, i32, i32, i32) {
+        (
+            self.active_area_left_offset as i32,
+            self.active_area_right_offset as i32,
+            self.active_area_top_offset as i32,
+            self.active_area_bottom_offset as i32,
+        )
+    }
+
+    /// Change of the offsets from `self` to `other` (left, right, top, bottom).
+    /// A positive delta means the active area shrinks on that side.
+    pub fn offsets_delta(&self, other: &Self) -> (i32, i32, i32, i32) {
+        let (left, right, top, bottom) = self.get_offsets_signed();
+        let (other_left, other_right, other_top, other_bottom) = other.get_offsets_signed();
+
+        (
+            other_left - left,
+            other_right - right,
+            other_top - top,
+            other_bottom - bottom,
+        )
+    }
+
+    /// Active image area in pixels (x, y, width, height), for a frame of `resolution`.
+    /// Offsets exceeding the frame result in an empty area.
+    pub fn active_rect(&self, resolution: (u16, u16)) -> (u16, u16, u16, u16) {
+        let (width, height) = resolution;
+
+        let x = self.active_area_left_offset.min(width);
+        let y = self.active_area_top_offset.min(height);
+
+        let horizontal = self.active_area_left_offset as u32 + self.active_area_right_offset as u32;
+        let vertical = self.active_area_top_offset as u32 + self.active_area_bottom_offset as u32;
+
+        let w = (width as u32).saturating_sub(horizontal) as u16;
+        let h = (height as u32).saturating_sub(vertical) as u16;
+
+        (x, y, w, h)
+    }
+
     pub fn get_offsets_vec(&self) -> Vec<u16> {
         vec![
             self.active_area_left_offset,
@@ -74,6 +150,24 @@ impl ExtMetadataBlockLevel5 {
         self.active_area_bottom_offset = bottom;
     }
 
+    /// Whether the opposing offsets are equal, as expected for centered content.
+    pub fn is_symmetric(&self) -> bool {
+        self.active_area_left_offset == self.active_area_right_offset
+            && self.active_area_top_offset == self.active_area_bottom_offset
+    }
+
+    /// Averages the opposing offsets, for centered content with mismatched offsets.
+    /// The size of the active area is kept: an odd total puts the extra line on the right/bottom.
+    pub fn symmetrize(&mut self) {
+        let horizontal = self.active_area_left_offset as u32 + self.active_area_right_offset as u32;
+        let vertical = self.active_area_top_offset as u32 + self.active_area_bottom_offset as u32;
+
+        self.active_area_left_offset = (horizontal / 2) as u16;
+        self.active_area_right_offset = (horizontal - horizontal / 2) as u16;
+        self.active_area_top_offset = (vertical / 2) as u16;
+        self.active_area_bottom_offset = (vertical - vertical / 2) as u16;
+    }
+
     pub fn crop(&mut self) {
         self.active_area_left_offset = 0;
         self.active_area_right_offset = 0;
@@ -89,6 +183,38 @@ impl ExtMetadataBlockLevel5 {
             active_area_bottom_offset: bottom,
         }
     }
+
+    /// Builds the offsets from black bars measured in pixels.
+    /// `resolution` is the (width, height) of the frame.
+    pub fn from_detected_bars(
+        resolution: (u16, u16),
+        top: u16,
+        bottom: u16,
+        left: u16,
+        right: u16,
+    ) -> Result<Self> {
+        let (width, height) = resolution;
+
+        ensure!(
+            (left as u32 + right as u32) < (width as u32),
+            "L5: horizontal bars ({}, {}) exceed the frame width {}",
+            left,
+            right,
+            width
+        );
+        ensure!(
+            (top as u32 + bottom as u32) < (height as u32),
+            "L5: vertical bars ({}, {}) exceed the frame height {}",
+            top,
+            bottom,
+            height
+        );
+
+        let level5 = Self::from_offsets(left, right, top, bottom);
+        level5.validate()?;
+
+        Ok(level5)
+    }
 }
 
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel5 {
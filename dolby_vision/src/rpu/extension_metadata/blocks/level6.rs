@@ -4,7 +4,7 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
+use super::{clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo};
 
 /// cbindgen:ignore
 pub const MAX_PQ_LUMINANCE: u16 = 10_000;
@@ -21,6 +21,34 @@ pub struct ExtMetadataBlockLevel6 {
 }
 
 impl ExtMetadataBlockLevel6 {
+    /// Builds the block from luminances in nits.
+    /// The minimum mastering luminance is coded in units of 0.0001 nits.
+    pub fn from_nits(
+        max_display_mastering_luminance: u16,
+        min_display_mastering_luminance: f32,
+        max_content_light_level: u16,
+        max_frame_average_light_level: u16,
+    ) -> Self {
+        Self {
+            max_display_mastering_luminance,
+            min_display_mastering_luminance: Self::min_luminance_from_nits(
+                min_display_mastering_luminance,
+            ),
+            max_content_light_level,
+            max_frame_average_light_level,
+        }
+    }
+
+    /// Converts a minimum mastering luminance in nits to its coded value, in 0.0001 nits.
+    pub fn min_luminance_from_nits(nits: f32) -> u16 {
+        (nits * 10000.0).round().clamp(0.0, u16::MAX as f32) as u16
+    }
+
+    /// Converts a coded minimum mastering luminance back to nits.
+    pub fn min_luminance_to_nits(&self) -> f32 {
+        self.min_display_mastering_luminance as f32 / 10000.0
+    }
+
     pub fn parse(reader: &mut BitVecReader) -> ExtMetadataBlock {
         ExtMetadataBlock::Level6(Self {
             max_display_mastering_luminance: reader.get_n(16),
@@ -50,6 +78,54 @@ impl ExtMetadataBlockLevel6 {
         Ok(())
     }
 
+    /// Clamps the values to 10 000 nits, and the MaxFALL to the MaxCLL when known.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+        let max = MAX_PQ_LUMINANCE;
+
+        clamp_field(
+            &mut corrections,
+            6,
+            "max_display_mastering_luminance",
+            &mut self.max_display_mastering_luminance,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            6,
+            "min_display_mastering_luminance",
+            &mut self.min_display_mastering_luminance,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            6,
+            "max_content_light_level",
+            &mut self.max_content_light_level,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            6,
+            "max_frame_average_light_level",
+            &mut self.max_frame_average_light_level,
+            max,
+        );
+
+        // A MaxCLL of 0 means unknown
+        if self.max_content_light_level > 0 {
+            clamp_field(
+                &mut corrections,
+                6,
+                "max_frame_average_light_level",
+                &mut self.max_frame_average_light_level,
+                self.max_content_light_level,
+            );
+        }
+
+        corrections
+    }
+
     pub fn source_meta_from_l6(&self) -> (u16, u16) {
         let mdl_min = self.min_display_mastering_luminance;
         let mdl_max = self.max_display_mastering_luminance;
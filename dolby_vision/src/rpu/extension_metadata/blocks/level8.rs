@@ -2,15 +2,16 @@ use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
+use super::{clamp_field, ExtMetadataBlock, ExtMetadataBlockInfo, MAX_12_BIT_VALUE};
 
 /// Creative intent trim passes per target display peak brightness
 /// For CM v4.0, L8 metadata only is present and used to compute L2
 #[repr(C)]
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize))]
+#[cfg_attr(feature = "serde_feature", serde(default))]
 pub struct ExtMetadataBlockLevel8 {
     pub target_display_index: u8,
     pub trim_slope: u16,
@@ -18,6 +19,7 @@ pub struct ExtMetadataBlockLevel8 {
     pub trim_power: u16,
     pub trim_chroma_weight: u16,
     pub trim_saturation_gain: u16,
+    /// 12 bits unsigned, unlike the L2 MS weight
     pub ms_weight: u16,
     pub target_mid_contrast: u16,
     pub clip_trim: u16,
@@ -40,7 +42,7 @@ impl Serialize for ExtMetadataBlockLevel8 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
-    { 
+    {
         let mut state = serializer.serialize_struct("ExtMetadataBlockLevel8", 21)?;
         state.serialize_field("target_display_index", &self.target_display_index)?;
         state.serialize_field("trim_slope", &self.trim_slope)?;
@@ -73,7 +75,7 @@ impl Serialize for ExtMetadataBlockLevel8 {
             state.serialize_field("hue_vector_field4", &self.hue_vector_field4)?;
             state.serialize_field("hue_vector_field5", &self.hue_vector_field5)?;
         }
-        
+
         state.end()
     }
 }
@@ -168,6 +170,46 @@ impl ExtMetadataBlockLevel8 {
 
         Ok(())
     }
+
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let mut corrections = Vec::new();
+        let max = MAX_12_BIT_VALUE;
+
+        clamp_field(&mut corrections, 8, "trim_slope", &mut self.trim_slope, max);
+        clamp_field(
+            &mut corrections,
+            8,
+            "trim_offset",
+            &mut self.trim_offset,
+            max,
+        );
+        clamp_field(&mut corrections, 8, "trim_power", &mut self.trim_power, max);
+        clamp_field(
+            &mut corrections,
+            8,
+            "trim_chroma_weight",
+            &mut self.trim_chroma_weight,
+            max,
+        );
+        clamp_field(
+            &mut corrections,
+            8,
+            "trim_saturation_gain",
+            &mut self.trim_saturation_gain,
+            max,
+        );
+        clamp_field(&mut corrections, 8, "ms_weight", &mut self.ms_weight, max);
+        clamp_field(
+            &mut corrections,
+            8,
+            "target_mid_contrast",
+            &mut self.target_mid_contrast,
+            max,
+        );
+        clamp_field(&mut corrections, 8, "clip_trim", &mut self.clip_trim, max);
+
+        corrections
+    }
 }
 
 impl ExtMetadataBlockInfo for ExtMetadataBlockLevel8 {
@@ -185,21 +227,21 @@ impl ExtMetadataBlockInfo for ExtMetadataBlockLevel8 {
         let mut fields_flag = 0;
         let default: Self = Default::default();
         if self.hue_vector_field0 != default.hue_vector_field0
-        || self.hue_vector_field1 != default.hue_vector_field1
-        || self.hue_vector_field2 != default.hue_vector_field2
-        || self.hue_vector_field3 != default.hue_vector_field3
-        || self.hue_vector_field4 != default.hue_vector_field4
-        || self.hue_vector_field5 != default.hue_vector_field5
-        { 
+            || self.hue_vector_field1 != default.hue_vector_field1
+            || self.hue_vector_field2 != default.hue_vector_field2
+            || self.hue_vector_field3 != default.hue_vector_field3
+            || self.hue_vector_field4 != default.hue_vector_field4
+            || self.hue_vector_field5 != default.hue_vector_field5
+        {
             fields_flag |= last_field_flag >> index;
         };
         index += 1;
         if self.saturation_vector_field0 != default.saturation_vector_field0
-        || self.saturation_vector_field1 != default.saturation_vector_field1
-        || self.saturation_vector_field2 != default.saturation_vector_field2
-        || self.saturation_vector_field3 != default.saturation_vector_field3
-        || self.saturation_vector_field4 != default.saturation_vector_field4
-        || self.saturation_vector_field5 != default.saturation_vector_field5
+            || self.saturation_vector_field1 != default.saturation_vector_field1
+            || self.saturation_vector_field2 != default.saturation_vector_field2
+            || self.saturation_vector_field3 != default.saturation_vector_field3
+            || self.saturation_vector_field4 != default.saturation_vector_field4
+            || self.saturation_vector_field5 != default.saturation_vector_field5
         {
             fields_flag |= last_field_flag >> index;
         };
@@ -211,7 +253,7 @@ impl ExtMetadataBlockInfo for ExtMetadataBlockLevel8 {
         if self.target_mid_contrast != default.target_mid_contrast {
             fields_flag |= last_field_flag >> index;
         };
-        
+
         return fields_flag;
     }
 
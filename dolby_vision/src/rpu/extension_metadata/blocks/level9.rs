@@ -2,14 +2,37 @@ use anyhow::Result;
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::{Deserialize, Serialize, Serializer, ser::SerializeStruct};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 
-use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
+use super::{
+    ExtMetadataBlock, ExtMetadataBlockInfo, PREDEFINED_COLORSPACE_PRIMARIES,
+    PREDEFINED_REALDEVICE_PRIMARIES,
+};
+
+/// Maximum difference of the chromaticity coordinates to match a predefined index
+pub const PRIMARIES_TOLERANCE: f64 = 0.001;
+
+/// Predefined color spaces, as `PREDEFINED_COLORSPACE_PRIMARIES` index
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+pub enum ColorPrimaries {
+    DciP3D65 = 0,
+    Bt709 = 1,
+    Bt2020 = 2,
+    Bt601Ntsc = 3,
+    Bt601Pal = 4,
+    DciP3 = 5,
+    Aces = 6,
+    SGamut = 7,
+    SGamut3Cine = 8,
+}
 
 /// Source/mastering display color primaries
 #[repr(C)]
 #[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize))]
+#[cfg_attr(feature = "serde_feature", serde(default))]
 pub struct ExtMetadataBlockLevel9 {
     pub source_primary_index: u8,
     pub source_primary_red_x: u16,
@@ -27,13 +50,13 @@ impl Serialize for ExtMetadataBlockLevel9 {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
-    { 
+    {
         let mut state = serializer.serialize_struct("ExtMetadataBlockLevel9", 9)?;
         state.serialize_field("source_primary_index", &self.source_primary_index)?;
 
         if self.source_primary_index == 255 {
             state.serialize_field("source_primary_red_x", &self.source_primary_red_x)?;
-            state.serialize_field("source_primary_red_y", &self.source_primary_white_y)?;
+            state.serialize_field("source_primary_red_y", &self.source_primary_red_y)?;
             state.serialize_field("source_primary_green_x", &self.source_primary_green_x)?;
             state.serialize_field("source_primary_green_y", &self.source_primary_green_y)?;
             state.serialize_field("source_primary_blue_x", &self.source_primary_blue_x)?;
@@ -67,6 +90,71 @@ impl ExtMetadataBlockLevel9 {
         return ExtMetadataBlock::Level9(block);
     }
 
+    pub fn from_named_gamut(gamut: ColorPrimaries) -> Self {
+        Self {
+            source_primary_index: gamut.index(),
+            ..Default::default()
+        }
+    }
+
+    /// Source primaries from the chromaticity coordinates of the red, green, blue and white points:
+    /// `[red_x, red_y, green_x, green_y, blue_x, blue_y, white_x, white_y]`.
+    ///
+    /// Uses the nearest predefined color space or real device index,
+    /// if every coordinate is within `PRIMARIES_TOLERANCE`.
+    /// Otherwise, the primaries are signaled as custom (index 255).
+    pub fn from_primaries(primaries: &[f64; 8]) -> Self {
+        if let Some(index) = nearest_predefined_primaries(primaries) {
+            return Self {
+                source_primary_index: index,
+                ..Default::default()
+            };
+        }
+
+        // Signed 16 bit, scaled by 32767
+        let p: Vec<u16> = primaries
+            .iter()
+            .map(|v| (v * 32767.0).round() as i16 as u16)
+            .collect();
+
+        Self {
+            source_primary_index: 255,
+            source_primary_red_x: p[0],
+            source_primary_red_y: p[1],
+            source_primary_green_x: p[2],
+            source_primary_green_y: p[3],
+            source_primary_blue_x: p[4],
+            source_primary_blue_y: p[5],
+            source_primary_white_x: p[6],
+            source_primary_white_y: p[7],
+        }
+    }
+
+    /// Custom primaries matching a predefined index are replaced by the index,
+    /// and the primaries of a predefined index are reset to their default values.
+    /// The block is then written in its shortest form, as reparsed.
+    pub fn canonicalize(&mut self) {
+        if self.source_primary_index == 255 {
+            let primaries = primaries_from_signed_values(&[
+                self.source_primary_red_x,
+                self.source_primary_red_y,
+                self.source_primary_green_x,
+                self.source_primary_green_y,
+                self.source_primary_blue_x,
+                self.source_primary_blue_y,
+                self.source_primary_white_x,
+                self.source_primary_white_y,
+            ]);
+
+            *self = Self::from_primaries(&primaries);
+        } else {
+            *self = Self {
+                source_primary_index: self.source_primary_index,
+                ..Default::default()
+            };
+        }
+    }
+
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         writer.write_n(&self.source_primary_index.to_be_bytes(), 8);
 
@@ -102,4 +190,73 @@ impl ExtMetadataBlockInfo for ExtMetadataBlockLevel9 {
         }
         return fields_flag;
     }
-}
\ No newline at end of file
+}
+
+/// Index of the nearest predefined color space or real device primaries,
+/// if every coordinate is within `PRIMARIES_TOLERANCE`.
+pub fn nearest_predefined_primaries(primaries: &[f64; 8]) -> Option<u8> {
+    let predefined = PREDEFINED_COLORSPACE_PRIMARIES
+        .iter()
+        .chain(PREDEFINED_REALDEVICE_PRIMARIES.iter());
+
+    nearest_primaries(primaries, predefined).map(|index| index as u8)
+}
+
+/// Position of the nearest primaries in `candidates`, within `PRIMARIES_TOLERANCE`
+fn nearest_primaries<'a>(
+    primaries: &[f64; 8],
+    candidates: impl Iterator<Item = &'a [f64; 8]>,
+) -> Option<usize> {
+    let max_difference = |predefined: &[f64; 8]| {
+        primaries
+            .iter()
+            .zip(predefined.iter())
+            .map(|(a, b)| (a - b).abs())
+            .fold(0.0, f64::max)
+    };
+
+    candidates
+        .map(max_difference)
+        .enumerate()
+        .filter(|(_, difference)| *difference <= PRIMARIES_TOLERANCE)
+        .fold(
+            None,
+            |nearest: Option<(usize, f64)>, (i, difference)| match nearest {
+                Some((_, nearest_difference)) if nearest_difference <= difference => nearest,
+                _ => Some((i, difference)),
+            },
+        )
+        .map(|(index, _)| index)
+}
+
+/// Chromaticity coordinates of custom primaries, signed 16 bit values scaled by 32767
+pub fn primaries_from_signed_values(values: &[u16; 8]) -> [f64; 8] {
+    let mut primaries = [0.0; 8];
+
+    for (primary, &value) in primaries.iter_mut().zip(values.iter()) {
+        *primary = value as i16 as f64 / 32767.0;
+    }
+
+    primaries
+}
+
+impl ColorPrimaries {
+    pub fn index(self) -> u8 {
+        self as u8
+    }
+
+    /// Chromaticity coordinates of the red, green, blue and white points
+    pub fn primaries(self) -> [f64; 8] {
+        PREDEFINED_COLORSPACE_PRIMARIES[self as usize]
+    }
+
+    /// L9 source primary index of the nearest real device primaries,
+    /// if every coordinate is within `PRIMARIES_TOLERANCE`.
+    ///
+    /// The real device indices follow the predefined color spaces,
+    /// so the first entry of `PREDEFINED_REALDEVICE_PRIMARIES` is index 9.
+    pub fn real_device_index(coords: &[f64; 8]) -> Option<u8> {
+        nearest_primaries(coords, PREDEFINED_REALDEVICE_PRIMARIES.iter())
+            .map(|index| (PREDEFINED_COLORSPACE_PRIMARIES.len() + index) as u8)
+    }
+}
@@ -27,7 +27,7 @@ pub use level4::ExtMetadataBlockLevel4;
 pub use level5::ExtMetadataBlockLevel5;
 pub use level6::ExtMetadataBlockLevel6;
 pub use level8::ExtMetadataBlockLevel8;
-pub use level9::ExtMetadataBlockLevel9;
+pub use level9::{ColorPrimaries, ExtMetadataBlockLevel9};
 pub use reserved::ReservedExtMetadataBlock;
 
 use super::WithExtMetadataBlocks;
@@ -35,30 +35,54 @@ use super::WithExtMetadataBlocks;
 /// cbindgen:ignore
 pub const MAX_12_BIT_VALUE: u16 = 4095;
 /// cbindgen:ignore
+pub const MAX_13_BIT_VALUE: u16 = 8191;
+/// cbindgen:ignore
 pub const PREDEFINED_COLORSPACE_PRIMARIES: &[[f64; 8]] = &[
-    [   0.68,   0.32,  0.265,   0.69,   0.15,   0.06,  0.3127,   0.329], //  0, DCI-P3 D65
-    [   0.64,   0.33,   0.30,   0.60,   0.15,   0.06,  0.3127,   0.329], //  1, BT.709
-    [  0.708,  0.292,  0.170,  0.797,  0.131,  0.046,  0.3127,   0.329], //  2, BT.2020
-    [   0.63,   0.34,   0.31,  0.595,  0.155,   0.07,  0.3127,   0.329], //  3, BT.601 NTSC / SMPTE-C
-    [   0.64,   0.33,   0.29,   0.60,   0.15,   0.06,  0.3127,   0.329], //  4, BT.601 PAL / BT.470 BG
-    [   0.68,   0.32,  0.265,   0.69,   0.15,   0.06,   0.314,   0.351], //  5, DCI-P3
-    [ 0.7347, 0.2653,    0.0,    1.0, 0.0001, -0.077, 0.32168, 0.33767], //  6, ACES
-    [   0.73,   0.28,   0.14,  0.855,   0.10,  -0.05,  0.3127,   0.329], //  7, S-Gamut
-    [  0.766,  0.275,  0.225,   0.80,  0.089, -0.087,  0.3127,   0.329], //  8, S-Gamut-3.Cine
-    ];
+    [0.68, 0.32, 0.265, 0.69, 0.15, 0.06, 0.3127, 0.329], //  0, DCI-P3 D65
+    [0.64, 0.33, 0.30, 0.60, 0.15, 0.06, 0.3127, 0.329],  //  1, BT.709
+    [0.708, 0.292, 0.170, 0.797, 0.131, 0.046, 0.3127, 0.329], //  2, BT.2020
+    [0.63, 0.34, 0.31, 0.595, 0.155, 0.07, 0.3127, 0.329], //  3, BT.601 NTSC / SMPTE-C
+    [0.64, 0.33, 0.29, 0.60, 0.15, 0.06, 0.3127, 0.329],  //  4, BT.601 PAL / BT.470 BG
+    [0.68, 0.32, 0.265, 0.69, 0.15, 0.06, 0.314, 0.351],  //  5, DCI-P3
+    [0.7347, 0.2653, 0.0, 1.0, 0.0001, -0.077, 0.32168, 0.33767], //  6, ACES
+    [0.73, 0.28, 0.14, 0.855, 0.10, -0.05, 0.3127, 0.329], //  7, S-Gamut
+    [0.766, 0.275, 0.225, 0.80, 0.089, -0.087, 0.3127, 0.329], //  8, S-Gamut-3.Cine
+];
 /// cbindgen:ignore
 pub const PREDEFINED_REALDEVICE_PRIMARIES: &[[f64; 8]] = &[
-    [  0.693,  0.304,  0.208,  0.761, 0.1467, 0.0527,  0.3127,   0.329],
-    [ 0.6867, 0.3085,  0.231,   0.69, 0.1489, 0.0638,  0.3127,   0.329],
-    [ 0.6781, 0.3189, 0.2365, 0.7048,  0.141, 0.0489,  0.3127,   0.329],
-    [   0.68,   0.32,  0.265,   0.69,   0.15,   0.06,  0.3127,   0.329],
-    [ 0.7042,  0.294, 0.2271,  0.725, 0.1416, 0.0516,  0.3127,   0.329],
-    [ 0.6745,  0.310, 0.2212, 0.7109,  0.152, 0.0619,  0.3127,   0.329],
-    [ 0.6805, 0.3191, 0.2522, 0.6702, 0.1397, 0.0554,  0.3127,   0.329],
-    [ 0.6838, 0.3085, 0.2709, 0.6378, 0.1478, 0.0589,  0.3127,   0.329],
-    [ 0.6753, 0.3193, 0.2636, 0.6835, 0.1521, 0.0627,  0.3127,   0.329],
-    [ 0.6981, 0.2898, 0.1814, 0.7189, 0.1517, 0.0567,  0.3127,   0.329],
-    ];
+    [0.693, 0.304, 0.208, 0.761, 0.1467, 0.0527, 0.3127, 0.329],
+    [0.6867, 0.3085, 0.231, 0.69, 0.1489, 0.0638, 0.3127, 0.329],
+    [0.6781, 0.3189, 0.2365, 0.7048, 0.141, 0.0489, 0.3127, 0.329],
+    [0.68, 0.32, 0.265, 0.69, 0.15, 0.06, 0.3127, 0.329],
+    [0.7042, 0.294, 0.2271, 0.725, 0.1416, 0.0516, 0.3127, 0.329],
+    [0.6745, 0.310, 0.2212, 0.7109, 0.152, 0.0619, 0.3127, 0.329],
+    [
+        0.6805, 0.3191, 0.2522, 0.6702, 0.1397, 0.0554, 0.3127, 0.329,
+    ],
+    [
+        0.6838, 0.3085, 0.2709, 0.6378, 0.1478, 0.0589, 0.3127, 0.329,
+    ],
+    [
+        0.6753, 0.3193, 0.2636, 0.6835, 0.1521, 0.0627, 0.3127, 0.329,
+    ],
+    [
+        0.6981, 0.2898, 0.1814, 0.7189, 0.1517, 0.0567, 0.3127, 0.329,
+    ],
+];
+
+/// Clamps `value` to `max`, recording the correction made.
+pub(crate) fn clamp_field(
+    corrections: &mut Vec<String>,
+    level: u8,
+    name: &str,
+    value: &mut u16,
+    max: u16,
+) {
+    if *value > max {
+        corrections.push(format!("L{}: {} {} clamped to {}", level, name, value, max));
+        *value = max;
+    }
+}
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
@@ -105,13 +129,13 @@ pub trait ExtMetadataBlockInfo {
                 bits = self.possible_required_bits()[count - i];
                 break;
             };
-        };
+        }
 
         return bits;
     }
 
     // Block levels with variable length should implement this
-    // 0b0001 means the first optional field in this block is modified, 
+    // 0b0001 means the first optional field in this block is modified,
     // 0b0010 for the second field, etc.
     fn modified_fields_flag(&self) -> u64 {
         0
@@ -123,17 +147,15 @@ pub trait ExtMetadataBlockInfo {
     }
 
     fn possible_bytes_size(&self) -> Vec<u64> {
-        return self.possible_required_bits()
-        .iter()
-        .map(|b| (b + b % 8) >> 3)
-        .collect()
+        return self
+            .possible_required_bits()
+            .iter()
+            .map(|b| (b + b % 8) >> 3)
+            .collect();
     }
 
     fn possible_bits_size(&self) -> Vec<u64> {
-        return self.possible_bytes_size()
-            .iter()
-            .map(|b| b * 8)
-            .collect()
+        return self.possible_bytes_size().iter().map(|b| b * 8).collect();
     }
 
     fn sort_key(&self) -> (u8, u16) {
@@ -244,6 +266,23 @@ impl ExtMetadataBlock {
         }
     }
 
+    pub fn modified_fields_flag(&self) -> u64 {
+        match self {
+            ExtMetadataBlock::Level1(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level2(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level3(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level4(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level5(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level6(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level8(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level9(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level10(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level11(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Level254(b) => b.modified_fields_flag(),
+            ExtMetadataBlock::Reserved(b) => b.modified_fields_flag(),
+        }
+    }
+
     pub fn level(&self) -> u8 {
         match self {
             ExtMetadataBlock::Level1(b) => b.level(),
@@ -295,6 +334,85 @@ impl ExtMetadataBlock {
         }
     }
 
+    /// Length in bytes of the payload actually written by the block, with the alignment bits.
+    /// Should be the same as `length_bytes`, the declared length.
+    pub fn written_length_bytes(&self) -> Result<u64> {
+        let mut writer = BitVecWriter::new();
+        self.write(&mut writer)?;
+
+        while !writer.is_aligned() {
+            writer.write(false);
+        }
+
+        Ok(writer.as_slice().len() as u64)
+    }
+
+    /// Clamps the block values to their valid ranges.
+    /// Returns the list of corrections made.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        match self {
+            ExtMetadataBlock::Level1(b) => b.sanitize(),
+            ExtMetadataBlock::Level2(b) => b.sanitize(),
+            ExtMetadataBlock::Level3(b) => b.sanitize(),
+            ExtMetadataBlock::Level5(b) => b.sanitize(),
+            ExtMetadataBlock::Level6(b) => b.sanitize(),
+            ExtMetadataBlock::Level8(b) => b.sanitize(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// Verifies that the size information of the block is consistent.
+    /// Meant to catch construction bugs, especially for variable length blocks.
+    pub fn self_check(&self) -> Result<()> {
+        let level = self.level();
+        let possible_required_bits = self.possible_required_bits();
+
+        ensure!(
+            !possible_required_bits.is_empty(),
+            "L{}: No possible required bits",
+            level
+        );
+
+        let max_fields_flag = 1 << (possible_required_bits.len() - 1);
+        let fields_flag = self.modified_fields_flag();
+
+        ensure!(
+            fields_flag < max_fields_flag,
+            "L{}: Modified fields flag {:#b} does not map to a valid length",
+            level,
+            fields_flag
+        );
+
+        let required_bits = self.required_bits();
+        ensure!(
+            possible_required_bits.contains(&required_bits),
+            "L{}: Required bits {} is not one of {:?}",
+            level,
+            required_bits,
+            possible_required_bits
+        );
+
+        let length_bytes = self.length_bytes();
+        let possible_length_bytes = self.possible_length_bytes();
+        ensure!(
+            possible_length_bytes.contains(&length_bytes),
+            "L{}: Length {} is not one of {:?}",
+            level,
+            length_bytes,
+            possible_length_bytes
+        );
+
+        ensure!(
+            required_bits <= self.length_bits(),
+            "L{}: Required bits {} exceed the block size of {} bits",
+            level,
+            required_bits,
+            self.length_bits()
+        );
+
+        Ok(())
+    }
+
     pub fn validate_correct_dm_data<T: WithExtMetadataBlocks>(&self) -> Result<()> {
         let level = self.level();
 
@@ -320,8 +438,7 @@ impl ExtMetadataBlock {
                 self.possible_length_bytes().contains(&expected_length)
             } else {
                 expected_length == self.length_bytes()
-            }
-            ,
+            },
             format!(
                 "{}: Invalid metadata block. Block level {} should have length {}",
                 T::VERSION,
@@ -331,15 +448,20 @@ impl ExtMetadataBlock {
         );
 
         self.validate_correct_dm_data::<T>()?;
-        
+
         let mut ext_block_use_bits = 0;
         if T::VARIABLE_LENGTH_BLOCK_LEVELS.contains(&level) {
             if self.possible_length_bytes().contains(&expected_length) {
-                let index = self.possible_length_bytes().iter().position(|b| b == &expected_length).unwrap();
-                ext_block_use_bits = self.possible_length_bits()[index] - self.possible_required_bits()[index];
+                let index = self
+                    .possible_length_bytes()
+                    .iter()
+                    .position(|b| b == &expected_length)
+                    .unwrap();
+                ext_block_use_bits =
+                    self.possible_length_bits()[index] - self.possible_required_bits()[index];
             }
         } else {
-                ext_block_use_bits = self.length_bits() - self.required_bits();
+            ext_block_use_bits = self.length_bits() - self.required_bits();
         }
 
         for _ in 0..ext_block_use_bits {
@@ -352,3 +474,75 @@ impl ExtMetadataBlock {
         Ok(())
     }
 }
+
+impl From<ExtMetadataBlockLevel1> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel1) -> Self {
+        ExtMetadataBlock::Level1(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel2> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel2) -> Self {
+        ExtMetadataBlock::Level2(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel3> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel3) -> Self {
+        ExtMetadataBlock::Level3(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel4> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel4) -> Self {
+        ExtMetadataBlock::Level4(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel5> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel5) -> Self {
+        ExtMetadataBlock::Level5(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel6> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel6) -> Self {
+        ExtMetadataBlock::Level6(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel8> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel8) -> Self {
+        ExtMetadataBlock::Level8(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel9> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel9) -> Self {
+        ExtMetadataBlock::Level9(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel10> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel10) -> Self {
+        ExtMetadataBlock::Level10(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel11> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel11) -> Self {
+        ExtMetadataBlock::Level11(block)
+    }
+}
+
+impl From<ExtMetadataBlockLevel254> for ExtMetadataBlock {
+    fn from(block: ExtMetadataBlockLevel254) -> Self {
+        ExtMetadataBlock::Level254(block)
+    }
+}
+
+impl From<ReservedExtMetadataBlock> for ExtMetadataBlock {
+    fn from(block: ReservedExtMetadataBlock) -> Self {
+        ExtMetadataBlock::Reserved(block)
+    }
+}
@@ -1,4 +1,8 @@
-use anyhow::{bail, Result};
+use anyhow::{ensure, Result};
+use std::any::Any;
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
 
 use bitvec::{order::Msb0, prelude::BitVec};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
@@ -7,6 +11,74 @@ use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 use serde::{Deserialize, Serialize};
 
 use super::{ExtMetadataBlock, ExtMetadataBlockInfo};
+use crate::rpu::extension_metadata::WithExtMetadataBlocks;
+use crate::utils::WarningSink;
+
+/// Typed data parsed from a vendor-specific reserved block.
+pub trait ReservedBlockData: Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+}
+
+/// Parser for vendor-specific data in a reserved block level.
+pub trait ReservedBlockParser: Send + Sync {
+    /// Whether the block payload carries the signature handled by the parser.
+    fn matches(&self, data: &[u8]) -> bool;
+
+    fn parse(&self, data: &[u8]) -> Result<Arc<dyn ReservedBlockData>>;
+}
+
+/// cbindgen:ignore
+pub const DEFINED_BLOCK_LEVELS: &[u8] = &[1, 2, 3, 4, 5, 6, 8, 9, 10, 11, 254];
+
+/// Parsers for reserved block levels, passed to the RPU parsing in `RpuParseOpts`.
+#[derive(Default, Clone)]
+pub struct ReservedBlockParsers {
+    parsers: HashMap<u8, Arc<dyn ReservedBlockParser>>,
+}
+
+impl ReservedBlockParsers {
+    /// Registers a parser for a reserved block level.
+    /// Blocks of a registered level are kept as raw bytes when the signature doesn't match.
+    pub fn register<P: ReservedBlockParser + 'static>(
+        &mut self,
+        level: u8,
+        parser: P,
+    ) -> Result<()> {
+        ensure!(
+            !DEFINED_BLOCK_LEVELS.contains(&level),
+            "Cannot register a parser for defined block level {}",
+            level
+        );
+
+        self.parsers.insert(level, Arc::new(parser));
+
+        Ok(())
+    }
+
+    pub fn contains(&self, level: u8) -> bool {
+        self.parsers.contains_key(&level)
+    }
+
+    pub fn get(&self, level: u8) -> Option<&dyn ReservedBlockParser> {
+        self.parsers.get(&level).map(|parser| parser.as_ref())
+    }
+}
+
+impl Debug for ReservedBlockParsers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut levels: Vec<&u8> = self.parsers.keys().collect();
+        levels.sort();
+
+        f.debug_struct("ReservedBlockParsers")
+            .field("levels", &levels)
+            .finish()
+    }
+}
+
+/// Whether the level is defined, but not allowed for the CM version of the `T` DM data.
+pub(crate) fn is_misplaced_block<T: WithExtMetadataBlocks>(level: u8) -> bool {
+    DEFINED_BLOCK_LEVELS.contains(&level) && !T::ALLOWED_BLOCK_LEVELS.contains(&level)
+}
 
 #[derive(Debug, Default, Clone)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
@@ -16,16 +88,39 @@ pub struct ReservedExtMetadataBlock {
 
     #[cfg_attr(
         feature = "serde_feature",
-        serde(serialize_with = "crate::utils::bitvec_ser_bits", skip_deserializing)
+        serde(
+            serialize_with = "crate::utils::bitvec_ser_bits",
+            deserialize_with = "crate::utils::bitvec_de_bits"
+        )
     )]
     pub data: BitVec<Msb0, u8>,
+
+    /// Data parsed by the parser of the block level, if any
+    #[cfg_attr(feature = "serde_feature", serde(skip))]
+    pub parsed: Option<Arc<dyn ReservedBlockData>>,
 }
 
 impl ReservedExtMetadataBlock {
+    /// Parses the block as raw bytes
     pub fn parse(
         ext_block_length: u64,
         ext_block_level: u8,
         reader: &mut BitVecReader,
+    ) -> Result<ExtMetadataBlock> {
+        Self::parse_with_parsers(
+            ext_block_length,
+            ext_block_level,
+            reader,
+            &ReservedBlockParsers::default(),
+        )
+    }
+
+    /// Parses the block, with the typed data of the parser for the level if it matches
+    pub fn parse_with_parsers(
+        ext_block_length: u64,
+        ext_block_level: u8,
+        reader: &mut BitVecReader,
+        parsers: &ReservedBlockParsers,
     ) -> Result<ExtMetadataBlock> {
         let bits = 8 * ext_block_length;
         let mut data = BitVec::new();
@@ -34,23 +129,53 @@ impl ReservedExtMetadataBlock {
             data.push(reader.get()?);
         }
 
+        let parsed = match parsers.get(ext_block_level) {
+            Some(parser) if parser.matches(data.as_raw_slice()) => {
+                Some(parser.parse(data.as_raw_slice())?)
+            }
+            _ => None,
+        };
+
         Ok(ExtMetadataBlock::Reserved(Self {
             ext_block_length,
             ext_block_level,
             data,
+            parsed,
         }))
     }
 
-    pub fn write(&self, _writer: &mut BitVecWriter) -> Result<()> {
-        bail!("Cannot write reserved block");
-        // self.data.iter().for_each(|b| writer.write(*b));
+    /// Parses a block of a level not allowed for the `T` DM data, see `RpuParseOpts`
+    pub(crate) fn parse_misplaced<T: WithExtMetadataBlocks>(
+        ext_block_length: u64,
+        ext_block_level: u8,
+        reader: &mut BitVecReader,
+        warnings: &WarningSink,
+    ) -> Result<ExtMetadataBlock> {
+        warnings.warn(&format!(
+            "{}: Invalid block level {}, kept as a reserved block",
+            T::VERSION,
+            ext_block_level
+        ));
+
+        Self::parse(ext_block_length, ext_block_level, reader)
+    }
+
+    pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
+        self.data.iter().for_each(|b| writer.write(*b));
+
+        Ok(())
+    }
+
+    /// Returns the data parsed by the parser of the block level, if it is of type `T`
+    pub fn parsed_data<T: 'static>(&self) -> Option<&T> {
+        self.parsed.as_ref()?.as_any().downcast_ref::<T>()
     }
 }
 
 impl ExtMetadataBlockInfo for ReservedExtMetadataBlock {
     // TODO: Level 255 is actually definded for DM debugging purposes, we may add it.
     fn level(&self) -> u8 {
-        0
+        self.ext_block_level
     }
 
     fn bytes_size(&self) -> u64 {
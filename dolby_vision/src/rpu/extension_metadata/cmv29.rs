@@ -5,6 +5,7 @@ use bitvec_helpers::bitvec_reader::BitVecReader;
 use serde::{Deserialize, Serialize};
 
 use super::WithExtMetadataBlocks;
+use crate::rpu::dovi_rpu::RpuParseOpts;
 use crate::rpu::extension_metadata::blocks::*;
 
 #[derive(Debug, Default, Clone)]
@@ -18,6 +19,7 @@ impl WithExtMetadataBlocks for CmV29DmData {
     const VERSION: &'static str = "CM v2.9";
     const ALLOWED_BLOCK_LEVELS: &'static [u8] = &[1, 2, 4, 5, 6];
     const VARIABLE_LENGTH_BLOCK_LEVELS: &'static [u8] = &[];
+    const REQUIRED_BLOCK_LEVELS: &'static [u8] = &[5, 6];
 
     fn set_num_ext_blocks(&mut self, num_ext_blocks: u64) {
         self.num_ext_blocks = num_ext_blocks;
@@ -35,11 +37,26 @@ impl WithExtMetadataBlocks for CmV29DmData {
         self.ext_metadata_blocks.as_mut()
     }
 
-    fn parse_block(&mut self, reader: &mut BitVecReader) -> Result<()> {
+    fn parse_block(&mut self, reader: &mut BitVecReader, opts: &RpuParseOpts) -> Result<()> {
         let ext_block_length = reader.get_ue()?;
         let ext_block_level = reader.get_n(8);
 
         let ext_metadata_block = match ext_block_level {
+            _ if opts.tolerate_misplaced_blocks
+                && reserved::is_misplaced_block::<Self>(ext_block_level) =>
+            {
+                // Reserved blocks are read entirely, there are no remaining bits
+                self.ext_metadata_blocks.push(
+                    reserved::ReservedExtMetadataBlock::parse_misplaced::<Self>(
+                        ext_block_length,
+                        ext_block_level,
+                        reader,
+                        &opts.warnings,
+                    )?,
+                );
+
+                return Ok(());
+            }
             1 => level1::ExtMetadataBlockLevel1::parse(reader),
             2 => level2::ExtMetadataBlockLevel2::parse(reader),
             4 => level4::ExtMetadataBlockLevel4::parse(reader),
@@ -50,6 +67,19 @@ impl WithExtMetadataBlocks for CmV29DmData {
                 Self::VERSION,
                 ext_block_level
             ),
+            _ if opts.reserved_block_parsers.contains(ext_block_level) => {
+                // Reserved blocks are read entirely, there are no remaining bits
+                self.ext_metadata_blocks.push(
+                    reserved::ReservedExtMetadataBlock::parse_with_parsers(
+                        ext_block_length,
+                        ext_block_level,
+                        reader,
+                        &opts.reserved_block_parsers,
+                    )?,
+                );
+
+                return Ok(());
+            }
             _ => {
                 ensure!(
                     false,
@@ -99,6 +129,7 @@ impl CmV29DmData {
 
         let invalid_blocks_count = blocks
             .iter()
+            .filter(|b| !matches!(b, ExtMetadataBlock::Reserved(_)))
             .filter(|b| !Self::ALLOWED_BLOCK_LEVELS.contains(&b.level()))
             .count();
 
@@ -5,6 +5,7 @@ use bitvec_helpers::bitvec_reader::BitVecReader;
 use serde::{Deserialize, Serialize};
 
 use super::WithExtMetadataBlocks;
+use crate::rpu::dovi_rpu::RpuParseOpts;
 use crate::rpu::extension_metadata::blocks::*;
 
 #[derive(Debug, Default, Clone)]
@@ -18,6 +19,7 @@ impl WithExtMetadataBlocks for CmV40DmData {
     const VERSION: &'static str = "CM v4.0";
     const ALLOWED_BLOCK_LEVELS: &'static [u8] = &[3, 8, 9, 10, 11, 254];
     const VARIABLE_LENGTH_BLOCK_LEVELS: &'static [u8] = &[8, 9, 10];
+    const REQUIRED_BLOCK_LEVELS: &'static [u8] = &[254];
 
     fn set_num_ext_blocks(&mut self, num_ext_blocks: u64) {
         self.num_ext_blocks = num_ext_blocks;
@@ -35,17 +37,45 @@ impl WithExtMetadataBlocks for CmV40DmData {
         self.ext_metadata_blocks.as_mut()
     }
 
-    fn parse_block(&mut self, reader: &mut BitVecReader) -> Result<()> {
+    fn parse_block(&mut self, reader: &mut BitVecReader, opts: &RpuParseOpts) -> Result<()> {
         let ext_block_length = reader.get_ue()?;
         let ext_block_level: u8 = reader.get_n(8);
 
         let ext_metadata_block = match ext_block_level {
+            _ if opts.tolerate_misplaced_blocks
+                && reserved::is_misplaced_block::<Self>(ext_block_level) =>
+            {
+                // Reserved blocks are read entirely, there are no remaining bits
+                self.ext_metadata_blocks.push(
+                    reserved::ReservedExtMetadataBlock::parse_misplaced::<Self>(
+                        ext_block_length,
+                        ext_block_level,
+                        reader,
+                        &opts.warnings,
+                    )?,
+                );
+
+                return Ok(());
+            }
             3 => level3::ExtMetadataBlockLevel3::parse(reader),
             8 => level8::ExtMetadataBlockLevel8::parse(ext_block_length, reader),
             9 => level9::ExtMetadataBlockLevel9::parse(ext_block_length, reader),
             10 => level10::ExtMetadataBlockLevel10::parse(ext_block_length, reader),
             11 => level11::ExtMetadataBlockLevel11::parse(reader),
             254 => level254::ExtMetadataBlockLevel254::parse(reader),
+            _ if opts.reserved_block_parsers.contains(ext_block_level) => {
+                // Reserved blocks are read entirely, there are no remaining bits
+                self.ext_metadata_blocks.push(
+                    reserved::ReservedExtMetadataBlock::parse_with_parsers(
+                        ext_block_length,
+                        ext_block_level,
+                        reader,
+                        &opts.reserved_block_parsers,
+                    )?,
+                );
+
+                return Ok(());
+            }
             _ => {
                 ensure!(
                     false,
@@ -112,6 +142,7 @@ impl CmV40DmData {
 
         let invalid_blocks_count = blocks
             .iter()
+            .filter(|b| !matches!(b, ExtMetadataBlock::Reserved(_)))
             .filter(|b| !Self::ALLOWED_BLOCK_LEVELS.contains(&b.level()))
             .count();
 
@@ -176,15 +207,39 @@ impl CmV40DmData {
             )
         );
 
+        // The target display of a L8 trim must be defined only once
+        for level8 in blocks.iter().filter_map(|b| match b {
+            ExtMetadataBlock::Level8(b) => Some(b),
+            _ => None,
+        }) {
+            let index = level8.target_display_index;
+
+            let mut level10_blocks = blocks.iter().filter_map(|b| match b {
+                ExtMetadataBlock::Level10(b) if b.target_display_index == index => Some(b),
+                _ => None,
+            });
+
+            if let Some(first) = level10_blocks.next() {
+                ensure!(
+                    level10_blocks.all(|b| b == first),
+                    "{}: L8 target display index {} is defined by conflicting L10 blocks",
+                    Self::VERSION,
+                    index
+                );
+            }
+        }
+
         Ok(())
     }
 
     pub fn new_with_l254() -> Self {
+        Self::new_with_custom_l254(&ExtMetadataBlockLevel254::cmv40_default())
+    }
+
+    pub fn new_with_custom_l254(level254: &ExtMetadataBlockLevel254) -> Self {
         Self {
             num_ext_blocks: 1,
-            ext_metadata_blocks: vec![ExtMetadataBlock::Level254(
-                ExtMetadataBlockLevel254::cmv40_default(),
-            )],
+            ext_metadata_blocks: vec![ExtMetadataBlock::Level254(level254.clone())],
         }
     }
 }
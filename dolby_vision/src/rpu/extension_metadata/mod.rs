@@ -1,4 +1,6 @@
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Error, Result};
+use std::convert::TryFrom;
+
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
@@ -13,6 +15,8 @@ pub use cmv40::CmV40DmData;
 
 use blocks::ExtMetadataBlock;
 
+use super::dovi_rpu::RpuParseOpts;
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 #[cfg_attr(feature = "serde_feature", serde(untagged))]
@@ -31,10 +35,13 @@ pub trait WithExtMetadataBlocks {
     const ALLOWED_BLOCK_LEVELS: &'static [u8];
     const VARIABLE_LENGTH_BLOCK_LEVELS: &'static [u8];
 
+    /// Levels checked by `validate_required_blocks`, none by default
+    const REQUIRED_BLOCK_LEVELS: &'static [u8] = &[];
+
     fn set_num_ext_blocks(&mut self, num_ext_blocks: u64);
     fn num_ext_blocks(&self) -> u64;
 
-    fn parse_block(&mut self, reader: &mut BitVecReader) -> Result<()>;
+    fn parse_block(&mut self, reader: &mut BitVecReader, opts: &RpuParseOpts) -> Result<()>;
     fn blocks_ref(&self) -> &Vec<ExtMetadataBlock>;
     fn blocks_mut(&mut self) -> &mut Vec<ExtMetadataBlock>;
 
@@ -58,6 +65,11 @@ pub trait WithExtMetadataBlocks {
             Self::VERSION
         );
 
+        // Preset target displays cannot be signaled
+        if let ExtMetadataBlock::Level10(b) = &meta {
+            b.validate()?;
+        }
+
         let blocks = self.blocks_mut();
         blocks.push(meta);
 
@@ -73,6 +85,40 @@ pub trait WithExtMetadataBlocks {
         self.update_extension_block_info();
     }
 
+    fn retain_blocks<F>(&mut self, f: F)
+    where
+        F: FnMut(&ExtMetadataBlock) -> bool,
+    {
+        let blocks = self.blocks_mut();
+        blocks.retain(f);
+
+        self.update_extension_block_info();
+    }
+
+    /// Ensures the blocks required for playback on every display are present.
+    fn validate_required_blocks(&self) -> Result<()> {
+        let blocks = self.blocks_ref();
+
+        for level in Self::REQUIRED_BLOCK_LEVELS {
+            ensure!(
+                blocks.iter().any(|b| b.level() == *level),
+                "{}: Missing required L{} metadata block",
+                Self::VERSION,
+                level
+            );
+        }
+
+        Ok(())
+    }
+
+    fn write_validated(&self, writer: &mut BitVecWriter, validate_required: bool) -> Result<()> {
+        if validate_required {
+            self.validate_required_blocks()?;
+        }
+
+        self.write(writer)
+    }
+
     fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
         let num_ext_blocks = self.num_ext_blocks();
 
@@ -105,6 +151,13 @@ pub trait WithExtMetadataBlocks {
 impl DmData {
     pub fn parse<T: WithExtMetadataBlocks + Default>(
         reader: &mut BitVecReader,
+    ) -> Result<Option<T>> {
+        DmData::parse_with_opts::<T>(reader, &RpuParseOpts::default())
+    }
+
+    pub fn parse_with_opts<T: WithExtMetadataBlocks + Default>(
+        reader: &mut BitVecReader,
+        opts: &RpuParseOpts,
     ) -> Result<Option<T>> {
         let mut meta = T::default();
         let num_ext_blocks = reader.get_ue()?;
@@ -119,7 +172,7 @@ impl DmData {
         }
 
         for _ in 0..num_ext_blocks {
-            meta.parse_block(reader)?;
+            meta.parse_block(reader, opts)?;
         }
 
         Ok(Some(meta))
@@ -138,4 +191,90 @@ impl DmData {
             DmData::V40(m) => m.validate(),
         }
     }
+
+    pub fn validate_required_blocks(&self) -> Result<()> {
+        match self {
+            DmData::V29(m) => m.validate_required_blocks(),
+            DmData::V40(m) => m.validate_required_blocks(),
+        }
+    }
+
+    pub fn as_v29(&self) -> Option<&CmV29DmData> {
+        match self {
+            DmData::V29(m) => Some(m),
+            DmData::V40(_) => None,
+        }
+    }
+
+    pub fn as_v40(&self) -> Option<&CmV40DmData> {
+        match self {
+            DmData::V29(_) => None,
+            DmData::V40(m) => Some(m),
+        }
+    }
+
+    /// Clamps the values of all the blocks to their valid ranges.
+    /// Returns the list of corrections made.
+    pub fn sanitize(&mut self) -> Vec<String> {
+        let blocks = match self {
+            DmData::V29(m) => m.blocks_mut(),
+            DmData::V40(m) => m.blocks_mut(),
+        };
+
+        blocks.iter_mut().flat_map(|b| b.sanitize()).collect()
+    }
+
+    /// Writes the DM data, first validating the required blocks if `validate_required` is set.
+    pub fn write_validated(
+        &self,
+        writer: &mut BitVecWriter,
+        validate_required: bool,
+    ) -> Result<()> {
+        match self {
+            DmData::V29(m) => m.write_validated(writer, validate_required),
+            DmData::V40(m) => m.write_validated(writer, validate_required),
+        }
+    }
+}
+
+impl From<CmV29DmData> for DmData {
+    fn from(meta: CmV29DmData) -> Self {
+        DmData::V29(meta)
+    }
+}
+
+impl From<CmV40DmData> for DmData {
+    fn from(meta: CmV40DmData) -> Self {
+        DmData::V40(meta)
+    }
+}
+
+impl TryFrom<DmData> for CmV29DmData {
+    type Error = Error;
+
+    fn try_from(dm_data: DmData) -> Result<Self> {
+        match dm_data {
+            DmData::V29(m) => Ok(m),
+            DmData::V40(_) => bail!(
+                "Expected {} DM data, found {}",
+                Self::VERSION,
+                CmV40DmData::VERSION
+            ),
+        }
+    }
+}
+
+impl TryFrom<DmData> for CmV40DmData {
+    type Error = Error;
+
+    fn try_from(dm_data: DmData) -> Result<Self> {
+        match dm_data {
+            DmData::V29(_) => bail!(
+                "Expected {} DM data, found {}",
+                Self::VERSION,
+                CmV29DmData::VERSION
+            ),
+            DmData::V40(m) => Ok(m),
+        }
+    }
 }
@@ -4,20 +4,26 @@ use std::{
     path::Path,
 };
 
-use anyhow::{ensure, Result};
+use anyhow::{bail, ensure, Result};
+use bitvec_helpers::bitvec_writer::BitVecWriter;
 
 #[cfg(feature = "serde_feature")]
 use serde::{Deserialize, Serialize};
 
 use crate::rpu::dovi_rpu::DoviRpu;
+use crate::utils::{nearest_standard_target, nits_to_pq, pq_to_nits, WarningSink};
 
+use super::extension_metadata::{CmV29DmData, WithExtMetadataBlocks};
 use super::{extension_metadata::blocks, vdr_dm_data::CmVersion};
 use blocks::*;
 
 const OUT_NAL_HEADER: &[u8] = &[0, 0, 0, 1];
 
+/// Per-frame analyzer, returning the min, avg and max PQ of a frame.
+pub type FrameAnalyzer = dyn Fn(usize) -> (u16, u16, u16);
+
 /// Generic generation config struct.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct GenerateConfig {
     /// Content mapping version
@@ -38,6 +44,22 @@ pub struct GenerateConfig {
     #[cfg_attr(feature = "serde_feature", serde(default))]
     pub source_max_pq: Option<u16>,
 
+    /// Bit depth of the base layer signaled in the RPU header, 10 or 12.
+    /// Defaults to 10.
+    #[cfg_attr(
+        feature = "serde_feature",
+        serde(default = "GenerateConfig::default_bit_depth")
+    )]
+    pub bl_bit_depth: u8,
+
+    /// Bit depth of the enhancement layer signaled in the RPU header, 10 or 12.
+    /// Defaults to 10.
+    #[cfg_attr(
+        feature = "serde_feature",
+        serde(default = "GenerateConfig::default_bit_depth")
+    )]
+    pub el_bit_depth: u8,
+
     /// Active area offsets.
     /// Defaults to zero offsets, should be present in RPU
     #[cfg_attr(feature = "serde_feature", serde(default))]
@@ -48,6 +70,22 @@ pub struct GenerateConfig {
     /// Defaults to 1000,0.0001
     pub level6: ExtMetadataBlockLevel6,
 
+    /// CM v4.0 L254 metadata: DM mode and version index.
+    /// Defaults to mode 0 (default processing) and version index 2.
+    ///
+    /// The levels required by the DM mode are checked for every generated RPU,
+    /// see `ExtMetadataBlockLevel254::dm_mode_required_levels`.
+    /// Modes without a known block set are generated with a warning, blocks unaltered.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub level254: Option<ExtMetadataBlockLevel254>,
+
+    /// CM v4.0 source primaries, added as a L9 block to every RPU.
+    /// Defaults to no L9 block, which means DCI-P3 D65 (index 0).
+    ///
+    /// L9 metadata from the default blocks or the shots replaces it.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub default_source_primaries: Option<ColorPrimaries>,
+
     /// List of metadata blocks to use for every RPU generated.
     ///
     /// Per-shot or per-frame metadata replaces the default
@@ -58,6 +96,59 @@ pub struct GenerateConfig {
     /// List of shots to generate.
     #[cfg_attr(feature = "serde_feature", serde(default))]
     pub shots: Vec<VideoShot>,
+
+    /// Add a L2 trim targeting 100 nits (SDR) to every frame without one.
+    /// The trim is derived from the L1 metadata of the frame,
+    /// see `ExtMetadataBlockLevel2::sdr_trim_from_level1`.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub sdr_fallback_trim: bool,
+
+    /// Add a L3 block to every CM v4.0 frame without one, with the effect of the L8 trim
+    /// of the first target display on the L1 metadata.
+    /// See `ExtMetadataBlockLevel3::from_level1_trim`.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub level3_from_level8: bool,
+
+    /// Source of the metadata levels not specified by a frame edit.
+    /// Defaults to inheriting from the shot.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub frame_edit_inheritance: FrameEditInheritance,
+
+    /// Only keep the metadata of the target display with this peak brightness, in nits.
+    /// Other L2/L8 trims and metadata levels besides L1, L5 and L6 are removed,
+    /// for displays reading a single target.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub single_target_nits: Option<u16>,
+
+    /// Write the L9 and L10 blocks in their shortest form, as authored by real devices:
+    /// custom primaries matching a predefined index are replaced by the index.
+    /// See `VdrDmData::canonicalize_blocks`.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub canonical_minimal: bool,
+
+    /// QC markers, as frame index and note.
+    /// Only used for reporting, they are not written to the RPUs.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub markers: Vec<(usize, String)>,
+
+    /// Receives the warnings emitted when generating.
+    #[cfg_attr(feature = "serde_feature", serde(skip))]
+    pub warnings: WarningSink,
+}
+
+/// Inheritance policy for the metadata levels a `ShotFrameEdit` doesn't specify.
+///
+/// Frames without edits always use the shot metadata.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
+pub enum FrameEditInheritance {
+    /// Unspecified levels use the shot metadata.
+    Shot,
+
+    /// Unspecified levels use the metadata of the previous frame edit in the shot,
+    /// carrying forward progressive changes.
+    /// The first edit of a shot inherits from the shot.
+    PreviousEdit,
 }
 
 /// Struct defining a video shot.
@@ -77,6 +168,11 @@ pub struct VideoShot {
     /// Number of frames contained in the shot.
     pub duration: usize,
 
+    /// Frame rate of the shot, in frames per second.
+    /// Only used to map timestamps to frame indices, for variable frame rate sequences.
+    #[cfg_attr(feature = "serde_feature", serde(default))]
+    pub frame_rate: Option<f64>,
+
     /// List of metadata blocks.
     #[cfg_attr(feature = "serde_feature", serde(default))]
     pub metadata_blocks: Vec<ExtMetadataBlock>,
@@ -98,23 +194,96 @@ pub struct ShotFrameEdit {
 }
 
 impl GenerateConfig {
+    /// Config for `length` frames all carrying the same L1, L5 and L6 metadata.
+    /// No shot structure or analysis, useful for placeholder tracks.
+    pub fn constant(
+        length: usize,
+        level1: ExtMetadataBlockLevel1,
+        level5: ExtMetadataBlockLevel5,
+        level6: ExtMetadataBlockLevel6,
+    ) -> Self {
+        Self {
+            length,
+            level5,
+            level6,
+            default_metadata_blocks: vec![ExtMetadataBlock::Level1(level1)],
+            shots: vec![VideoShot {
+                start: 0,
+                duration: length,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    fn default_bit_depth() -> u8 {
+        10
+    }
+
     pub fn generate_rpu_list(&self) -> Result<Vec<DoviRpu>> {
         let rpu = DoviRpu::profile81_config(self)?;
         let mut list = Vec::with_capacity(self.length);
 
+        let sdr_trim = ExtMetadataBlockLevel2::from_nits(100);
+
+        let level254 = self
+            .level254
+            .clone()
+            .unwrap_or_else(ExtMetadataBlockLevel254::cmv40_default);
+        let dm_mode_required_levels = match self.cm_version {
+            CmVersion::V29 => None,
+            CmVersion::V40 => {
+                let required_levels = level254.dm_mode_required_levels();
+
+                if required_levels.is_none() {
+                    self.warnings.warn(&format!(
+                        "L254: DM mode {} has no known block set, the blocks are not checked",
+                        level254.dm_mode
+                    ));
+                }
+
+                required_levels
+            }
+        };
+
         let shots_length: usize = self.shots.iter().map(|s| s.duration).sum();
 
-        ensure!(
-            self.length == shots_length,
-            format!(
-                "Config length is not the same as shots total duration. Config: {}, Shots: {}",
-                self.length, shots_length
-            )
-        );
+        if self.length != shots_length {
+            let discrepancy = if shots_length < self.length {
+                format!("{} frames missing", self.length - shots_length)
+            } else {
+                format!("{} frames too many", shots_length - self.length)
+            };
+
+            bail!(
+                "Config length is not the same as shots total duration. Config: {}, Shots: {} ({})",
+                self.length,
+                shots_length,
+                discrepancy
+            );
+        }
+
+        let shots_blocks = self.shots.iter().flat_map(|shot| {
+            let edits_blocks = shot
+                .frame_edits
+                .iter()
+                .flat_map(|e| e.metadata_blocks.iter());
+
+            shot.metadata_blocks.iter().chain(edits_blocks)
+        });
+
+        for block in self.default_metadata_blocks.iter().chain(shots_blocks) {
+            if let ExtMetadataBlock::Level11(level11) = block {
+                level11.validate_reference_mode()?;
+            }
+        }
 
         for shot in &self.shots {
             let end = shot.duration;
 
+            // Blocks of the previous frame edits in the shot, in order
+            let mut previous_edit_blocks: Vec<ExtMetadataBlock> = Vec::new();
+
             for i in 0..end {
                 let mut frame_rpu = rpu.clone();
 
@@ -132,19 +301,259 @@ impl GenerateConfig {
 
                     // Set different metadata for this frame
                     if let Some(edit) = frame_edit {
+                        if self.frame_edit_inheritance == FrameEditInheritance::PreviousEdit {
+                            for block in &previous_edit_blocks {
+                                vdr_dm_data.replace_metadata_block(block.clone())?;
+                            }
+
+                            previous_edit_blocks.extend(edit.metadata_blocks.iter().cloned());
+                        }
+
                         for block in &edit.metadata_blocks {
                             vdr_dm_data.replace_metadata_block(block.clone())?;
                         }
                     }
+
+                    if self.sdr_fallback_trim {
+                        let has_sdr_trim = vdr_dm_data.level_blocks_iter(2).any(|block| {
+                            matches!(block, ExtMetadataBlock::Level2(level2)
+                                if level2.target_max_pq == sdr_trim.target_max_pq)
+                        });
+
+                        if !has_sdr_trim {
+                            let level2 = match vdr_dm_data.get_block(1) {
+                                Some(ExtMetadataBlock::Level1(level1)) => {
+                                    ExtMetadataBlockLevel2::sdr_trim_from_level1(level1)
+                                }
+                                _ => sdr_trim.clone(),
+                            };
+
+                            vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level2(level2))?;
+                        }
+                    }
+
+                    if self.level3_from_level8 && vdr_dm_data.get_block(3).is_none() {
+                        vdr_dm_data.add_level3_from_level8()?;
+                    }
+
+                    if let Some(target_nits) = self.single_target_nits {
+                        vdr_dm_data.retain_target_display(target_nits);
+                    }
+
+                    if self.canonical_minimal {
+                        vdr_dm_data.canonicalize_blocks();
+                    }
+
+                    vdr_dm_data.validate_required_blocks()?;
+
+                    for &level in dm_mode_required_levels.unwrap_or_default() {
+                        ensure!(
+                            vdr_dm_data.get_block(level).is_some(),
+                            "L254: DM mode {} requires L{} metadata",
+                            level254.dm_mode,
+                            level
+                        );
+                    }
                 }
 
                 list.push(frame_rpu)
             }
         }
 
+        // The first frame is always a scene cut, regardless of the shots start
+        if let Some(vdr_dm_data) = list.first_mut().and_then(|rpu| rpu.vdr_dm_data.as_mut()) {
+            vdr_dm_data.set_scene_cut(true);
+        }
+
+        ensure!(
+            list.len() == self.length,
+            "Generated {} RPUs, expected {} frames",
+            list.len(),
+            self.length
+        );
+
         Ok(list)
     }
 
+    /// Generates the RPUs, with the L1 of every frame replaced by the PQ values returned by `analyzer`.
+    /// The analyzer is called once for every frame: analyzing decoded pixels is costly,
+    /// and dominates the generation time.
+    pub fn generate_rpu_list_with_analyzer(
+        &self,
+        analyzer: &FrameAnalyzer,
+    ) -> Result<Vec<DoviRpu>> {
+        let mut rpus = self.generate_rpu_list()?;
+
+        for (frame, rpu) in rpus.iter_mut().enumerate() {
+            if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_mut() {
+                let (min_pq, avg_pq, max_pq) = analyzer(frame);
+                let level1 = ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq);
+
+                vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level1(level1))?;
+            }
+        }
+
+        Ok(rpus)
+    }
+
+    /// Index of the frame displayed at `seconds` from the start of the sequence.
+    ///
+    /// The shots are played in order, each at its own frame rate.
+    /// Every shot up to the timestamp must have a frame rate.
+    pub fn frame_index_at(&self, seconds: f64) -> Result<usize> {
+        ensure!(seconds >= 0.0, "Invalid negative timestamp: {}", seconds);
+
+        let mut shot_start_seconds = 0.0;
+
+        for shot in &self.shots {
+            let frame_rate = match shot.frame_rate {
+                Some(frame_rate) if frame_rate > 0.0 => frame_rate,
+                _ => bail!("Shot {} has no valid frame rate", shot.id),
+            };
+
+            // Tolerance for timestamps computed from rounded frame durations
+            let offset = ((seconds - shot_start_seconds) * frame_rate + 1e-6).floor() as usize;
+
+            if offset < shot.duration {
+                return Ok(shot.start + offset);
+            }
+
+            shot_start_seconds += shot.duration as f64 / frame_rate;
+        }
+
+        bail!(
+            "Timestamp {}s is after the end of the shots, at {}s",
+            seconds,
+            shot_start_seconds
+        )
+    }
+
+    /// Sets the length from the end of the last shot, after the shots are edited.
+    pub fn recompute_length(&mut self) {
+        self.length = self
+            .shots
+            .iter()
+            .map(|shot| shot.start + shot.duration)
+            .max()
+            .unwrap_or(0);
+    }
+
+    /// Duration in seconds of the sequence, played at a constant `frame_rate`.
+    pub fn duration_seconds(&self, frame_rate: f64) -> f64 {
+        self.length as f64 / frame_rate
+    }
+
+    /// Snaps the L2 and L10 target displays to the nearest standard target peak brightness.
+    /// L8 trims are for the target display defined by the L10 block of the same index.
+    ///
+    /// A warning is emitted for every target that is changed.
+    pub fn snap_targets(&mut self) {
+        let shot_blocks = self.shots.iter_mut().flat_map(|shot| {
+            let edit_blocks = shot
+                .frame_edits
+                .iter_mut()
+                .flat_map(|edit| edit.metadata_blocks.iter_mut());
+
+            shot.metadata_blocks.iter_mut().chain(edit_blocks)
+        });
+
+        for block in self.default_metadata_blocks.iter_mut().chain(shot_blocks) {
+            let level = block.level();
+
+            let target_max_pq = match block {
+                ExtMetadataBlock::Level2(b) => &mut b.target_max_pq,
+                ExtMetadataBlock::Level10(b) => &mut b.target_max_pq,
+                _ => continue,
+            };
+
+            let nits = pq_to_nits(*target_max_pq as f64 / 4095.0).round() as u16;
+            let standard_nits = nearest_standard_target(nits);
+            let standard_pq = (nits_to_pq(standard_nits.into()) * 4095.0).round() as u16;
+
+            if *target_max_pq != standard_pq {
+                self.warnings.warn(&format!(
+                    "L{} target display of {} nits snapped to {} nits",
+                    level, nits, standard_nits
+                ));
+
+                *target_max_pq = standard_pq;
+            }
+        }
+    }
+
+    /// Merges the consecutive shots with the same metadata blocks, regardless of their order.
+    /// The merged shot keeps the ID of the first shot, the total length is unchanged.
+    ///
+    /// Frame edits are moved to the merged shot.
+    /// With `FrameEditInheritance::PreviousEdit`, shots with frame edits are not merged,
+    /// as they would inherit the edits of the previous shot.
+    ///
+    /// Returns the number of shots merged into their previous shot.
+    pub fn merge_identical_shots(&mut self) -> usize {
+        let shots_count = self.shots.len();
+        let keep_edits_separate = self.frame_edit_inheritance == FrameEditInheritance::PreviousEdit;
+
+        let mut merged_shots: Vec<VideoShot> = Vec::with_capacity(shots_count);
+
+        for mut shot in self.shots.drain(..) {
+            let previous = merged_shots.last_mut().filter(|previous| {
+                (!keep_edits_separate || shot.frame_edits.is_empty())
+                    && same_metadata_blocks(&previous.metadata_blocks, &shot.metadata_blocks)
+            });
+
+            if let Some(previous) = previous {
+                for edit in &mut shot.frame_edits {
+                    edit.edit_offset += previous.duration;
+                }
+
+                previous.duration += shot.duration;
+                previous.frame_edits.append(&mut shot.frame_edits);
+            } else {
+                merged_shots.push(shot);
+            }
+        }
+
+        self.shots = merged_shots;
+
+        shots_count - self.shots.len()
+    }
+
+    /// Encoded sizes in bytes of the RPUs generated as CM v2.9 and as CM v4.0, in this order.
+    /// The CM v4.0 metadata is dropped for the CM v2.9 variant.
+    pub fn estimate_sizes(&self) -> Result<(usize, usize)> {
+        let is_cmv29_block =
+            |b: &ExtMetadataBlock| CmV29DmData::ALLOWED_BLOCK_LEVELS.contains(&b.level());
+
+        let mut cmv29_config = self.clone();
+        cmv29_config.cm_version = CmVersion::V29;
+        cmv29_config.default_metadata_blocks.retain(is_cmv29_block);
+
+        for shot in &mut cmv29_config.shots {
+            shot.metadata_blocks.retain(is_cmv29_block);
+
+            for frame_edit in &mut shot.frame_edits {
+                frame_edit.metadata_blocks.retain(is_cmv29_block);
+            }
+        }
+
+        let mut cmv40_config = self.clone();
+        cmv40_config.cm_version = CmVersion::V40;
+
+        Ok((cmv29_config.encoded_size()?, cmv40_config.encoded_size()?))
+    }
+
+    /// Size of the generated RPUs, as written by `write_rpus`
+    fn encoded_size(&self) -> Result<usize> {
+        self.generate_rpu_list()?
+            .iter()
+            .map(|rpu| {
+                // Start code instead of 0x7C01
+                rpu.write_hevc_unspec62_nalu()
+                    .map(|data| OUT_NAL_HEADER.len() + data.len() - 2)
+            })
+            .sum()
+    }
+
     pub fn encode_option_rpus(rpus: &mut Vec<Option<DoviRpu>>) -> Vec<Vec<u8>> {
         let encoded_rpus = rpus
             .iter_mut()
@@ -185,6 +594,27 @@ impl GenerateConfig {
 
         Ok(())
     }
+
+    /// Generates the RPUs and hands them back encoded, `chunk` frames at a time.
+    /// Each chunk is the concatenation of the encoded RPUs, as returned by `encode_rpus`.
+    pub fn write_rpus_chunked(&self, chunk: usize, mut f: impl FnMut(&[u8])) -> Result<()> {
+        ensure!(chunk > 0, "Chunk size must be at least 1 frame");
+
+        let rpus = self.generate_rpu_list()?;
+        let mut data = Vec::new();
+
+        for chunk_rpus in rpus.chunks(chunk) {
+            data.clear();
+
+            for rpu in chunk_rpus {
+                data.extend(rpu.write_hevc_unspec62_nalu()?);
+            }
+
+            f(&data);
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for GenerateConfig {
@@ -194,8 +624,12 @@ impl Default for GenerateConfig {
             length: Default::default(),
             source_min_pq: Default::default(),
             source_max_pq: Default::default(),
+            bl_bit_depth: GenerateConfig::default_bit_depth(),
+            el_bit_depth: GenerateConfig::default_bit_depth(),
             default_metadata_blocks: Default::default(),
             level5: Default::default(),
+            level254: Default::default(),
+            default_source_primaries: Default::default(),
             level6: ExtMetadataBlockLevel6 {
                 max_display_mastering_luminance: 1000,
                 min_display_mastering_luminance: 1,
@@ -203,11 +637,100 @@ impl Default for GenerateConfig {
                 max_frame_average_light_level: 0,
             },
             shots: Default::default(),
+            sdr_fallback_trim: Default::default(),
+            level3_from_level8: Default::default(),
+            frame_edit_inheritance: Default::default(),
+            single_target_nits: Default::default(),
+            canonical_minimal: Default::default(),
+            markers: Default::default(),
+            warnings: Default::default(),
+        }
+    }
+}
+
+/// Sort key, size in bits and written payload of a block
+type EncodedBlock = ((u8, u16), u64, Vec<u8>);
+
+/// Compares the blocks by their written payload, regardless of their order
+pub(crate) fn same_metadata_blocks(a: &[ExtMetadataBlock], b: &[ExtMetadataBlock]) -> bool {
+    let encoded = |blocks: &[ExtMetadataBlock]| -> Option<Vec<EncodedBlock>> {
+        let mut encoded = blocks
+            .iter()
+            .map(|block| {
+                let mut writer = BitVecWriter::new();
+                block.write(&mut writer).ok()?;
+
+                Some((
+                    block.sort_key(),
+                    block.required_bits(),
+                    writer.as_slice().to_vec(),
+                ))
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        encoded.sort();
+
+        Some(encoded)
+    };
+
+    a.len() == b.len()
+        && match (encoded(a), encoded(b)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
         }
+}
+
+impl Default for FrameEditInheritance {
+    fn default() -> Self {
+        Self::Shot
     }
 }
 
 impl VideoShot {
+    /// Shots starting at the frames of a cut list, covering `length` frames.
+    /// The cuts are sorted, and a cut at frame 0 is implied.
+    ///
+    /// The shots have no metadata, the default metadata blocks apply.
+    pub fn from_cut_list(cuts: &[usize], length: usize) -> Result<Vec<VideoShot>> {
+        ensure!(
+            length > 0,
+            "Cannot create shots from a cut list for 0 frames"
+        );
+
+        let mut starts = cuts.to_vec();
+        starts.push(0);
+        starts.sort_unstable();
+        starts.dedup();
+
+        let last_start = *starts.last().unwrap();
+        ensure!(
+            last_start < length,
+            "Cut at frame {} is out of range, length is {} frames",
+            last_start,
+            length
+        );
+
+        let ends = starts
+            .iter()
+            .skip(1)
+            .copied()
+            .chain(std::iter::once(length));
+
+        let shots = starts
+            .iter()
+            .zip(ends)
+            .enumerate()
+            .map(|(i, (&start, end))| VideoShot {
+                id: i.to_string(),
+                start,
+                duration: end - start,
+                ..Default::default()
+            })
+            .collect();
+
+        Ok(shots)
+    }
+
     pub fn copy_metadata_from_shot(
         &mut self,
         other_shot: &VideoShot,
@@ -280,8 +803,9 @@ mod tests {
     use anyhow::Result;
     use std::path::PathBuf;
 
+    use super::GenerateConfig;
     use crate::{
-        rpu::{extension_metadata::blocks::ExtMetadataBlock, vdr_dm_data::CmVersion},
+        rpu::{extension_metadata::blocks::*, vdr_dm_data::CmVersion},
         xml::{CmXmlParser, XmlParserOpts},
     };
 
@@ -293,6 +817,9 @@ mod tests {
         let opts = XmlParserOpts {
             canvas_width: Some(3840),
             canvas_height: Some(2160),
+            strict: false,
+            length: None,
+            ..Default::default()
         };
         let parser = CmXmlParser::parse_file(&assets_path.join("assets/tests/cmv4_0_2.xml"), opts)?;
 
@@ -556,4 +1083,687 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn constant_config() -> Result<()> {
+        let config = GenerateConfig::constant(
+            100,
+            ExtMetadataBlockLevel1::from_stats(0, 2828, 1120),
+            ExtMetadataBlockLevel5::from_offsets(0, 0, 276, 276),
+            ExtMetadataBlockLevel6 {
+                max_display_mastering_luminance: 1000,
+                min_display_mastering_luminance: 1,
+                max_content_light_level: 1000,
+                max_frame_average_light_level: 400,
+            },
+        );
+
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(rpus.len(), 100);
+
+        assert_eq!(rpus[0].vdr_dm_data.as_ref().unwrap().scene_refresh_flag, 1);
+
+        for rpu in &rpus[1..] {
+            let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+            assert_eq!(vdr_dm_data.scene_refresh_flag, 0);
+
+            if let ExtMetadataBlock::Level1(level1) = vdr_dm_data.get_block(1).unwrap() {
+                assert_eq!(level1.min_pq, 0);
+                assert_eq!(level1.max_pq, 2828);
+                assert_eq!(level1.avg_pq, 1120);
+            } else {
+                panic!("No L1 block");
+            }
+
+            if let ExtMetadataBlock::Level5(level5) = vdr_dm_data.get_block(5).unwrap() {
+                assert_eq!(level5.get_offsets(), (0, 0, 276, 276));
+            } else {
+                panic!("No L5 block");
+            }
+
+            if let ExtMetadataBlock::Level6(level6) = vdr_dm_data.get_block(6).unwrap() {
+                assert_eq!(level6.max_content_light_level, 1000);
+                assert_eq!(level6.max_frame_average_light_level, 400);
+            } else {
+                panic!("No L6 block");
+            }
+        }
+
+        let second_rpu = rpus[1].write_rpu()?;
+        assert!(rpus[2..]
+            .iter()
+            .all(|rpu| rpu.write_rpu().unwrap() == second_rpu));
+
+        Ok(())
+    }
+
+    #[test]
+    fn custom_level254() -> Result<()> {
+        let config = GenerateConfig {
+            level254: Some(ExtMetadataBlockLevel254 {
+                dm_mode: 1,
+                dm_version_index: 2,
+            }),
+            ..GenerateConfig::constant(
+                10,
+                ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+                ExtMetadataBlockLevel5::default(),
+                ExtMetadataBlockLevel6::default(),
+            )
+        };
+
+        let rpus = config.generate_rpu_list()?;
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+        // L1, L5, L6 in CMv2.9
+        assert_eq!(vdr_dm_data.metadata_blocks(1).unwrap().len(), 3);
+
+        // L11, L254 in CMv4.0
+        assert_eq!(vdr_dm_data.metadata_blocks(3).unwrap().len(), 2);
+
+        if let ExtMetadataBlock::Level254(level254) = vdr_dm_data.get_block(254).unwrap() {
+            assert_eq!(level254.dm_mode, 1);
+            assert_eq!(level254.dm_version_index, 2);
+        } else {
+            panic!("No L254 block");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn level254_dm_mode_block_set() -> Result<()> {
+        let config_with_dm_mode = |dm_mode: u8| GenerateConfig {
+            level254: Some(ExtMetadataBlockLevel254 {
+                dm_mode,
+                dm_version_index: 2,
+            }),
+            ..GenerateConfig::constant(
+                10,
+                ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+                ExtMetadataBlockLevel5::default(),
+                ExtMetadataBlockLevel6::default(),
+            )
+        };
+        let cmv40_levels = |config: &GenerateConfig| -> Result<Vec<u8>> {
+            let rpus = config.generate_rpu_list()?;
+            let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+            Ok(vdr_dm_data
+                .level_blocks_iter(254)
+                .chain(vdr_dm_data.level_blocks_iter(11))
+                .map(|b| b.level())
+                .collect())
+        };
+
+        let required_levels = ExtMetadataBlockLevel254::cmv40_default().dm_mode_required_levels();
+        assert_eq!(required_levels, Some(&[254][..]));
+        assert_eq!(cmv40_levels(&config_with_dm_mode(0))?, vec![254, 11]);
+
+        // Unknown mode, the same blocks are generated
+        let unknown_mode = ExtMetadataBlockLevel254 {
+            dm_mode: 1,
+            dm_version_index: 2,
+        };
+        assert!(unknown_mode.dm_mode_required_levels().is_none());
+        assert_eq!(cmv40_levels(&config_with_dm_mode(1))?, vec![254, 11]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn frame_edit_inheritance() -> Result<()> {
+        use super::{FrameEditInheritance, ShotFrameEdit, VideoShot};
+
+        let level2 = |trim_slope| {
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+                trim_slope,
+                ..ExtMetadataBlockLevel2::from_nits(600)
+            })
+        };
+        let level1 =
+            |max_pq| ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, max_pq, 819));
+
+        let mut config = GenerateConfig {
+            length: 4,
+            shots: vec![VideoShot {
+                start: 0,
+                duration: 4,
+                metadata_blocks: vec![level1(2081), level2(2048)],
+                frame_edits: vec![
+                    ShotFrameEdit {
+                        edit_offset: 1,
+                        metadata_blocks: vec![level1(2200), level2(2200)],
+                    },
+                    ShotFrameEdit {
+                        edit_offset: 2,
+                        metadata_blocks: vec![level1(2300)],
+                    },
+                ],
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let l2_trim_slopes = |config: &GenerateConfig| -> Result<Vec<u16>> {
+            Ok(config
+                .generate_rpu_list()?
+                .iter()
+                .map(|rpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(2) {
+                    Some(ExtMetadataBlock::Level2(level2)) => level2.trim_slope,
+                    _ => panic!("No L2 block"),
+                })
+                .collect())
+        };
+
+        // Second edit omits L2, inherits from the shot
+        assert_eq!(l2_trim_slopes(&config)?, vec![2048, 2200, 2048, 2048]);
+
+        // Second edit inherits L2 from the first edit
+        config.frame_edit_inheritance = FrameEditInheritance::PreviousEdit;
+        assert_eq!(l2_trim_slopes(&config)?, vec![2048, 2200, 2200, 2048]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn merge_identical_shots() -> Result<()> {
+        use super::{ShotFrameEdit, VideoShot};
+        use crate::rpu::vdr_dm_data::VdrDmData;
+
+        let level1 =
+            |max_pq| ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, max_pq, 819));
+        let level2 = ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(600));
+
+        let mut config = GenerateConfig {
+            length: 9,
+            shots: vec![
+                VideoShot {
+                    id: String::from("1"),
+                    start: 0,
+                    duration: 2,
+                    metadata_blocks: vec![level1(2081), level2.clone()],
+                    ..Default::default()
+                },
+                VideoShot {
+                    id: String::from("2"),
+                    start: 2,
+                    duration: 3,
+                    metadata_blocks: vec![level2.clone(), level1(2081)],
+                    frame_edits: vec![ShotFrameEdit {
+                        edit_offset: 1,
+                        metadata_blocks: vec![level1(2200)],
+                    }],
+                    ..Default::default()
+                },
+                VideoShot {
+                    id: String::from("3"),
+                    start: 5,
+                    duration: 4,
+                    metadata_blocks: vec![level1(2300), level2],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let max_pq = |dm_data: &VdrDmData| match dm_data.get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => level1.max_pq,
+            _ => panic!("No L1 block"),
+        };
+
+        let rpus_before = config.generate_rpu_list()?;
+
+        assert_eq!(config.merge_identical_shots(), 1);
+        assert_eq!(config.shots.len(), 2);
+
+        let merged = &config.shots[0];
+        assert_eq!(merged.id, "1");
+        assert_eq!(merged.duration, 5);
+        assert_eq!(merged.frame_edits.len(), 1);
+        assert_eq!(merged.frame_edits[0].edit_offset, 3);
+        assert_eq!(config.shots[1].duration, 4);
+
+        // Same metadata, without the scene cut of the merged shot
+        let rpus_after = config.generate_rpu_list()?;
+        assert_eq!(rpus_after.len(), rpus_before.len());
+
+        for (i, (before, after)) in rpus_before.iter().zip(rpus_after.iter()).enumerate() {
+            let before_dm_data = before.vdr_dm_data.as_ref().unwrap();
+            let after_dm_data = after.vdr_dm_data.as_ref().unwrap();
+
+            assert_eq!(
+                before_dm_data.scene_refresh_flag == 1,
+                i == 0 || i == 2 || i == 5
+            );
+            assert_eq!(after_dm_data.scene_refresh_flag == 1, i == 0 || i == 5);
+
+            assert_eq!(max_pq(before_dm_data), max_pq(after_dm_data));
+        }
+
+        // Nothing left to merge
+        assert_eq!(config.merge_identical_shots(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn first_frame_scene_cut() -> Result<()> {
+        use super::VideoShot;
+
+        let level1 = ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819));
+
+        // Shots are generated in order, the start offsets are not used for placement
+        let config = GenerateConfig {
+            length: 8,
+            shots: vec![
+                VideoShot {
+                    id: String::from("2"),
+                    start: 5,
+                    duration: 3,
+                    metadata_blocks: vec![level1.clone()],
+                    ..Default::default()
+                },
+                VideoShot {
+                    id: String::from("1"),
+                    start: 0,
+                    duration: 5,
+                    metadata_blocks: vec![level1],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(rpus.len(), 8);
+
+        let scene_cuts: Vec<u64> = rpus
+            .iter()
+            .map(|rpu| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag)
+            .collect();
+        assert_eq!(scene_cuts, vec![1, 0, 0, 1, 0, 0, 0, 0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn default_source_primaries() -> Result<()> {
+        use super::VideoShot;
+
+        let level1 = ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819));
+
+        let mut config = GenerateConfig {
+            length: 2,
+            shots: vec![
+                VideoShot {
+                    id: String::from("1"),
+                    start: 0,
+                    duration: 1,
+                    metadata_blocks: vec![level1.clone()],
+                    ..Default::default()
+                },
+                VideoShot {
+                    id: String::from("2"),
+                    start: 1,
+                    duration: 1,
+                    metadata_blocks: vec![
+                        level1,
+                        ExtMetadataBlock::Level9(ExtMetadataBlockLevel9::from_named_gamut(
+                            ColorPrimaries::Bt709,
+                        )),
+                    ],
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        let source_primary_index = |rpu: &crate::rpu::dovi_rpu::DoviRpu| match rpu
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .get_block(9)
+        {
+            Some(ExtMetadataBlock::Level9(level9)) => Some(level9.source_primary_index),
+            _ => None,
+        };
+
+        // No L9 by default, DCI-P3 D65
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(source_primary_index(&rpus[0]), None);
+
+        config.default_source_primaries = Some(ColorPrimaries::Bt2020);
+
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(source_primary_index(&rpus[0]), Some(2));
+
+        // Shot metadata replaces the default
+        assert_eq!(source_primary_index(&rpus[1]), Some(1));
+
+        Ok(())
+    }
+
+    #[test]
+    fn level6_min_luminance_units() -> Result<()> {
+        assert_eq!(ExtMetadataBlockLevel6::min_luminance_from_nits(0.0001), 1);
+        assert_eq!(ExtMetadataBlockLevel6::min_luminance_from_nits(0.005), 50);
+        assert_eq!(ExtMetadataBlockLevel6::min_luminance_from_nits(0.0), 0);
+
+        let level6 = ExtMetadataBlockLevel6::from_nits(1000, 0.005, 1000, 400);
+        assert_eq!(level6.min_display_mastering_luminance, 50);
+        assert!((level6.min_luminance_to_nits() - 0.005).abs() < f32::EPSILON);
+
+        let config = GenerateConfig::constant(
+            1,
+            ExtMetadataBlockLevel1::from_stats(0, 2828, 1120),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+
+        let rpus = config.generate_rpu_list()?;
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+        if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+            assert_eq!(level6.min_display_mastering_luminance, 1);
+            assert_eq!(level6.max_display_mastering_luminance, 1000);
+        } else {
+            panic!("No L6 block");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmv40_carries_both_dm_data() -> Result<()> {
+        use crate::rpu::dovi_rpu::DoviRpu;
+
+        let mut config = GenerateConfig::constant(
+            1,
+            ExtMetadataBlockLevel1::from_stats(0, 2828, 1120),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+        config.cm_version = CmVersion::V29;
+
+        let rpus = config.generate_rpu_list()?;
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+        assert!(vdr_dm_data.metadata_blocks(1).is_some());
+        assert!(vdr_dm_data.metadata_blocks(3).is_none());
+
+        config.cm_version = CmVersion::V40;
+        config.default_source_primaries = Some(ColorPrimaries::Bt2020);
+
+        let rpus = config.generate_rpu_list()?;
+        let data = rpus[0].write_hevc_unspec62_nalu()?;
+
+        // Both DM data are written, for CM v2.9 and CM v4.0 displays
+        let rpu = DoviRpu::parse_unspec62_nalu(&data)?;
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        // L1, L5, L6 in CMv2.9
+        assert_eq!(vdr_dm_data.metadata_blocks(1).unwrap().len(), 3);
+
+        // L9, L11, L254 in CMv4.0
+        assert_eq!(vdr_dm_data.metadata_blocks(3).unwrap().len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn canonical_minimal_blocks() -> Result<()> {
+        use crate::rpu::dovi_rpu::DoviRpu;
+
+        // Signed 16 bit, scaled by 32767
+        let encode = |primaries: [f64; 8]| -> Vec<u16> {
+            primaries
+                .iter()
+                .map(|v| (v * 32767.0).round() as i16 as u16)
+                .collect()
+        };
+
+        // Custom primaries, equal to the predefined BT.2020 (2) and DCI-P3 D65 (0)
+        let p = encode(PREDEFINED_COLORSPACE_PRIMARIES[2]);
+        let level9 = ExtMetadataBlockLevel9 {
+            source_primary_index: 255,
+            source_primary_red_x: p[0],
+            source_primary_red_y: p[1],
+            source_primary_green_x: p[2],
+            source_primary_green_y: p[3],
+            source_primary_blue_x: p[4],
+            source_primary_blue_y: p[5],
+            source_primary_white_x: p[6],
+            source_primary_white_y: p[7],
+        };
+
+        let p = encode(PREDEFINED_COLORSPACE_PRIMARIES[0]);
+        let level10 = ExtMetadataBlockLevel10 {
+            target_display_index: 20,
+            target_max_pq: 3079,
+            target_min_pq: 0,
+            target_primary_index: 255,
+            target_primary_red_x: p[0],
+            target_primary_red_y: p[1],
+            target_primary_green_x: p[2],
+            target_primary_green_y: p[3],
+            target_primary_blue_x: p[4],
+            target_primary_blue_y: p[5],
+            target_primary_white_x: p[6],
+            target_primary_white_y: p[7],
+        };
+
+        let mut config = GenerateConfig::constant(
+            1,
+            ExtMetadataBlockLevel1::from_stats(0, 2828, 1120),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::default(),
+        );
+        config
+            .default_metadata_blocks
+            .push(ExtMetadataBlock::Level9(level9));
+        config
+            .default_metadata_blocks
+            .push(ExtMetadataBlock::Level10(level10));
+
+        // Custom primaries are kept by default
+        let rpus = config.generate_rpu_list()?;
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+        if let Some(ExtMetadataBlock::Level9(level9)) = vdr_dm_data.get_block(9) {
+            assert_eq!(level9.source_primary_index, 255);
+            assert_eq!(level9.bytes_size(), 17);
+        } else {
+            panic!("No L9 block");
+        }
+
+        config.canonical_minimal = true;
+
+        let rpus = config.generate_rpu_list()?;
+        let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&rpus[0].write_hevc_unspec62_nalu()?)?;
+
+        for rpu in &[&rpus[0], &reparsed_rpu] {
+            let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+            // 8 bit form, the primaries are the defaults
+            if let Some(ExtMetadataBlock::Level9(level9)) = vdr_dm_data.get_block(9) {
+                assert_eq!(level9.source_primary_index, 2);
+                assert_eq!(level9.bytes_size(), 1);
+                assert_eq!(level9.source_primary_red_x, 0);
+            } else {
+                panic!("No L9 block");
+            }
+
+            if let Some(ExtMetadataBlock::Level10(level10)) = vdr_dm_data.get_block(10) {
+                assert_eq!(level10.target_display_index, 20);
+                assert_eq!(level10.target_max_pq, 3079);
+                assert_eq!(level10.target_primary_index, 0);
+                assert_eq!(level10.bytes_size(), 5);
+                assert_eq!(level10.target_primary_red_x, 0);
+            } else {
+                panic!("No L10 block");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn recompute_length() -> Result<()> {
+        use super::VideoShot;
+
+        let mut config = GenerateConfig::constant(
+            48,
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+
+        config.shots.push(VideoShot {
+            start: 48,
+            duration: 72,
+            ..Default::default()
+        });
+        assert_eq!(config.length, 48);
+
+        config.recompute_length();
+        assert_eq!(config.length, 120);
+        assert!((config.duration_seconds(24.0) - 5.0).abs() < f64::EPSILON);
+
+        assert_eq!(config.generate_rpu_list()?.len(), 120);
+
+        Ok(())
+    }
+
+    #[test]
+    fn variable_frame_rate_shots() -> Result<()> {
+        use super::VideoShot;
+
+        let level1 = ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819));
+
+        // 2 seconds at 24 fps, then 2 seconds at 60 fps
+        let config = GenerateConfig {
+            length: 168,
+            default_metadata_blocks: vec![level1],
+            shots: vec![
+                VideoShot {
+                    id: String::from("1"),
+                    start: 0,
+                    duration: 48,
+                    frame_rate: Some(24.0),
+                    ..Default::default()
+                },
+                VideoShot {
+                    id: String::from("2"),
+                    start: 48,
+                    duration: 120,
+                    frame_rate: Some(60.0),
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+
+        // Still one RPU per frame
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(rpus.len(), 168);
+
+        assert_eq!(config.frame_index_at(0.0)?, 0);
+        assert_eq!(config.frame_index_at(1.0)?, 24);
+        assert_eq!(config.frame_index_at(47.0 / 24.0)?, 47);
+        assert_eq!(config.frame_index_at(2.0)?, 48);
+        assert_eq!(config.frame_index_at(2.5)?, 78);
+        assert_eq!(config.frame_index_at(4.0 - 1.0 / 60.0)?, 167);
+
+        assert!(config.frame_index_at(4.0).is_err());
+        assert!(config.frame_index_at(-1.0).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_rpus_chunked() -> Result<()> {
+        let config = GenerateConfig::constant(
+            50,
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+
+        let mut chunks = Vec::new();
+        config.write_rpus_chunked(16, |data| chunks.push(data.to_vec()))?;
+
+        // 3 full chunks and the remaining 2 frames
+        assert_eq!(chunks.len(), 4);
+
+        let mut rpus = config.generate_rpu_list()?;
+        let encoded_rpus = GenerateConfig::encode_rpus(&mut rpus);
+
+        assert_eq!(chunks.concat(), encoded_rpus.concat());
+        assert_eq!(chunks[3], encoded_rpus[48..].concat());
+
+        assert!(config.write_rpus_chunked(0, |_| {}).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn shots_length_mismatch() {
+        use super::VideoShot;
+
+        let mut config = GenerateConfig::constant(
+            48,
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+
+        config.shots.push(VideoShot {
+            start: 48,
+            duration: 24,
+            ..Default::default()
+        });
+        config.length = 96;
+
+        let err = config.generate_rpu_list().unwrap_err().to_string();
+        assert!(err.contains("Config: 96, Shots: 72"));
+        assert!(err.contains("24 frames missing"));
+
+        config.length = 60;
+
+        let err = config.generate_rpu_list().unwrap_err().to_string();
+        assert!(err.contains("12 frames too many"));
+    }
+
+    #[test]
+    fn l1_from_analyzer() -> Result<()> {
+        let config = GenerateConfig::constant(
+            10,
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+        );
+
+        // Mock measurements of the decoded frames
+        let rpus = config.generate_rpu_list_with_analyzer(&|frame| {
+            let frame = frame as u16;
+            (frame, 1000 + frame, 2100 + frame)
+        })?;
+
+        assert_eq!(rpus.len(), 10);
+
+        for (frame, rpu) in rpus.iter().enumerate() {
+            let frame = frame as u16;
+
+            let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+            if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+                assert_eq!(level1.min_pq, frame);
+                assert_eq!(level1.avg_pq, 1000 + frame);
+                assert_eq!(level1.max_pq, 2100 + frame);
+            } else {
+                panic!("No L1 block");
+            }
+        }
+
+        Ok(())
+    }
 }
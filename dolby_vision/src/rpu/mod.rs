@@ -1,3 +1,4 @@
+pub mod bit_layout;
 pub mod dovi_rpu;
 pub mod extension_metadata;
 pub mod generate;
@@ -5,6 +6,10 @@ pub mod profiles;
 pub mod rpu_data_header;
 pub mod rpu_data_mapping;
 pub mod rpu_data_nlq;
+#[cfg(feature = "serde_feature")]
+pub mod shot_diff;
+pub mod stats;
+pub mod trims;
 pub mod vdr_dm_data;
 
 use crc::{Crc, CRC_32_MPEG_2};
@@ -1,4 +1,5 @@
-use super::vdr_dm_data::VdrDmData;
+use super::extension_metadata::{CmV29DmData, CmV40DmData, WithExtMetadataBlocks};
+use super::vdr_dm_data::{CmVersion, VdrDmData};
 
 pub mod profile4;
 pub mod profile5;
@@ -14,3 +15,103 @@ pub trait DoviProfile {
         true
     }
 }
+
+/// Metadata carried by a profile, or by an actual RPU with `DoviRpu::footprint`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FootprintInfo {
+    pub profile: u8,
+
+    /// Whether the EL is mapped by NLQ parameters
+    pub el_mapping: bool,
+    pub backwards_compatible: bool,
+
+    pub cm_versions: Vec<CmVersion>,
+
+    /// Sorted extension metadata block levels
+    pub block_levels: Vec<u8>,
+}
+
+/// Footprint of the metadata a profile can carry.
+/// Unknown profiles carry no metadata.
+pub fn metadata_footprint(profile: u8) -> FootprintInfo {
+    let cm_versions = match profile {
+        4 => vec![CmVersion::V29],
+        5 | 7 | 8 => vec![CmVersion::V29, CmVersion::V40],
+        _ => Vec::new(),
+    };
+
+    let mut block_levels: Vec<u8> = cm_versions
+        .iter()
+        .flat_map(|version| match version {
+            CmVersion::V29 => CmV29DmData::ALLOWED_BLOCK_LEVELS,
+            CmVersion::V40 => CmV40DmData::ALLOWED_BLOCK_LEVELS,
+        })
+        .copied()
+        .collect();
+    block_levels.sort_unstable();
+
+    FootprintInfo {
+        profile,
+        el_mapping: matches!(profile, 4 | 7),
+        backwards_compatible: backwards_compatible(profile),
+        cm_versions,
+        block_levels,
+    }
+}
+
+pub(crate) fn backwards_compatible(profile: u8) -> bool {
+    match profile {
+        4 => profile4::Profile4::backwards_compatible(),
+        5 => profile5::Profile5::backwards_compatible(),
+        7 => profile7::Profile7::backwards_compatible(),
+        8 => profile81::Profile81::backwards_compatible(),
+        _ => false,
+    }
+}
+
+impl FootprintInfo {
+    /// Differences from `self` to `other`, to explain a conversion
+    pub fn diff(&self, other: &FootprintInfo) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.el_mapping != other.el_mapping {
+            let change = if other.el_mapping { "added" } else { "removed" };
+            differences.push(format!("EL mapping {}", change));
+        }
+
+        if self.backwards_compatible != other.backwards_compatible {
+            let change = if other.backwards_compatible {
+                "added"
+            } else {
+                "removed"
+            };
+            differences.push(format!("Backwards compatibility {}", change));
+        }
+
+        for version in &self.cm_versions {
+            if !other.cm_versions.contains(version) {
+                differences.push(format!("{:?} DM data removed", version));
+            }
+        }
+
+        for version in &other.cm_versions {
+            if !self.cm_versions.contains(version) {
+                differences.push(format!("{:?} DM data added", version));
+            }
+        }
+
+        for level in &self.block_levels {
+            if !other.block_levels.contains(level) {
+                differences.push(format!("L{} metadata removed", level));
+            }
+        }
+
+        for level in &other.block_levels {
+            if !self.block_levels.contains(level) {
+                differences.push(format!("L{} metadata added", level));
+            }
+        }
+
+        differences
+    }
+}
@@ -2,17 +2,19 @@ use anyhow::{ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::{dovi_rpu::DoviRpu, NUM_COMPONENTS};
 
 #[derive(Default, Debug, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct RpuDataHeader {
     pub rpu_nal_prefix: u8,
     pub rpu_type: u8,
     pub rpu_format: u16,
     pub vdr_rpu_profile: u8,
+    /// Always 0, enforced by `validate`. Unrelated to the Dolby Vision bitstream level.
+    /// The level (resolution and frame rate) is signaled by the container, not by the RPU.
     pub vdr_rpu_level: u8,
     pub vdr_seq_info_present_flag: bool,
     pub chroma_resampling_explicit_filter_flag: bool,
@@ -170,14 +172,27 @@ impl RpuDataHeader {
         };
 
         ensure!(self.vdr_rpu_level == 0, "vdr_rpu_level should be 0");
-        ensure!(
-            self.bl_bit_depth_minus8 == 2,
-            "bl_bit_depth_minus8 should be 2"
-        );
-        ensure!(
-            self.el_bit_depth_minus8 == 2,
-            "el_bit_depth_minus8 should be 2"
-        );
+
+        // Profile 8 can signal 12 bit layers
+        if profile == 8 {
+            ensure!(
+                self.bl_bit_depth_minus8 == 2 || self.bl_bit_depth_minus8 == 4,
+                "profile 8: bl_bit_depth_minus8 should be 2 or 4"
+            );
+            ensure!(
+                self.el_bit_depth_minus8 == 2 || self.el_bit_depth_minus8 == 4,
+                "profile 8: el_bit_depth_minus8 should be 2 or 4"
+            );
+        } else {
+            ensure!(
+                self.bl_bit_depth_minus8 == 2,
+                "bl_bit_depth_minus8 should be 2"
+            );
+            ensure!(
+                self.el_bit_depth_minus8 == 2,
+                "el_bit_depth_minus8 should be 2"
+            );
+        }
         ensure!(
             self.vdr_bit_depth_minus_8 <= 6,
             "vdr_bit_depth_minus_8 should be <= 6"
@@ -328,4 +343,28 @@ impl RpuDataHeader {
             num_y_partitions_minus1: 0,
         }
     }
+
+    /// Profile 8 header for the specified BL and EL bit depths, either 10 or 12.
+    /// The pivots are set to the full BL range.
+    pub fn p8_with_bit_depths(bl_bit_depth: u8, el_bit_depth: u8) -> Result<RpuDataHeader> {
+        ensure!(
+            matches!(bl_bit_depth, 10 | 12) && matches!(el_bit_depth, 10 | 12),
+            "profile 8: BL and EL bit depths should be 10 or 12, found BL: {}, EL: {}",
+            bl_bit_depth,
+            el_bit_depth
+        );
+
+        let mut header = RpuDataHeader::p8_default();
+
+        header.bl_bit_depth_minus8 = (bl_bit_depth - 8) as u64;
+        header.el_bit_depth_minus8 = (el_bit_depth - 8) as u64;
+
+        let max_pivot = (1 << bl_bit_depth) - 1;
+        header
+            .pred_pivot_value
+            .iter_mut()
+            .for_each(|pivots| pivots[1] = max_pivot);
+
+        Ok(header)
+    }
 }
@@ -2,7 +2,7 @@ use anyhow::{bail, ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::dovi_rpu::DoviRpu;
 use super::rpu_data_header::RpuDataHeader;
@@ -11,7 +11,7 @@ use super::rpu_data_nlq::RpuDataNlq;
 use super::NUM_COMPONENTS;
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct RpuDataMapping {
     pub mapping_idc: [Vec<u64>; NUM_COMPONENTS],
     pub mapping_param_pred_flag: [Vec<bool>; NUM_COMPONENTS],
@@ -30,6 +30,31 @@ pub struct RpuDataMapping {
     pub mmr_coef: [Vec<Vec<Vec<u64>>>; NUM_COMPONENTS],
 }
 
+/// Prediction method of the mapping
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MappingMethod {
+    /// Polynomial prediction only
+    Polynomial,
+
+    /// Multivariate multiple regression, for at least one pivot.
+    /// Usually for the chroma components, the luma is predicted with polynomials.
+    Mmr,
+}
+
+/// MMR prediction parameters of a pivot
+#[derive(Debug, Clone, PartialEq)]
+pub struct MmrPivot {
+    pub component: usize,
+    pub pivot_idx: usize,
+
+    /// MMR order, from 1 to 3
+    pub order: u8,
+    pub constant: i64,
+
+    /// Integer part of the coefficients, per order
+    pub coef_int: Vec<Vec<i64>>,
+}
+
 pub fn vdr_rpu_data_payload(dovi_rpu: &mut DoviRpu, reader: &mut BitVecReader) -> Result<()> {
     dovi_rpu.rpu_data_mapping = Some(RpuDataMapping::parse(reader, &mut dovi_rpu.header)?);
 
@@ -318,6 +343,48 @@ impl RpuDataMapping {
         Ok(())
     }
 
+    fn is_mmr_pivot(&self, cmp: usize, pivot_idx: usize) -> bool {
+        self.mapping_idc[cmp].get(pivot_idx) == Some(&1)
+    }
+
+    pub fn mapping_method(&self) -> MappingMethod {
+        let uses_mmr = (0..NUM_COMPONENTS)
+            .any(|cmp| (0..self.mapping_idc[cmp].len()).any(|i| self.is_mmr_pivot(cmp, i)));
+
+        if uses_mmr {
+            MappingMethod::Mmr
+        } else {
+            MappingMethod::Polynomial
+        }
+    }
+
+    /// Parameters of the MMR predicted pivots, in component and pivot order.
+    /// The coefficients of order 0 are unused, the MMR constant applies instead.
+    pub fn mmr_pivots(&self) -> Vec<MmrPivot> {
+        (0..NUM_COMPONENTS)
+            .flat_map(|cmp| (0..self.mapping_idc[cmp].len()).map(move |i| (cmp, i)))
+            .filter(|&(cmp, i)| self.is_mmr_pivot(cmp, i))
+            .map(|(cmp, i)| MmrPivot {
+                component: cmp,
+                pivot_idx: i,
+                order: self.mmr_order_minus1[cmp][i] + 1,
+                constant: self.mmr_constant_int[cmp][i],
+                coef_int: self.mmr_coef_int[cmp][i].clone(),
+            })
+            .collect()
+    }
+
+    /// Whether the mapping is the identity, as set by `set_empty_p81_mapping`:
+    /// a single first order polynomial per component, with the coefficients 0 and 1.
+    pub fn is_identity(&self) -> bool {
+        (0..NUM_COMPONENTS).all(|cmp| {
+            self.mapping_idc[cmp] == [0]
+                && self.poly_order_minus1[cmp] == [0]
+                && self.poly_coef_int[cmp] == [vec![0, 1]]
+                && self.poly_coef[cmp] == [vec![0, 0]]
+        })
+    }
+
     pub fn set_empty_p81_mapping(&mut self) {
         self.mapping_idc.iter_mut().for_each(|v| {
             v.clear();
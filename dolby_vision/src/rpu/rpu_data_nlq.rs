@@ -2,14 +2,14 @@ use anyhow::{bail, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use super::rpu_data_header::RpuDataHeader;
 
 use super::NUM_COMPONENTS;
 
 #[derive(Debug, Default, Clone)]
-#[cfg_attr(feature = "serde_feature", derive(Serialize))]
+#[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub struct RpuDataNlq {
     pub num_nlq_param_predictors: Vec<[u64; NUM_COMPONENTS]>,
     pub nlq_param_pred_flag: Vec<[bool; NUM_COMPONENTS]>,
@@ -164,6 +164,40 @@ impl RpuDataNlq {
         });
     }
 
+    /// Sets every NLQ parameter to 0, so that no EL residual is added
+    pub fn zero_residual(&mut self) {
+        let mut params = [
+            &mut self.nlq_offset,
+            &mut self.vdr_in_max_int,
+            &mut self.vdr_in_max,
+            &mut self.linear_deadzone_slope_int,
+            &mut self.linear_deadzone_slope,
+            &mut self.linear_deadzone_threshold_int,
+            &mut self.linear_deadzone_threshold,
+        ];
+
+        for param in params.iter_mut() {
+            param.iter_mut().for_each(|v| *v = [0; NUM_COMPONENTS]);
+        }
+    }
+
+    /// Whether every NLQ parameter is 0
+    pub fn is_zero_residual(&self) -> bool {
+        let params = [
+            &self.nlq_offset,
+            &self.vdr_in_max_int,
+            &self.vdr_in_max,
+            &self.linear_deadzone_slope_int,
+            &self.linear_deadzone_slope,
+            &self.linear_deadzone_threshold_int,
+            &self.linear_deadzone_threshold,
+        ];
+
+        params
+            .iter()
+            .all(|param| param.iter().flatten().all(|v| *v == 0))
+    }
+
     pub fn write(&self, writer: &mut BitVecWriter, header: &RpuDataHeader) -> Result<()> {
         let pivot_idx_count = if let Some(nlq_num_pivots_minus2) = header.nlq_num_pivots_minus2 {
             nlq_num_pivots_minus2 as usize + 1
@@ -0,0 +1,113 @@
+use std::cmp::max;
+
+use serde_json::{Map, Value};
+
+use super::extension_metadata::blocks::ExtMetadataBlock;
+use super::generate::VideoShot;
+
+/// Difference between the metadata blocks of two shots.
+///
+/// Blocks are matched by their sort key: the level, and the target display for L2, L8 and L10.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockDiff {
+    /// Block only present in the first shot
+    Removed { key: (u8, u16) },
+
+    /// Block only present in the second shot
+    Added { key: (u8, u16) },
+
+    /// Field value changed, the values are JSON formatted
+    Changed {
+        key: (u8, u16),
+        field: String,
+        old: String,
+        new: String,
+    },
+}
+
+/// Compares the metadata blocks of two shots, field by field.
+/// The frame edits are not compared.
+pub fn diff_shots(a: &VideoShot, b: &VideoShot) -> Vec<BlockDiff> {
+    let mut keys: Vec<(u8, u16)> = a
+        .metadata_blocks
+        .iter()
+        .chain(b.metadata_blocks.iter())
+        .map(|block| block.sort_key())
+        .collect();
+
+    keys.sort_unstable();
+    keys.dedup();
+
+    let mut diffs = Vec::new();
+
+    for key in keys {
+        let a_blocks = blocks_with_key(a, key);
+        let b_blocks = blocks_with_key(b, key);
+
+        for i in 0..max(a_blocks.len(), b_blocks.len()) {
+            match (a_blocks.get(i), b_blocks.get(i)) {
+                (Some(a_block), Some(b_block)) => {
+                    diff_block_fields(key, a_block, b_block, &mut diffs)
+                }
+                (Some(_), None) => diffs.push(BlockDiff::Removed { key }),
+                _ => diffs.push(BlockDiff::Added { key }),
+            }
+        }
+    }
+
+    diffs
+}
+
+fn blocks_with_key(shot: &VideoShot, key: (u8, u16)) -> Vec<&ExtMetadataBlock> {
+    shot.metadata_blocks
+        .iter()
+        .filter(|block| block.sort_key() == key)
+        .collect()
+}
+
+fn diff_block_fields(
+    key: (u8, u16),
+    a: &ExtMetadataBlock,
+    b: &ExtMetadataBlock,
+    diffs: &mut Vec<BlockDiff>,
+) {
+    let a_fields = block_fields(a);
+    let b_fields = block_fields(b);
+    let null = Value::Null;
+
+    let changed = a_fields
+        .iter()
+        .map(|(field, old)| (field, old, b_fields.get(field).unwrap_or(&null)))
+        .chain(
+            b_fields
+                .iter()
+                .filter(|(field, _)| !a_fields.contains_key(*field))
+                .map(|(field, new)| (field, &null, new)),
+        )
+        .filter(|(_, old, new)| old != new);
+
+    for (field, old, new) in changed {
+        diffs.push(BlockDiff::Changed {
+            key,
+            field: field.clone(),
+            old: old.to_string(),
+            new: new.to_string(),
+        });
+    }
+}
+
+/// Fields of the block, in declaration order
+fn block_fields(block: &ExtMetadataBlock) -> Map<String, Value> {
+    // Serialized as { "LevelN": { fields } }
+    match serde_json::to_value(block) {
+        Ok(Value::Object(variant)) => variant
+            .into_iter()
+            .next()
+            .and_then(|(_, fields)| match fields {
+                Value::Object(fields) => Some(fields),
+                _ => None,
+            })
+            .unwrap_or_default(),
+        _ => Map::new(),
+    }
+}
@@ -0,0 +1,193 @@
+use anyhow::{bail, ensure, Result};
+
+use super::dovi_rpu::DoviRpu;
+use super::extension_metadata::blocks::{
+    ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel2, ExtMetadataBlockLevel6,
+    ExtMetadataBlockLevel8,
+};
+use super::vdr_dm_data::CmVersion;
+use crate::utils::nits_to_pq;
+
+/// Number of 12 bit PQ codes
+const PQ_CODES: usize = 4096;
+
+/// QC marker of a frame, with the L1 `max_pq` of the frame
+#[derive(Debug, Clone, PartialEq)]
+pub struct FrameMarker {
+    pub frame: usize,
+    pub max_pq: Option<u16>,
+    pub note: String,
+}
+
+fn level1_block(rpu: &DoviRpu) -> Option<&ExtMetadataBlockLevel1> {
+    match rpu.vdr_dm_data.as_ref()?.get_block(1)? {
+        ExtMetadataBlock::Level1(block) => Some(block),
+        _ => None,
+    }
+}
+
+fn level6_block(rpu: &DoviRpu) -> Option<&ExtMetadataBlockLevel6> {
+    match rpu.vdr_dm_data.as_ref()?.get_block(6)? {
+        ExtMetadataBlock::Level6(block) => Some(block),
+        _ => None,
+    }
+}
+
+/// L2 and L8 trim blocks of a frame, in the RPU order
+fn trim_blocks(rpu: &DoviRpu) -> (Vec<&ExtMetadataBlockLevel2>, Vec<&ExtMetadataBlockLevel8>) {
+    let mut trims = (Vec::new(), Vec::new());
+
+    if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_ref() {
+        let blocks = vdr_dm_data
+            .level_blocks_iter(2)
+            .chain(vdr_dm_data.level_blocks_iter(8));
+
+        for block in blocks {
+            match block {
+                ExtMetadataBlock::Level2(level2) => trims.0.push(level2),
+                ExtMetadataBlock::Level8(level8) => trims.1.push(level8),
+                _ => (),
+            }
+        }
+    }
+
+    trims
+}
+
+/// Counts the frames per L1 `max_pq` range, with `buckets` ranges of equal size
+/// covering the 12 bit PQ range.
+/// Frames without L1 metadata are not counted.
+pub fn pq_histogram(rpus: &[DoviRpu], buckets: usize) -> Vec<u64> {
+    let mut histogram = vec![0; buckets];
+
+    if buckets == 0 {
+        return histogram;
+    }
+
+    rpus.iter().filter_map(level1_block).for_each(|level1| {
+        let index = (level1.max_pq as usize).min(PQ_CODES - 1) * buckets / PQ_CODES;
+        histogram[index] += 1;
+    });
+
+    histogram
+}
+
+/// Index of the frame with the highest L1 `max_pq`, the first one if there are several.
+/// Returns `None` if no frame has L1 metadata.
+pub fn brightest_frame(rpus: &[DoviRpu]) -> Option<usize> {
+    rpus.iter()
+        .enumerate()
+        .filter_map(|(i, rpu)| level1_block(rpu).map(|level1| (i, level1.max_pq)))
+        .rev()
+        .max_by_key(|(_, max_pq)| *max_pq)
+        .map(|(i, _)| i)
+}
+
+/// Index of the frame with the lowest L1 `max_pq`, the first one if there are several.
+/// Returns `None` if no frame has L1 metadata.
+pub fn darkest_frame(rpus: &[DoviRpu]) -> Option<usize> {
+    rpus.iter()
+        .enumerate()
+        .filter_map(|(i, rpu)| level1_block(rpu).map(|level1| (i, level1.max_pq)))
+        .min_by_key(|(_, max_pq)| *max_pq)
+        .map(|(i, _)| i)
+}
+
+/// Indices of the frames whose L1 `max_pq` exceeds the L6 MaxCLL, converted to PQ.
+/// Frames without L1 or L6 metadata, or with an unknown MaxCLL (0), are not checked.
+pub fn validate_l1_l6_consistency(rpus: &[DoviRpu]) -> Vec<usize> {
+    rpus.iter()
+        .enumerate()
+        .filter(|(_, rpu)| match (level1_block(rpu), level6_block(rpu)) {
+            (Some(level1), Some(level6)) if level6.max_content_light_level > 0 => {
+                let max_cll_pq =
+                    (nits_to_pq(level6.max_content_light_level.into()) * 4095.0).round() as u16;
+
+                level1.max_pq > max_cll_pq
+            }
+            _ => false,
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Indices of the frames whose L2 or L8 trims differ from the previous frame,
+/// without a scene refresh: the trims change within a scene, usually from a frame edit.
+pub fn trim_change_frames(rpus: &[DoviRpu]) -> Vec<usize> {
+    rpus.windows(2)
+        .enumerate()
+        .filter(|(_, pair)| {
+            let scene_refresh = pair[1]
+                .vdr_dm_data
+                .as_ref()
+                .map_or(false, |vdr_dm_data| vdr_dm_data.scene_refresh_flag != 0);
+
+            !scene_refresh && trim_blocks(&pair[0]) != trim_blocks(&pair[1])
+        })
+        .map(|(i, _)| i + 1)
+        .collect()
+}
+
+/// CM version common to every RPU with DM metadata.
+/// A mix of versions usually comes from concatenating RPUs of different sources,
+/// the error lists the frames with a version other than the first frame's.
+pub fn check_cm_version_consistency(rpus: &[DoviRpu]) -> Result<CmVersion> {
+    let versions: Vec<(usize, CmVersion)> = rpus
+        .iter()
+        .enumerate()
+        .filter_map(|(i, rpu)| {
+            rpu.vdr_dm_data
+                .as_ref()
+                .map(|vdr_dm_data| (i, vdr_dm_data.cm_version()))
+        })
+        .collect();
+
+    let (first_frame, cm_version) = match versions.first() {
+        Some(&first) => first,
+        None => bail!("No RPU with DM metadata"),
+    };
+
+    let differing_frames: Vec<usize> = versions
+        .iter()
+        .filter(|(_, version)| *version != cm_version)
+        .map(|(i, _)| *i)
+        .collect();
+
+    ensure!(
+        differing_frames.is_empty(),
+        "Inconsistent CM version: {:?} at frame {}, but not for frames {:?}",
+        cm_version,
+        first_frame,
+        differing_frames
+    );
+
+    Ok(cm_version)
+}
+
+/// Highest target display peak brightness with a trim across the RPUs, in nits.
+/// See `DoviRpu::peak_target_nits`, returns 0 without any trim.
+pub fn list_peak_target_nits(rpus: &[DoviRpu]) -> u16 {
+    rpus.iter()
+        .map(|rpu| rpu.peak_target_nits())
+        .max()
+        .unwrap_or(0)
+}
+
+/// Markers of the frames present in the RPU list, in frame order.
+/// Markers after the last frame are ignored.
+pub fn frame_markers(rpus: &[DoviRpu], markers: &[(usize, String)]) -> Vec<FrameMarker> {
+    let mut frame_markers: Vec<FrameMarker> = markers
+        .iter()
+        .filter_map(|(frame, note)| {
+            rpus.get(*frame).map(|rpu| FrameMarker {
+                frame: *frame,
+                max_pq: level1_block(rpu).map(|level1| level1.max_pq),
+                note: note.clone(),
+            })
+        })
+        .collect();
+
+    frame_markers.sort_by_key(|marker| marker.frame);
+
+    frame_markers
+}
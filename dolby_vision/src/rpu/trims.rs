@@ -0,0 +1,71 @@
+use std::cmp::min;
+
+use super::dovi_rpu::DoviRpu;
+use super::extension_metadata::blocks::{ExtMetadataBlock, MAX_12_BIT_VALUE};
+
+/// 12 bit trim power from the trim gamma, as found in the L2 and L8 XML trims.
+/// The gamma is clamped to [-1.0, 1.0], the trim power saturates at 4095 below -0.666.
+pub fn gamma_to_trim_power(gamma: f32) -> u16 {
+    let gamma = gamma.clamp(-1.0, 1.0);
+
+    min(
+        4095,
+        (((2.0 / (1.0 + gamma / 2.0) - 2.0) * 2048.0) + 2048.0).round() as u16,
+    )
+}
+
+/// Trim gamma from the 12 bit trim power, inverse of `gamma_to_trim_power`
+pub fn trim_power_to_gamma(trim_power: u16) -> f32 {
+    let power = (trim_power as f32 - 2048.0) / 2048.0;
+
+    4.0 / (power + 2.0) - 2.0
+}
+
+/// Signed L2 MS weight from the XML MS weight, clamped to [-1, 4095].
+/// The L8 MS weight is unsigned, negative values are 0 for L8.
+pub fn weight_to_ms_weight(weight: f32) -> i16 {
+    (((weight * 2048.0) + 2048.0).round() as i16).clamp(-1, MAX_12_BIT_VALUE as i16)
+}
+
+/// Trim of a normalized input: `(input * slope + offset) ^ power`, clamped to [0.0, 1.0].
+/// The slope, offset and power are the 12 bit values of the L2 and L8 trims.
+pub fn apply_trim(input: f64, trim_slope: u16, trim_offset: u16, trim_power: u16) -> f64 {
+    let slope = trim_slope as f64 / 4096.0 + 0.5;
+    let offset = trim_offset as f64 / 4096.0 - 0.5;
+    let power = trim_power as f64 / 4096.0 + 0.5;
+
+    (input * slope + offset).clamp(0.0, 1.0).powf(power)
+}
+
+/// Offsets the saturation gain and chroma weight of every L2 trim, for a global look adjustment.
+/// L2 has no hue trim, `hue_delta` is applied to the chroma weight.
+/// The adjusted values are clamped to the 12 bit range.
+pub fn adjust_l2(rpus: &mut [DoviRpu], sat_delta: i16, hue_delta: i16) {
+    let offset = |value: u16, delta: i16| -> u16 {
+        (value as i32 + delta as i32).clamp(0, MAX_12_BIT_VALUE as i32) as u16
+    };
+
+    for rpu in rpus.iter_mut() {
+        if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_mut() {
+            for block in vdr_dm_data.level_blocks_iter_mut(2) {
+                if let ExtMetadataBlock::Level2(level2) = block {
+                    level2.trim_saturation_gain = offset(level2.trim_saturation_gain, sat_delta);
+                    level2.trim_chroma_weight = offset(level2.trim_chroma_weight, hue_delta);
+                }
+            }
+
+            rpu.modified = true;
+        }
+    }
+}
+
+/// Clamps the metadata of every RPU to a display of `peak_nits` peak brightness,
+/// see `VdrDmData::clamp_to_display`.
+pub fn clamp_to_display(rpus: &mut [DoviRpu], peak_nits: u16) {
+    for rpu in rpus.iter_mut() {
+        if let Some(vdr_dm_data) = rpu.vdr_dm_data.as_mut() {
+            vdr_dm_data.clamp_to_display(peak_nits);
+            rpu.modified = true;
+        }
+    }
+}
@@ -2,10 +2,13 @@ use anyhow::{bail, ensure, Result};
 use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
 
 #[cfg(feature = "serde_feature")]
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
-use super::dovi_rpu::DoviRpu;
-use super::extension_metadata::blocks::{ExtMetadataBlock, ExtMetadataBlockLevel11};
+use super::dovi_rpu::{DoviRpu, RpuParseOpts};
+use super::extension_metadata::blocks::{
+    ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel11, ExtMetadataBlockLevel2,
+    ExtMetadataBlockLevel3, ExtMetadataBlockLevel9,
+};
 use super::extension_metadata::*;
 use super::generate::GenerateConfig;
 use super::profiles::profile81::Profile81;
@@ -54,27 +57,54 @@ pub struct VdrDmData {
 
     #[cfg_attr(
         feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "deserialize_cmv29_metadata"
+        )
     )]
     pub cmv29_metadata: Option<DmData>,
     #[cfg_attr(
         feature = "serde_feature",
-        serde(skip_serializing_if = "Option::is_none")
+        serde(
+            default,
+            skip_serializing_if = "Option::is_none",
+            deserialize_with = "deserialize_cmv40_metadata"
+        )
     )]
     pub cmv40_metadata: Option<DmData>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 #[cfg_attr(feature = "serde_feature", derive(Deserialize, Serialize))]
 pub enum CmVersion {
     V29,
     V40,
 }
 
-pub fn vdr_dm_data_payload(dovi_rpu: &mut DoviRpu, reader: &mut BitVecReader) -> Result<()> {
+// `DmData` is untagged, the version is known from the field
+#[cfg(feature = "serde_feature")]
+fn deserialize_cmv29_metadata<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DmData>, D::Error> {
+    Ok(Option::<CmV29DmData>::deserialize(deserializer)?.map(DmData::V29))
+}
+
+#[cfg(feature = "serde_feature")]
+fn deserialize_cmv40_metadata<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DmData>, D::Error> {
+    Ok(Option::<CmV40DmData>::deserialize(deserializer)?.map(DmData::V40))
+}
+
+pub fn vdr_dm_data_payload(
+    dovi_rpu: &mut DoviRpu,
+    reader: &mut BitVecReader,
+    opts: &RpuParseOpts,
+) -> Result<()> {
     let mut vdr_dm_data = VdrDmData::parse(reader)?;
 
-    if let Some(cmv29_dm_data) = DmData::parse::<CmV29DmData>(reader)? {
+    if let Some(cmv29_dm_data) = DmData::parse_with_opts::<CmV29DmData>(reader, opts)? {
         vdr_dm_data.cmv29_metadata = Some(DmData::V29(cmv29_dm_data));
     }
 
@@ -82,7 +112,7 @@ pub fn vdr_dm_data_payload(dovi_rpu: &mut DoviRpu, reader: &mut BitVecReader) ->
 
     // 40 or 48 w/ CRC32 + 16 bits required level 254
     if reader.available() >= final_length + 16 {
-        if let Some(cmv40_dm_data) = DmData::parse::<CmV40DmData>(reader)? {
+        if let Some(cmv40_dm_data) = DmData::parse_with_opts::<CmV40DmData>(reader, opts)? {
             vdr_dm_data.cmv40_metadata = Some(DmData::V40(cmv40_dm_data));
         }
     }
@@ -167,7 +197,29 @@ impl VdrDmData {
         Ok(())
     }
 
+    /// Ensures the L5 and L6 (CM v2.9) and L254 (CM v4.0) blocks are present.
+    pub fn validate_required_blocks(&self) -> Result<()> {
+        if let Some(cmv29) = &self.cmv29_metadata {
+            cmv29.validate_required_blocks()?;
+        }
+
+        if let Some(cmv40) = &self.cmv40_metadata {
+            cmv40.validate_required_blocks()?;
+        }
+
+        Ok(())
+    }
+
     pub fn write(&self, writer: &mut BitVecWriter) -> Result<()> {
+        self.write_validated(writer, false)
+    }
+
+    /// Writes the DM data, first validating the required blocks if `validate_required` is set.
+    pub fn write_validated(
+        &self,
+        writer: &mut BitVecWriter,
+        validate_required: bool,
+    ) -> Result<()> {
         writer.write_ue(self.affected_dm_metadata_id);
         writer.write_ue(self.current_dm_metadata_id);
         writer.write_ue(self.scene_refresh_flag);
@@ -211,11 +263,11 @@ impl VdrDmData {
         writer.write_n(&self.source_diagonal.to_be_bytes(), 10);
 
         if let Some(cmv29) = &self.cmv29_metadata {
-            cmv29.write(writer)?;
+            cmv29.write_validated(writer, validate_required)?;
         }
 
         if let Some(cmv40) = &self.cmv40_metadata {
-            cmv40.write(writer)?;
+            cmv40.write_validated(writer, validate_required)?;
         }
 
         Ok(())
@@ -241,6 +293,15 @@ impl VdrDmData {
         None
     }
 
+    /// CM v4.0 if the CM v4.0 metadata is present, CM v2.9 otherwise
+    pub fn cm_version(&self) -> CmVersion {
+        if self.cmv40_metadata.is_some() {
+            CmVersion::V40
+        } else {
+            CmVersion::V29
+        }
+    }
+
     pub fn metadata_blocks(&self, level: u8) -> Option<&Vec<ExtMetadataBlock>> {
         self.extension_metadata_for_level(level)
             .map(|dm_data| match dm_data {
@@ -282,6 +343,20 @@ impl VdrDmData {
         self.level_blocks_iter_mut(level).next()
     }
 
+    /// L1 metadata after applying the L3 offsets, as used by the display.
+    /// Without L3 metadata, the L1 metadata is returned unchanged.
+    pub fn effective_l1(&self) -> Option<ExtMetadataBlockLevel1> {
+        let level1 = match self.get_block(1)? {
+            ExtMetadataBlock::Level1(b) => b,
+            _ => return None,
+        };
+
+        match self.get_block(3) {
+            Some(ExtMetadataBlock::Level3(level3)) => Some(level3.apply_to(level1)),
+            _ => Some(level1.clone()),
+        }
+    }
+
     pub fn add_metadata_block(&mut self, block: ExtMetadataBlock) -> Result<()> {
         let level = block.level();
 
@@ -304,6 +379,157 @@ impl VdrDmData {
         }
     }
 
+    /// Removes the metadata of every target display other than the one of `target_nits`.
+    ///
+    /// The L1, L5, L6 and L254 blocks are kept, with the L2 trim of the target display.
+    /// For CM v4.0, the L8 trim and L10 target display with the same peak brightness are kept.
+    pub fn retain_target_display(&mut self, target_nits: u16) {
+        let target_max_pq = ExtMetadataBlockLevel2::from_nits(target_nits).target_max_pq;
+
+        let target_display_indices: Vec<u8> = self
+            .level_blocks_iter(10)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level10(b) if b.target_max_pq == target_max_pq => {
+                    Some(b.target_display_index)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let keep_block = |block: &ExtMetadataBlock| match block {
+            ExtMetadataBlock::Level1(_)
+            | ExtMetadataBlock::Level5(_)
+            | ExtMetadataBlock::Level6(_)
+            | ExtMetadataBlock::Level254(_) => true,
+            ExtMetadataBlock::Level2(b) => b.target_max_pq == target_max_pq,
+            ExtMetadataBlock::Level8(b) => target_display_indices.contains(&b.target_display_index),
+            ExtMetadataBlock::Level10(b) => {
+                target_display_indices.contains(&b.target_display_index)
+            }
+            _ => false,
+        };
+
+        for dm_data in [&mut self.cmv29_metadata, &mut self.cmv40_metadata].iter_mut() {
+            match dm_data {
+                Some(DmData::V29(meta)) => meta.retain_blocks(keep_block),
+                Some(DmData::V40(meta)) => meta.retain_blocks(keep_block),
+                None => (),
+            }
+        }
+    }
+
+    /// Clamps the metadata to a display of `peak_nits` peak brightness.
+    ///
+    /// The L1 max PQ and the L6 MaxCLL and MaxFALL are clamped to the peak brightness.
+    /// The trims for brighter target displays are removed: L2 trims, and L8 trims along with
+    /// their L10 target display.
+    ///
+    /// L8 trims for preset target displays are kept, unlike the L2 trims which carry their
+    /// target max PQ. A preset target display is only signaled by its index, without L10
+    /// metadata, so its peak brightness is not known from the RPU to be compared.
+    pub fn clamp_to_display(&mut self, peak_nits: u16) {
+        let peak_pq = ExtMetadataBlockLevel2::from_nits(peak_nits).target_max_pq;
+
+        for block in self.level_blocks_iter_mut(1) {
+            if let ExtMetadataBlock::Level1(level1) = block {
+                if level1.max_pq > peak_pq {
+                    *level1 =
+                        ExtMetadataBlockLevel1::from_stats(level1.min_pq, peak_pq, level1.avg_pq);
+                }
+            }
+        }
+
+        for block in self.level_blocks_iter_mut(6) {
+            if let ExtMetadataBlock::Level6(level6) = block {
+                level6.max_content_light_level = level6.max_content_light_level.min(peak_nits);
+                level6.max_frame_average_light_level =
+                    level6.max_frame_average_light_level.min(peak_nits);
+            }
+        }
+
+        let brighter_indices: Vec<u8> = self
+            .level_blocks_iter(10)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level10(b) if b.target_max_pq > peak_pq => {
+                    Some(b.target_display_index)
+                }
+                _ => None,
+            })
+            .collect();
+
+        let keep_block = |block: &ExtMetadataBlock| match block {
+            ExtMetadataBlock::Level2(b) => b.target_max_pq <= peak_pq,
+            ExtMetadataBlock::Level8(b) => !brighter_indices.contains(&b.target_display_index),
+            ExtMetadataBlock::Level10(b) => !brighter_indices.contains(&b.target_display_index),
+            _ => true,
+        };
+
+        for dm_data in [&mut self.cmv29_metadata, &mut self.cmv40_metadata].iter_mut() {
+            match dm_data {
+                Some(DmData::V29(meta)) => meta.retain_blocks(keep_block),
+                Some(DmData::V40(meta)) => meta.retain_blocks(keep_block),
+                None => (),
+            }
+        }
+    }
+
+    /// Adds the L3 offsets of the L8 trim with the lowest target display index on the L1 values.
+    /// Nothing is added without L1 or L8 metadata.
+    pub fn add_level3_from_level8(&mut self) -> Result<()> {
+        let level1 = match self.get_block(1) {
+            Some(ExtMetadataBlock::Level1(level1)) => level1,
+            _ => return Ok(()),
+        };
+
+        let level8 = self
+            .level_blocks_iter(8)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level8(level8) => Some(level8),
+                _ => None,
+            })
+            .min_by_key(|level8| level8.target_display_index);
+
+        if let Some(level8) = level8 {
+            let level3 = ExtMetadataBlockLevel3::from_level1_trim(level1, level8);
+            self.replace_metadata_block(ExtMetadataBlock::Level3(level3))?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the variable length blocks in their shortest form.
+    /// See `ExtMetadataBlockLevel9::canonicalize` and `ExtMetadataBlockLevel10::canonicalize`.
+    /// L8 fields equal to their default values are already omitted when written.
+    pub fn canonicalize_blocks(&mut self) {
+        for block in self.level_blocks_iter_mut(9) {
+            if let ExtMetadataBlock::Level9(level9) = block {
+                level9.canonicalize();
+            }
+        }
+
+        for block in self.level_blocks_iter_mut(10) {
+            if let ExtMetadataBlock::Level10(level10) = block {
+                level10.canonicalize();
+            }
+        }
+    }
+
+    /// Removes the CM v4.0 metadata and every CM v2.9 block other than L1, L5 and L6.
+    pub fn retain_minimal_hdr(&mut self) {
+        self.cmv40_metadata = None;
+
+        if let Some(DmData::V29(meta)) = &mut self.cmv29_metadata {
+            meta.retain_blocks(|block| {
+                matches!(
+                    block,
+                    ExtMetadataBlock::Level1(_)
+                        | ExtMetadataBlock::Level5(_)
+                        | ExtMetadataBlock::Level6(_)
+                )
+            });
+        }
+    }
+
     pub fn replace_metadata_level(&mut self, block: ExtMetadataBlock) -> Result<()> {
         let level = block.level();
 
@@ -348,6 +574,8 @@ impl VdrDmData {
             }
             ExtMetadataBlock::Level9(_) => self.replace_metadata_level(block),
             ExtMetadataBlock::Level10(level10) => {
+                level10.validate()?;
+
                 if let Some(dm_data) = self.extension_metadata_for_level_mut(level) {
                     match dm_data {
                         DmData::V40(cmv40) => cmv40.replace_level10_block(level10),
@@ -443,7 +671,13 @@ impl VdrDmData {
             }
             CmVersion::V40 => {
                 vdr_dm_data.cmv29_metadata = Some(DmData::V29(CmV29DmData::default()));
-                vdr_dm_data.cmv40_metadata = Some(DmData::V40(CmV40DmData::new_with_l254()));
+                let cmv40_dm_data = if let Some(level254) = &config.level254 {
+                    CmV40DmData::new_with_custom_l254(level254)
+                } else {
+                    CmV40DmData::new_with_l254()
+                };
+
+                vdr_dm_data.cmv40_metadata = Some(DmData::V40(cmv40_dm_data));
             }
         }
 
@@ -460,6 +694,12 @@ impl VdrDmData {
             ExtMetadataBlockLevel11::default_reference_cinema(),
         ))?;
 
+        if let Some(primaries) = config.default_source_primaries {
+            self.replace_metadata_block(ExtMetadataBlock::Level9(
+                ExtMetadataBlockLevel9::from_named_gamut(primaries),
+            ))?;
+        }
+
         if !config.default_metadata_blocks.is_empty() {
             let level_block_list: &[u8] = &[5, 6, 254];
 
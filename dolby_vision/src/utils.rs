@@ -1,7 +1,10 @@
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
 #[cfg(feature = "serde_feature")]
 use {
     bitvec::prelude::*,
-    serde::{ser::Serializer, Serialize},
+    serde::{de::Deserializer, ser::Serializer, Deserialize, Serialize},
 };
 
 pub const ST2084_Y_MAX: f64 = 10000.0;
@@ -11,6 +14,59 @@ pub const ST2084_C1: f64 = 3424.0 / 4096.0;
 pub const ST2084_C2: f64 = (2413.0 / 4096.0) * 32.0;
 pub const ST2084_C3: f64 = (2392.0 / 4096.0) * 32.0;
 
+/// Peak brightness of the standard mastering/target displays, in nits
+pub const STANDARD_TARGET_NITS: &[u16] = &[100, 600, 1000, 2000, 4000, 10000];
+
+type WarningHandler = dyn Fn(&str) + Send + Sync;
+
+/// Receives the warnings emitted while parsing or generating metadata.
+/// By default, warnings are printed to stdout.
+#[derive(Clone, Default)]
+pub struct WarningSink {
+    handler: Option<Arc<WarningHandler>>,
+}
+
+impl WarningSink {
+    /// Sink passing the warnings to `handler`, which can be called from any thread
+    pub fn new<F: Fn(&str) + Send + Sync + 'static>(handler: F) -> Self {
+        Self {
+            handler: Some(Arc::new(handler)),
+        }
+    }
+
+    /// Sink collecting the warnings in the returned list
+    pub fn capture() -> (Self, Arc<Mutex<Vec<String>>>) {
+        let warnings = Arc::new(Mutex::new(Vec::new()));
+        let captured = warnings.clone();
+
+        let sink = Self::new(move |msg| {
+            captured
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .push(msg.to_string())
+        });
+
+        (sink, warnings)
+    }
+
+    /// Emits a warning to the handler, or prints it to stdout
+    pub fn warn(&self, msg: &str) {
+        if let Some(handler) = &self.handler {
+            handler(msg);
+        } else {
+            println!("{}", msg);
+        }
+    }
+}
+
+impl fmt::Debug for WarningSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WarningSink")
+            .field("handler", &self.handler.is_some())
+            .finish()
+    }
+}
+
 /// Helper function to calculate PQ codes from nits (cd/m2) values
 #[inline(always)]
 pub fn nits_to_pq(nits: f64) -> f64 {
@@ -20,6 +76,36 @@ pub fn nits_to_pq(nits: f64) -> f64 {
         .powf(ST2084_M2)
 }
 
+/// Helper function to calculate nits (cd/m2) values from normalized PQ values
+#[inline(always)]
+pub fn pq_to_nits(pq: f64) -> f64 {
+    let v = pq.powf(1.0 / ST2084_M2);
+
+    ((v - ST2084_C1).max(0.0) / (ST2084_C2 - ST2084_C3 * v)).powf(1.0 / ST2084_M1) * ST2084_Y_MAX
+}
+
+/// Snaps a target display peak brightness to the nearest standard target, in nits.
+/// The lower target is used when halfway between two targets.
+pub fn nearest_standard_target(nits: u16) -> u16 {
+    *STANDARD_TARGET_NITS
+        .iter()
+        .min_by_key(|&&target| (i32::from(target) - i32::from(nits)).abs())
+        .unwrap()
+}
+
+/// Peak brightness of a target display from its 12 bit PQ code, in nits.
+/// Codes of a standard target are that target, other codes are rounded to the nearest nit.
+pub fn target_pq_to_nits(target_max_pq: u16) -> u16 {
+    let nits = pq_to_nits(target_max_pq as f64 / 4095.0).round() as u16;
+    let standard_nits = nearest_standard_target(nits);
+
+    if (nits_to_pq(standard_nits.into()) * 4095.0).round() as u16 == target_max_pq {
+        standard_nits
+    } else {
+        nits
+    }
+}
+
 /// Serializing a bitvec as a vec of bits
 #[cfg(feature = "serde_feature")]
 pub fn bitvec_ser_bits<S: Serializer>(bitvec: &BitVec<Msb0, u8>, s: S) -> Result<S::Ok, S::Error> {
@@ -27,6 +113,13 @@ pub fn bitvec_ser_bits<S: Serializer>(bitvec: &BitVec<Msb0, u8>, s: S) -> Result
     bits.serialize(s)
 }
 
+/// Deserializing a bitvec from a vec of bits
+#[cfg(feature = "serde_feature")]
+pub fn bitvec_de_bits<'de, D: Deserializer<'de>>(d: D) -> Result<BitVec<Msb0, u8>, D::Error> {
+    let bits: Vec<u8> = Vec::deserialize(d)?;
+    Ok(bits.into_iter().map(|b| b != 0).collect())
+}
+
 /// Copied from hevc_parser for convenience, and to avoid a dependency
 /// Unescapes a byte slice from annexb.
 /// Allocates a new Vec.
@@ -0,0 +1,128 @@
+use std::fmt;
+
+/// Errors of the XML metadata parsing.
+///
+/// Returned wrapped in `anyhow::Error`, use `downcast_ref::<XmlParseError>` to categorize them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmlParseError {
+    /// The document is not valid XML
+    InvalidDocument {
+        message: String,
+    },
+
+    /// A required node is missing, or has no text
+    MissingNode {
+        name: String,
+    },
+
+    /// No version attribute or node was found
+    MissingVersion,
+
+    /// The version is known, but not supported
+    UnhandledLegacyVersion {
+        found: String,
+    },
+
+    InvalidVersion {
+        found: String,
+    },
+
+    /// A trim has the wrong number of values
+    TrimValueCount {
+        level: u8,
+        target_id: Option<String>,
+        expected: usize,
+        found: usize,
+        raw: String,
+    },
+
+    /// A field other than the trim has the wrong number of values
+    FieldValueCount {
+        level: u8,
+        field: String,
+        expected: usize,
+        found: usize,
+    },
+
+    /// A value could not be parsed as a number
+    InvalidValue {
+        field: String,
+        value: String,
+    },
+
+    /// A trim references a target display that isn't defined
+    UndefinedTarget {
+        id: String,
+    },
+
+    /// Several shots have the same UniqueID
+    DuplicateShotId {
+        id: String,
+    },
+
+    /// A shot has no `Duration`, and no following shot or sequence length to end it
+    UnknownShotDuration {
+        id: String,
+    },
+}
+
+impl fmt::Display for XmlParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            XmlParseError::InvalidDocument { message } => write!(f, "Invalid XML: {}", message),
+            XmlParseError::MissingNode { name } => write!(f, "Could not find {} node", name),
+            XmlParseError::MissingVersion => write!(f, "No XML version found!"),
+            XmlParseError::UnhandledLegacyVersion { found } => write!(
+                f,
+                "Unhandled legacy XML version {} found! Please open an issue.",
+                found
+            ),
+            XmlParseError::InvalidVersion { found } => {
+                write!(f, "invalid XML version {} found!", found)
+            }
+            XmlParseError::TrimValueCount {
+                level,
+                target_id,
+                expected,
+                found,
+                raw,
+            } => {
+                write!(f, "Invalid L{} trim", level)?;
+
+                if let Some(id) = target_id {
+                    write!(f, " for target display {}", id)?;
+                }
+
+                write!(
+                    f,
+                    ": should be {} values, found {} in '{}'",
+                    expected, found, raw
+                )
+            }
+            XmlParseError::FieldValueCount {
+                level,
+                field,
+                expected,
+                found,
+            } => write!(
+                f,
+                "Invalid L{} {}: should be {} values, found {}",
+                level, field, expected, found
+            ),
+            XmlParseError::InvalidValue { field, value } => {
+                write!(f, "Invalid {} value '{}'", field, value)
+            }
+            XmlParseError::UndefinedTarget { id } => {
+                write!(f, "No target display found for ID {}", id)
+            }
+            XmlParseError::DuplicateShotId { id } => {
+                write!(f, "Duplicate shot UniqueID {}", id)
+            }
+            XmlParseError::UnknownShotDuration { id } => {
+                write!(f, "Shot {} has no Duration, and its end is unknown", id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for XmlParseError {}
@@ -1,7 +1,15 @@
 /// XML metadata parser
 mod parser;
 
+/// XML metadata writer
+mod writer;
+
+/// XML parsing errors
+mod error;
+
 #[cfg(test)]
 mod tests;
 
+pub use error::XmlParseError;
 pub use parser::{CmXmlParser, XmlParserOpts};
+pub use writer::{CmXmlWriter, XmlWriterOpts};
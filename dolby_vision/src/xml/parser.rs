@@ -1,18 +1,24 @@
-use anyhow::{bail, ensure, Result};
+use anyhow::{ensure, Result};
 use roxmltree::{Document, Node};
 use std::cmp::min;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
+use std::str::FromStr;
 
 use crate::rpu::extension_metadata::blocks::*;
 use crate::rpu::generate::{GenerateConfig, ShotFrameEdit, VideoShot};
+use crate::rpu::trims::{gamma_to_trim_power, weight_to_ms_weight};
 use crate::rpu::vdr_dm_data::CmVersion;
-use crate::utils::nits_to_pq;
+use crate::utils::{nits_to_pq, WarningSink};
+
+use super::XmlParseError;
 
 use level10::PRESET_TARGET_DISPLAYS;
 
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
 #[derive(Default, Debug)]
 pub struct CmXmlParser {
     opts: XmlParserOpts,
@@ -24,10 +30,21 @@ pub struct CmXmlParser {
     pub config: GenerateConfig,
 }
 
+/// Options of the XML parser, built from `XmlParserOpts::default()`.
 #[derive(Default, Debug)]
+#[non_exhaustive]
 pub struct XmlParserOpts {
     pub canvas_width: Option<u16>,
     pub canvas_height: Option<u16>,
+
+    /// Fail on suspicious metadata instead of warning, such as duplicate shot IDs
+    pub strict: bool,
+
+    /// Length of the sequence in frames, for a last shot without `Duration`
+    pub length: Option<usize>,
+
+    /// Receives the warnings, also used when generating from the parsed config
+    pub warnings: WarningSink,
 }
 
 #[derive(Default, Debug)]
@@ -38,21 +55,86 @@ pub struct TargetDisplay {
     primaries: String,
 }
 
+impl XmlParserOpts {
+    /// Canvas dimensions, required to compute the L5 metadata
+    pub fn with_canvas(mut self, width: Option<u16>, height: Option<u16>) -> Self {
+        self.canvas_width = width;
+        self.canvas_height = height;
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn with_length(mut self, length: Option<usize>) -> Self {
+        self.length = length;
+        self
+    }
+
+    pub fn with_warnings(mut self, warnings: WarningSink) -> Self {
+        self.warnings = warnings;
+        self
+    }
+}
+
 impl CmXmlParser {
+    /// Gzipped files are decompressed, from either the `.gz` extension or the gzip magic bytes.
+    /// Decompressing requires the `xml-gz` feature.
     pub fn parse_file(file_path: &Path, opts: XmlParserOpts) -> Result<CmXmlParser> {
-        let mut s = String::new();
-        File::open(file_path)?.read_to_string(&mut s)?;
+        let mut data = Vec::new();
+        File::open(file_path)?.read_to_end(&mut data)?;
+
+        let is_gzip = data.starts_with(&GZIP_MAGIC)
+            || file_path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map_or(false, |ext| ext.eq_ignore_ascii_case("gz"));
+
+        let s = if is_gzip {
+            Self::decompress_gzip(file_path, &data)?
+        } else {
+            String::from_utf8(data)?
+        };
 
         Self::new(s, opts)
     }
 
+    #[cfg(feature = "xml-gz")]
+    fn decompress_gzip(_file_path: &Path, data: &[u8]) -> Result<String> {
+        use flate2::read::GzDecoder;
+
+        let mut s = String::new();
+        GzDecoder::new(data).read_to_string(&mut s)?;
+
+        Ok(s)
+    }
+
+    #[cfg(not(feature = "xml-gz"))]
+    fn decompress_gzip(file_path: &Path, _data: &[u8]) -> Result<String> {
+        anyhow::bail!(
+            "Cannot read gzipped XML {:?}: the `xml-gz` feature is not enabled",
+            file_path
+        )
+    }
+
     pub fn new(s: String, opts: XmlParserOpts) -> Result<CmXmlParser> {
         let mut parser = CmXmlParser {
             opts,
             ..Default::default()
         };
+        parser.config.warnings = parser.opts.warnings.clone();
 
-        let doc = roxmltree::Document::parse(&s).unwrap();
+        let doc = match roxmltree::Document::parse(&s) {
+            Ok(doc) => doc,
+            Err(e) => {
+                return Err(XmlParseError::InvalidDocument {
+                    message: e.to_string(),
+                }
+                .into())
+            }
+        };
 
         parser.xml_version = parser.parse_xml_version(&doc)?;
 
@@ -64,13 +146,11 @@ impl CmXmlParser {
         }
 
         if let Some(output) = doc.descendants().find(|e| e.has_tag_name("Output")) {
-            parser.parse_global_level5(&output)?;
-
             if let Some(video) = output.descendants().find(|e| e.has_tag_name("Video")) {
                 let (max_frame_average_light_level, max_content_light_level) =
-                    parser.parse_level6(&video);
+                    parser.parse_level6(&video)?;
                 let (min_display_mastering_luminance, max_display_mastering_luminance) =
-                    parser.parse_mastering_display_metadata(&video);
+                    parser.parse_mastering_display_metadata(&video)?;
 
                 parser.config.level6 = ExtMetadataBlockLevel6 {
                     max_display_mastering_luminance,
@@ -79,22 +159,28 @@ impl CmXmlParser {
                     max_frame_average_light_level,
                 };
 
-                parser.target_displays = parser.parse_target_displays(&video);
-
-                let (dm_mode, dm_version_index) = parser.parse_level254(&video);
-
+                parser.target_displays = parser.parse_target_displays(&video)?;
 
+                if parser.is_cmv4() {
+                    let (dm_mode, dm_version_index) = parser.parse_level254(&video)?;
 
+                    parser.config.level254 = Some(ExtMetadataBlockLevel254 {
+                        dm_mode,
+                        dm_version_index,
+                    });
+                }
 
                 parser.config.shots = parser.parse_shots(&video)?;
-                parser.config.shots.sort_by_key(|s| s.start);
 
                 parser.config.length = parser.config.shots.iter().map(|s| s.duration).sum();
+
+                // Falls back to the shots L5
+                parser.parse_global_level5(&output)?;
             } else {
-                bail!("Could not find Video node");
+                return Err(missing_node("Video"));
             }
         } else {
-            bail!("Could not find Output node");
+            return Err(missing_node("Output"));
         }
 
         Ok(parser)
@@ -110,242 +196,154 @@ impl CmXmlParser {
                     None
                 };
 
-            let mut rev: u16 = 0;
-
             if let Some(v) = version_attr {
-                let ver: Vec<&str> = v.split('.').collect();
-                ver
-                    .iter()
-                    .rev()
-                    .enumerate()
-                    .for_each(|(i, n)| {
-                        rev += n.parse::<u16>().unwrap() << (i * 4);
-                    });
+                let rev = parse_version(v)?;
                 match rev {
-                    0x205 => {}
-                    0x1 | 0x20 | 0x201 | 0x204 => bail!("Unhandled legacy XML version {} found! Please open an issue.", v),
-                    _ => bail!("invalid XML version {} found!", v)
+                    // The older 2.0.x dynamic metadata is parsed as CM v2.9
+                    0x201 | 0x204 | 0x205 => {}
+                    0x1 | 0x20 => {
+                        return Err(XmlParseError::UnhandledLegacyVersion {
+                            found: v.to_string(),
+                        }
+                        .into())
+                    }
+                    _ => return Err(invalid_version(v)),
                 };
                 Ok(rev)
             } else if let Some(v) = version_node {
-                let ver: Vec<&str> = v.split('.').collect();
-                ver
-                    .iter()
-                    .rev()
-                    .enumerate()
-                    .for_each(|(i, n)| {
-                        rev += n.parse::<u16>().unwrap() << (i * 4);
-                    });
+                let rev = parse_version(v)?;
                 match rev {
                     0x402 | 0x500 | 0x510 => {}
-                    0x510.. => println!("Possibly unhandled new XML version {} found! Please open an issue if you get anything wrong.", v),
-                    _ => bail!("invalid XML version {} found!", v)
+                    0x510.. => self.opts.warnings.warn(&format!("Possibly unhandled new XML version {} found! Please open an issue if you get anything wrong.", v)),
+                    _ => return Err(invalid_version(v)),
                 };
                 Ok(rev)
             } else {
-                bail!("No XML version found!");
+                Err(XmlParseError::MissingVersion.into())
             }
-    } else {
-         bail!("Could not find DolbyLabsMDF root node.");
+        } else {
+            Err(missing_node("DolbyLabsMDF"))
         }
     }
 
-    fn parse_level6(&self, video: &Node) -> (u16, u16) {
+    fn parse_level6(&self, video: &Node) -> Result<(u16, u16)> {
         if let Some(node) = video.descendants().find(|e| e.has_tag_name("Level6")) {
-            let maxfall = if let Some(fall) = node.children().find(|e| e.has_tag_name("MaxFALL")) {
-                fall.text().map_or(0, |e| e.parse::<u16>().unwrap())
-            } else {
-                0
-            };
-
-            let maxcll = if let Some(cll) = node.children().find(|e| e.has_tag_name("MaxCLL")) {
-                cll.text().map_or(0, |e| e.parse::<u16>().unwrap())
-            } else {
-                0
-            };
+            let maxfall = optional_value(&node, "MaxFALL")?.unwrap_or(0);
+            let maxcll = optional_value(&node, "MaxCLL")?.unwrap_or(0);
 
-            (maxfall, maxcll)
+            Ok((maxfall, maxcll))
         } else {
-            (0, 0)
+            Ok((0, 0))
         }
     }
 
-    fn parse_mastering_display_metadata(&self, video: &Node) -> (u16, u16) {
+    fn parse_mastering_display_metadata(&self, video: &Node) -> Result<(u16, u16)> {
         if let Some(node) = video
             .descendants()
             .find(|e| e.has_tag_name("MasteringDisplay"))
         {
-            let min = if let Some(min_brightness) = node
-                .children()
-                .find(|e| e.has_tag_name("MinimumBrightness"))
-            {
-                min_brightness.text().map_or(0, |e| {
-                    let v = e.parse::<f32>().unwrap();
-                    (v * 10000.0) as u16
-                })
-            } else {
-                0
-            };
+            let min = optional_value::<f32>(&node, "MinimumBrightness")?
+                .map_or(0, ExtMetadataBlockLevel6::min_luminance_from_nits);
+            let max = optional_value(&node, "PeakBrightness")?.unwrap_or(0);
 
-            let max = if let Some(max_brightness) =
-                node.children().find(|e| e.has_tag_name("PeakBrightness"))
-            {
-                max_brightness
-                    .text()
-                    .map_or(0, |e| e.parse::<u16>().unwrap())
-            } else {
-                0
-            };
-
-            (min, max)
+            Ok((min, max))
         } else {
-            (0, 0)
+            Ok((0, 0))
         }
     }
 
-    fn parse_target_displays(&mut self, video: &Node) -> HashMap<String, TargetDisplay> {
+    fn parse_target_displays(&mut self, video: &Node) -> Result<HashMap<String, TargetDisplay>> {
         let mut targets = HashMap::new();
 
-        video
+        for e in video
             .descendants()
             .filter(|e| e.has_tag_name("TargetDisplay"))
-            .for_each(|e| {
-                let id = e
-                    .children()
-                    .find(|e| e.has_tag_name("ID"))
-                    .unwrap()
-                    .text()
-                    .unwrap()
-                    .to_string();
+        {
+            let id = child_text(&e, "ID")?.to_string();
+            let peak_nits = parse_value("PeakBrightness", child_text(&e, "PeakBrightness")?)?;
 
-                let peak_nits = e
-                    .children()
-                    .find(|e| e.has_tag_name("PeakBrightness"))
-                    .unwrap()
-                    .text()
-                    .unwrap()
-                    .parse::<u16>()
-                    .unwrap();
-
-                if self.xml_version >= 0x500 {
-                    let application_type = e
-                    .children()
-                    .find(|e| e.has_tag_name("ApplicationType"))
-                    .unwrap()
-                    .text()
-                    .unwrap()
-                    .to_string();
-
-                    // Only parse HOME targets
-                    if application_type == "HOME" {
-                        let min_nits = e
-                        .children()
-                        .find(|e| e.has_tag_name("MinimumBrightness"))
-                        .unwrap()
-                        .text()
-                        .unwrap()
-                        .parse::<f64>()
-                        .unwrap();
-
-                        let primary_red = e
-                            .descendants()
-                            .find(|e| e.has_tag_name("Red"))
-                            .unwrap()
-                            .text()
-                            .unwrap();
-
-                        let primary_green = e
-                            .descendants()
-                            .find(|e| e.has_tag_name("Green"))
-                            .unwrap()
-                            .text()
-                            .unwrap();
-
-                        let primary_blue = e
-                            .descendants()
-                            .find(|e| e.has_tag_name("Blue"))
-                            .unwrap()
-                            .text()
-                            .unwrap();
-
-                        let primary_white = e
-                            .children()
-                            .find(|e| e.has_tag_name("WhitePoint"))
-                            .unwrap()
-                            .text()
-                            .unwrap();
-                        
-                        let primaries = [primary_red, primary_green, primary_blue,  primary_white]
-                            .join(&self.separator.to_string());
-
-                        targets.insert(id.clone(), TargetDisplay { id: id.clone(), peak_nits, min_nits, primaries });
-
-                        if !PRESET_TARGET_DISPLAYS.contains(&id.parse::<u8>().unwrap()) {
-                            let block = self.parse_global_level10(targets.get(&id).unwrap()).unwrap();
-                            self.config.default_metadata_blocks.push(ExtMetadataBlock::Level10(block));
-                        }
-                    }
-                } else {
-                    targets.insert(id.clone(), TargetDisplay { id, peak_nits, ..Default::default()});
-                }
-            });
+            // Only parse HOME targets
+            if self.xml_version >= 0x500 && child_text(&e, "ApplicationType")? != "HOME" {
+                continue;
+            }
 
-        targets
-    }
+            if !self.is_cmv4() {
+                targets.insert(
+                    id.clone(),
+                    TargetDisplay {
+                        id,
+                        peak_nits,
+                        ..Default::default()
+                    },
+                );
+                continue;
+            }
 
-    fn parse_level254(&self, video: &Node) -> (u8, u8) {
-        if let Some(node) = video.descendants().find(|e| e.has_tag_name("Level254")) {
-            let dm_mode = if let Some(dmm) = node.children().find(|e| e.has_tag_name("DMMode")) {
-                dmm.text().map_or(0, |e| e.parse::<u8>().unwrap())
-            } else {
-                0
+            let min_nits = match e
+                .children()
+                .find(|e| e.has_tag_name("MinimumBrightness"))
+                .and_then(|e| e.text())
+            {
+                Some(min_nits) => parse_value("MinimumBrightness", min_nits)?,
+                None => 0.0,
             };
 
-            let dm_version_index = if let Some(dmv) = node.children().find(|e| e.has_tag_name("DMVersion")) {
-                dmv.text().map_or(2, |e| e.parse::<u8>().unwrap())
-            } else {
-                2
+            let primaries = self.parse_target_display_primaries(&e);
+            let is_custom_target = id
+                .parse::<u8>()
+                .map_or(false, |id| !PRESET_TARGET_DISPLAYS.contains(&id));
+
+            let target = TargetDisplay {
+                id: id.clone(),
+                peak_nits,
+                min_nits,
+                primaries: primaries.unwrap_or_default(),
             };
 
-            (dm_mode, dm_version_index)
+            // Custom targets are signaled with L10 metadata, once per ID
+            if is_custom_target && !target.primaries.is_empty() && !targets.contains_key(&id) {
+                let block = self.parse_global_level10(&target)?;
+                self.config
+                    .default_metadata_blocks
+                    .push(ExtMetadataBlock::Level10(block));
+            }
+
+            targets.insert(id, target);
+        }
+
+        Ok(targets)
+    }
+
+    fn parse_level254(&self, video: &Node) -> Result<(u8, u8)> {
+        if let Some(node) = video.descendants().find(|e| e.has_tag_name("Level254")) {
+            let dm_mode = optional_value(&node, "DMMode")?.unwrap_or(0);
+            let dm_version_index = optional_value(&node, "DMVersion")?.unwrap_or(2);
+
+            Ok((dm_mode, dm_version_index))
         } else {
-            (0, 2)
+            Ok((0, 2))
         }
     }
 
     fn parse_shots(&self, video: &Node) -> Result<Vec<VideoShot>> {
-        let shots = video
+        let mut shots = video
             .descendants()
             .filter(|e| e.has_tag_name("Shot"))
             .map(|n| {
                 let mut shot = VideoShot {
-                    id: n
-                        .children()
-                        .find(|e| e.has_tag_name("UniqueID"))
-                        .unwrap()
-                        .text()
-                        .unwrap()
-                        .to_string(),
+                    id: child_text(&n, "UniqueID")?.to_string(),
                     ..Default::default()
                 };
 
+                let mut has_duration = true;
+
                 if let Some(record) = n.children().find(|e| e.has_tag_name("Record")) {
-                    shot.start = record
-                        .children()
-                        .find(|e| e.has_tag_name("In"))
-                        .unwrap()
-                        .text()
-                        .unwrap()
-                        .parse::<usize>()
-                        .unwrap();
-                    shot.duration = record
-                        .children()
-                        .find(|e| e.has_tag_name("Duration"))
-                        .unwrap()
-                        .text()
-                        .unwrap()
-                        .parse::<usize>()
-                        .unwrap();
+                    shot.start = parse_value("In", child_text(&record, "In")?)?;
+
+                    match optional_child_text(&record, "Duration") {
+                        Some(duration) => shot.duration = parse_value("Duration", duration)?,
+                        None => has_duration = false,
+                    }
                 }
 
                 shot.metadata_blocks = self.parse_shot_trims(&n)?;
@@ -353,14 +351,7 @@ impl CmXmlParser {
                 let frames = n.children().filter(|e| e.has_tag_name("Frame"));
 
                 for frame in frames {
-                    let edit_offset = frame
-                        .children()
-                        .find(|e| e.has_tag_name("EditOffset"))
-                        .unwrap()
-                        .text()
-                        .unwrap()
-                        .parse::<usize>()
-                        .unwrap();
+                    let edit_offset = parse_value("EditOffset", child_text(&frame, "EditOffset")?)?;
 
                     shot.frame_edits.push(ShotFrameEdit {
                         edit_offset,
@@ -368,45 +359,103 @@ impl CmXmlParser {
                     });
                 }
 
-                Ok(shot)
+                Ok((shot, has_duration))
             })
-            .collect();
+            .collect::<Result<Vec<_>>>()?;
+
+        shots.sort_by_key(|(shot, _)| shot.start);
+        self.fill_missing_durations(&mut shots)?;
+
+        let shots: Vec<VideoShot> = shots.into_iter().map(|(shot, _)| shot).collect();
+
+        self.check_duplicate_shot_ids(&shots)?;
+
+        Ok(shots)
+    }
+
+    /// Shots without `Duration` run until the next shot starts.
+    /// The last shot runs until the end of the sequence, from the `length` option.
+    fn fill_missing_durations(&self, shots: &mut [(VideoShot, bool)]) -> Result<()> {
+        let first_start = shots.first().map(|(shot, _)| shot.start).unwrap_or(0);
+
+        for i in 0..shots.len() {
+            if shots[i].1 {
+                continue;
+            }
+
+            let end = match shots.get(i + 1) {
+                Some((next_shot, _)) => Some(next_shot.start),
+                None => self.opts.length.map(|length| first_start + length),
+            };
+
+            let shot = &mut shots[i].0;
+
+            match end {
+                Some(end) if end > shot.start => shot.duration = end - shot.start,
+                _ => {
+                    return Err(XmlParseError::UnknownShotDuration {
+                        id: shot.id.clone(),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        Ok(())
+    }
 
-        shots
+    /// Duplicate UniqueIDs usually come from an export bug
+    fn check_duplicate_shot_ids(&self, shots: &[VideoShot]) -> Result<()> {
+        let mut ids = HashSet::new();
+
+        for shot in shots {
+            if !ids.insert(shot.id.as_str()) {
+                let err = XmlParseError::DuplicateShotId {
+                    id: shot.id.clone(),
+                };
+
+                if self.opts.strict {
+                    return Err(err.into());
+                }
+
+                self.opts.warnings.warn(&err.to_string());
+            }
+        }
+
+        Ok(())
     }
 
     fn parse_shot_trims(&self, node: &Node) -> Result<Vec<ExtMetadataBlock>> {
         let mut metadata_blocks = Vec::new();
 
-        let dynamic_meta_tag = if self.is_cmv4() {
-            "DVDynamicData"
-        } else {
-            "PluginNode"
-        };
-
-        if let Some(defaults_node) = node
-            .descendants()
-            .find(|e| e.has_tag_name(dynamic_meta_tag))
-        {
-            if self.is_cmv4() {
+        if self.is_cmv4() {
+            if let Some(defaults_node) =
+                node.descendants().find(|e| e.has_tag_name("DVDynamicData"))
+            {
                 let level_nodes = defaults_node
                     .children()
-                    .filter(|e| e.has_attribute("level"));
+                    .filter_map(|e| e.attribute("level").map(|level| (e, level)));
 
-                for level_node in level_nodes {
-                    let level = level_node.attribute("level").unwrap();
+                for (level_node, level) in level_nodes {
                     self.parse_trim_levels(&level_node, level, &mut metadata_blocks)?;
                 }
-            } else {
-                let edr_nodes = defaults_node
-                    .children()
-                    .filter(|e| e.has_tag_name("DolbyEDR") && e.has_attribute("level"));
+            }
+        } else {
+            // Legacy 2.0.x exports can have the DolbyEDR nodes directly in the shot or frame.
+            // Only the children are searched, the frames of a shot have their own nodes.
+            let edr_parent = node
+                .children()
+                .find(|e| e.has_tag_name("PluginNode"))
+                .unwrap_or(*node);
 
-                for edr in edr_nodes {
-                    let level = edr.attribute("level").unwrap();
-                    self.parse_trim_levels(&edr, level, &mut metadata_blocks)?;
-                }
-            };
+            let edr_nodes = edr_parent
+                .children()
+                .filter(|e| e.has_tag_name("DolbyEDR"))
+                .filter_map(|e| e.attribute("level").map(|level| (e, level)));
+
+            for (edr, level) in edr_nodes {
+                self.parse_trim_levels(&edr, level, &mut metadata_blocks)?;
+            }
         }
 
         Ok(metadata_blocks)
@@ -430,11 +479,18 @@ impl CmXmlParser {
             metadata_blocks.push(ExtMetadataBlock::Level8(self.parse_level8_trim(node)?));
         } else if level == "9" {
             metadata_blocks.push(ExtMetadataBlock::Level9(self.parse_level9_trim(node)?));
+        } else if level == "11" {
+            metadata_blocks.push(ExtMetadataBlock::Level11(self.parse_level11_trim(node)?));
         }
 
         Ok(())
     }
 
+    /// Global L5 metadata, in order of precedence:
+    /// - From the `CanvasAspectRatio` and `ImageAspectRatio` of the output.
+    /// - From the L5 metadata of the first shot, when the aspect ratios are absent.
+    ///   Requires the canvas dimensions, and the shots to be parsed.
+    /// - Zero offsets otherwise.
     pub fn parse_global_level5(&mut self, output: &Node) -> Result<()> {
         let canvas_ar = if let Some(canvas_ar) = output
             .children()
@@ -459,18 +515,59 @@ impl CmXmlParser {
                 .calculate_level5_metadata(c_ar, i_ar)
                 .ok()
                 .unwrap_or_default();
+        } else if self.opts.canvas_width.is_some() && self.opts.canvas_height.is_some() {
+            let first_shot_level5 = self.config.shots.first().and_then(|shot| {
+                shot.metadata_blocks.iter().find_map(|block| match block {
+                    ExtMetadataBlock::Level5(level5) => Some(level5.clone()),
+                    _ => None,
+                })
+            });
+
+            if let Some(level5) = first_shot_level5 {
+                self.config.level5 = level5;
+            }
         }
 
         Ok(())
     }
 
+    /// Returns the red, green, blue and white point chromaticities, separated by the XML separator
+    fn parse_target_display_primaries(&self, target: &Node) -> Option<String> {
+        let primaries = target.children().find(|e| e.has_tag_name("Primaries"))?;
+
+        let mut values: Vec<&str> = ["Red", "Green", "Blue"]
+            .iter()
+            .map(|color| {
+                primaries
+                    .children()
+                    .find(|e| e.has_tag_name(*color))
+                    .and_then(|e| e.text())
+            })
+            .collect::<Option<Vec<&str>>>()?;
+
+        values.push(
+            target
+                .children()
+                .find(|e| e.has_tag_name("WhitePoint"))
+                .and_then(|e| e.text())?,
+        );
+
+        Some(values.join(&self.separator.to_string()))
+    }
+
     fn parse_global_level10(&self, target: &TargetDisplay) -> Result<ExtMetadataBlockLevel10> {
-        let primaries: Vec<&str> = target.primaries.split(self.separator).collect(); 
+        let primaries: Vec<&str> = target.primaries.split(self.separator).collect();
+        ensure_value_count(10, "Primaries", primaries.len(), 8)?;
+
+        let primaries = parse_values::<f64>("Primaries", &primaries)?;
         let index = self.parse_primary_index(&primaries, false)?;
-        
+
         let mut block = ExtMetadataBlockLevel10 {
-            target_display_index: target.id.parse::<u8>().unwrap(),
-            target_max_pq: min(4095, (nits_to_pq(target.peak_nits.into()) * 4095.0).round() as u16),
+            target_display_index: parse_value("ID", &target.id)?,
+            target_max_pq: min(
+                4095,
+                (nits_to_pq(target.peak_nits.into()) * 4095.0).round() as u16,
+            ),
             target_min_pq: min(4095, (nits_to_pq(target.min_nits) * 4095.0).round() as u16),
             target_primary_index: index,
             ..Default::default()
@@ -478,18 +575,19 @@ impl CmXmlParser {
 
         if index == 255 {
             let p: Vec<u16> = primaries
-            .iter()
-            .map(|v||i| -> u16 {
-                    match i {
-                        // This value will not be 32768
-                        32767.. => min(32767, i - 32767),
-                        _ => i + 32769,
-                    }
-                } ((v.parse::<f64>().unwrap() * 32767.0 + 32767.0).round() as u16)
-            )
-            .collect();
-        
-            block.target_primary_red_x =p[0];
+                .iter()
+                .map(|v| {
+                    |i| -> u16 {
+                        match i {
+                            // This value will not be 32768
+                            32767.. => min(32767, i - 32767),
+                            _ => i + 32769,
+                        }
+                    }((v * 32767.0 + 32767.0).round() as u16)
+                })
+                .collect();
+
+            block.target_primary_red_x = p[0];
             block.target_primary_red_y = p[1];
             block.target_primary_green_x = p[2];
             block.target_primary_green_y = p[3];
@@ -499,57 +597,35 @@ impl CmXmlParser {
             block.target_primary_white_y = p[7];
         }
 
+        block.validate()?;
+
         Ok(block)
     }
 
     pub fn parse_level1_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel1> {
-        let measurements = node
-            .children()
-            .find(|e| e.has_tag_name("ImageCharacter"))
-            .unwrap()
-            .text()
-            .unwrap();
-        let measurements: Vec<&str> = measurements.split(self.separator).collect();
+        let raw_measurements = child_text(node, "ImageCharacter")?;
+        let measurements = self.split_values(1, None, raw_measurements, 3)?;
 
-        ensure!(
-            measurements.len() == 3,
-            "invalid L1 trim: should be 3 values"
-        );
+        let measurements = parse_values::<f32>("ImageCharacter", &measurements)?;
 
-        let min_pq = (measurements[0].parse::<f32>().unwrap() * 4095.0).round() as u16;
-        let avg_pq = (measurements[1].parse::<f32>().unwrap() * 4095.0).round() as u16;
-        let max_pq = (measurements[2].parse::<f32>().unwrap() * 4095.0).round() as u16;
+        let min_pq = (measurements[0] * 4095.0).round() as u16;
+        let avg_pq = (measurements[1] * 4095.0).round() as u16;
+        let max_pq = (measurements[2] * 4095.0).round() as u16;
 
         Ok(ExtMetadataBlockLevel1::from_stats(min_pq, max_pq, avg_pq))
     }
 
     pub fn parse_level2_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel2> {
-        let target_id = node
-            .children()
-            .find(|e| e.has_tag_name("TID"))
-            .unwrap()
-            .text()
-            .unwrap()
-            .to_string();
-
-        let trim = node
-            .children()
-            .find(|e| e.has_tag_name("Trim"))
-            .unwrap()
-            .text()
-            .unwrap();
-        let trim: Vec<&str> = trim.split(self.separator).collect();
+        let target_id = child_text(node, "TID")?.to_string();
+        let raw_trim = child_text(node, "Trim")?;
 
-        let target_display = self
-            .target_displays
-            .get(&target_id)
-            .expect("No target display found for L2 trim");
+        let target_display = self.target_display(&target_id)?;
 
-        ensure!(trim.len() == 9, "invalid L2 trim: should be 9 values");
+        let trim = self.split_values(2, Some(&target_id), raw_trim, 9)?;
+        let trim = parse_values::<f32>("Trim", &trim)?;
 
-        let trim_lift = trim[3].parse::<f32>().unwrap();
-        let trim_gain = trim[4].parse::<f32>().unwrap();
-        let trim_gamma = trim[5].parse::<f32>().unwrap().clamp(-1.0, 1.0);
+        let trim_lift = trim[3];
+        let trim_gain = trim[4];
 
         let trim_slope = min(
             4095,
@@ -560,22 +636,10 @@ impl CmXmlParser {
             4095,
             ((((trim_gain + 2.0) * (trim_lift / 2.0)) * 2048.0) + 2048.0).round() as u16,
         );
-        let trim_power = min(
-            4095,
-            (((2.0 / (1.0 + trim_gamma / 2.0) - 2.0) * 2048.0) + 2048.0).round() as u16,
-        );
-        let trim_chroma_weight = min(
-            4095,
-            ((trim[6].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
-        let trim_saturation_gain = min(
-            4095,
-            ((trim[7].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
-        let ms_weight = min(
-            4095,
-            ((trim[8].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as i16,
-        );
+        let trim_power = gamma_to_trim_power(trim[5]);
+        let trim_chroma_weight = min(4095, ((trim[6] * 2048.0) + 2048.0).round() as u16);
+        let trim_saturation_gain = min(4095, ((trim[7] * 2048.0) + 2048.0).round() as u16);
+        let ms_weight = weight_to_ms_weight(trim[8]);
 
         Ok(ExtMetadataBlockLevel2 {
             trim_slope,
@@ -589,42 +653,24 @@ impl CmXmlParser {
     }
 
     pub fn parse_level3_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel3> {
-        let measurements = node
-            .children()
-            .find(|e| e.has_tag_name("L1Offset"))
-            .unwrap()
-            .text()
-            .unwrap();
-        let measurements: Vec<&str> = measurements.split(self.separator).collect();
+        let raw_offsets = child_text(node, "L1Offset")?;
+        let offsets = self.split_values(3, None, raw_offsets, 3)?;
 
-        ensure!(
-            measurements.len() == 3,
-            "invalid L3 trim: should be 3 values"
-        );
+        let offsets = parse_values::<f32>("L1Offset", &offsets)?;
 
         Ok(ExtMetadataBlockLevel3 {
-            min_pq_offset: ((measurements[0].parse::<f32>().unwrap() * 2048.0) + 2048.0).round()
-                as u16,
-            max_pq_offset: ((measurements[1].parse::<f32>().unwrap() * 2048.0) + 2048.0).round()
-                as u16,
-            avg_pq_offset: ((measurements[2].parse::<f32>().unwrap() * 2048.0) + 2048.0).round()
-                as u16,
+            min_pq_offset: ((offsets[0] * 2048.0) + 2048.0).round() as u16,
+            max_pq_offset: ((offsets[1] * 2048.0) + 2048.0).round() as u16,
+            avg_pq_offset: ((offsets[2] * 2048.0) + 2048.0).round() as u16,
         })
     }
 
     pub fn parse_level5_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel5> {
-        let ratios = node
-            .children()
-            .find(|e| e.has_tag_name("AspectRatios"))
-            .unwrap()
-            .text()
-            .unwrap();
-        let ratios: Vec<&str> = ratios.split(self.separator).collect();
+        let raw_ratios = child_text(node, "AspectRatios")?;
+        let ratios = self.split_values(5, None, raw_ratios, 2)?;
 
-        ensure!(ratios.len() == 2, "invalid L5 trim: should be 2 values");
-
-        let canvas_ar = ratios[0].parse::<f32>().unwrap();
-        let image_ar = ratios[1].parse::<f32>().unwrap();
+        let canvas_ar = parse_value("AspectRatios", ratios[0])?;
+        let image_ar = parse_value("AspectRatios", ratios[1])?;
 
         Ok(self
             .calculate_level5_metadata(canvas_ar, image_ar)
@@ -633,66 +679,27 @@ impl CmXmlParser {
     }
 
     pub fn parse_level8_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel8> {
-        let target_id = node
-            .children()
-            .find(|e| e.has_tag_name("TID"))
-            .unwrap()
-            .text()
-            .unwrap()
-            .to_string();
+        let target_id = child_text(node, "TID")?.to_string();
+        let raw_trim = child_text(node, "L8Trim")?;
 
-        let trim = node
-            .children()
-            .find(|e| e.has_tag_name("L8Trim"))
-            .unwrap()
-            .text()
-            .unwrap();
-        let trim: Vec<&str> = trim.split(self.separator).collect();
+        let target_display = self.target_display(&target_id)?;
 
-        let target_display = self
-            .target_displays
-            .get(&target_id)
-            .expect("No target display found for L8 trim");
+        let trim = self.split_values(8, Some(&target_id), raw_trim, 6)?;
+        let trim = parse_values::<f32>("L8Trim", &trim)?;
 
-        ensure!(trim.len() == 6, "Invalid L8 trim: should be 6 values");
+        // Absent in some XML versions, the defaults keep the shorter L8 encoding
+        let bias: Option<f32> = optional_child_text(node, "MidContrastBias")
+            .map(|v| parse_value("MidContrastBias", v))
+            .transpose()?;
+        let clipping: Option<f32> = optional_child_text(node, "HighlightClipping")
+            .map(|v| parse_value("HighlightClipping", v))
+            .transpose()?;
 
-        let bias = node
-            .children()
-            .find(|e| e.has_tag_name("MidContrastBias"))
-            .unwrap()
-            .text()
-            .unwrap();
+        let satvec = self.vector_field_values(node, "SaturationVectorField")?;
+        let huevec = self.vector_field_values(node, "HueVectorField")?;
 
-        let clipping = node
-            .children()
-            .find(|e| e.has_tag_name("HighlightClipping"))
-            .unwrap()
-            .text()
-            .unwrap();
-
-        let satvec = node
-            .children()
-            .find(|e| e.has_tag_name("SaturationVectorField"))
-            .unwrap()
-            .text()
-            .unwrap();
-            
-        let satvec: Vec<&str> = satvec.split(self.separator).collect();
-        ensure!(satvec.len() == 6, "Invalid L8 SatVectorField: should be 6 values");
-
-        let huevec = node
-            .children()
-            .find(|e| e.has_tag_name("HueVectorField"))
-            .unwrap()
-            .text()
-            .unwrap();
-
-        let huevec: Vec<&str> = huevec.split(self.separator).collect();
-        ensure!(huevec.len() == 6, "Invalid L8 HueVectorField: should be 6 values");
-
-        let trim_lift = trim[0].parse::<f32>().unwrap();
-        let trim_gain = trim[1].parse::<f32>().unwrap();
-        let trim_gamma = trim[2].parse::<f32>().unwrap().clamp(-1.0, 1.0);
+        let trim_lift = trim[0];
+        let trim_gain = trim[1];
 
         let trim_slope = min(
             4095,
@@ -703,83 +710,47 @@ impl CmXmlParser {
             4095,
             ((((trim_gain + 2.0) * (trim_lift / 2.0)) * 2048.0) + 2048.0).round() as u16,
         );
-        let trim_power = min(
-            4095,
-            (((2.0 / (1.0 + trim_gamma / 2.0) - 2.0) * 2048.0) + 2048.0).round() as u16,
-        );
-        let trim_chroma_weight = min(
-            4095,
-            ((trim[3].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
-        let trim_saturation_gain = min(
-            4095,
-            ((trim[4].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
-        let ms_weight = min(
-            4095,
-            ((trim[5].parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
+        let trim_power = gamma_to_trim_power(trim[2]);
+        let trim_chroma_weight = min(4095, ((trim[3] * 2048.0) + 2048.0).round() as u16);
+        let trim_saturation_gain = min(4095, ((trim[4] * 2048.0) + 2048.0).round() as u16);
+        let ms_weight = weight_to_ms_weight(trim[5]).max(0) as u16;
 
-        let target_mid_contrast = min(
-            4095,
-            ((bias.parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
+        let default = ExtMetadataBlockLevel8::default();
 
-        let clip_trim = min(
-            4095,
-            ((clipping.parse::<f32>().unwrap() * 2048.0) + 2048.0).round() as u16,
-        );
+        let target_mid_contrast = bias.map_or(default.target_mid_contrast, |bias| {
+            min(4095, ((bias * 2048.0) + 2048.0).round() as u16)
+        });
 
-        let saturation_vector_field0 = min(
-            255, ((satvec[0].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let clip_trim = clipping.map_or(default.clip_trim, |clipping| {
+            min(4095, ((clipping * 2048.0) + 2048.0).round() as u16)
+        });
 
-        let saturation_vector_field1 = min(
-            255, ((satvec[1].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field0 = min(255, ((satvec[0] * 128.0) + 128.0).round() as u8);
 
-        let saturation_vector_field2 = min(
-            255, ((satvec[2].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field1 = min(255, ((satvec[1] * 128.0) + 128.0).round() as u8);
 
-        let saturation_vector_field3 = min(
-            255, ((satvec[3].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field2 = min(255, ((satvec[2] * 128.0) + 128.0).round() as u8);
 
-        let saturation_vector_field4 = min(
-            255, ((satvec[4].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field3 = min(255, ((satvec[3] * 128.0) + 128.0).round() as u8);
 
-        let saturation_vector_field5 = min(
-            255, ((satvec[5].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field4 = min(255, ((satvec[4] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field0 = min(
-            255, ((huevec[0].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let saturation_vector_field5 = min(255, ((satvec[5] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field1 = min(
-            255, ((huevec[1].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let hue_vector_field0 = min(255, ((huevec[0] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field2 = min(
-            255, ((huevec[2].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let hue_vector_field1 = min(255, ((huevec[1] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field3 = min(
-            255, ((huevec[3].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let hue_vector_field2 = min(255, ((huevec[2] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field4 = min(
-            255, ((huevec[4].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let hue_vector_field3 = min(255, ((huevec[3] * 128.0) + 128.0).round() as u8);
 
-        let hue_vector_field5 = min(
-            255, ((huevec[5].parse::<f32>().unwrap() * 128.0) + 128.0).round() as u8,
-        );
+        let hue_vector_field4 = min(255, ((huevec[4] * 128.0) + 128.0).round() as u8);
+
+        let hue_vector_field5 = min(255, ((huevec[5] * 128.0) + 128.0).round() as u8);
 
-        Ok (ExtMetadataBlockLevel8 {
-            target_display_index: target_display.id.parse::<u8>()?,
+        Ok(ExtMetadataBlockLevel8 {
+            target_display_index: parse_value("TID", &target_display.id)?,
             trim_slope,
             trim_offset,
             trim_power,
@@ -803,16 +774,16 @@ impl CmXmlParser {
         })
     }
 
-    fn parse_primary_index(&self, primaries: &Vec<&str>, is_source: bool) -> Result<u8> {
-        fn compare_primaries(a: &Vec<&str>, b: &[f64; 8], compare_flag: &mut u8) -> bool {
+    fn parse_primary_index(&self, primaries: &[f64], is_source: bool) -> Result<u8> {
+        fn compare_primaries(a: &[f64], b: &[f64; 8], compare_flag: &mut u8) -> bool {
             *compare_flag = 0;
             for i in 0..8 {
-                if (a[i].parse::<f64>().unwrap() - b[i]).abs() < f64::EPSILON {
+                if (a[i] - b[i]).abs() < f64::EPSILON {
                     *compare_flag |= 1 << i;
                 } else {
                     break;
                 }
-            };
+            }
             if *compare_flag == 0b11111111 {
                 return true;
             } else {
@@ -828,7 +799,7 @@ impl CmXmlParser {
             } else {
                 result += 1;
             };
-        };
+        }
         if compare_flag != 0b11111111 && is_source {
             for p in PREDEFINED_REALDEVICE_PRIMARIES {
                 if compare_primaries(primaries, p, &mut compare_flag) {
@@ -836,7 +807,7 @@ impl CmXmlParser {
                 } else {
                     result += 1;
                 };
-            };
+            }
         };
         if compare_flag != 0b11111111 {
             result = 255;
@@ -845,44 +816,74 @@ impl CmXmlParser {
     }
 
     pub fn parse_level9_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel9> {
-        let source_color_primary = node
-            .children()
-            .find(|e| e.has_tag_name("SourceColorPrimary"))
-            .unwrap()
-            .text()
-            .unwrap();
-            
-            let primaries: Vec<&str> = source_color_primary.split(self.separator).collect();
-            ensure!(primaries.len() == 8, "Invalid L9 SourceColorPrimary: should be 8 values");
-            let index = self.parse_primary_index(&primaries, true)?;
-
-            let mut block = ExtMetadataBlockLevel9 {
-                source_primary_index: index,
-              ..Default::default()
-            };
+        let source_color_primary = child_text(node, "SourceColorPrimary")?;
 
-            if index == 255 {
-                let p: Vec<u16> = primaries
-                    .iter()
-                    .map(|v||i| -> u16 {
-                            match i {
-                                // This value will not be 32768
-                                32767.. => min(32767, i - 32767),
-                                _ => i + 32769,
-                            }
-                        } ((v.parse::<f64>().unwrap() * 32767.0 + 32767.0).round() as u16)
-                    )
-                    .collect();
-                
-                block.source_primary_red_x =p[0];
-                block.source_primary_red_y = p[1];
-                block.source_primary_green_x = p[2];
-                block.source_primary_green_y = p[3];
-                block.source_primary_blue_x = p[4];
-                block.source_primary_blue_y = p[5];
-                block.source_primary_white_x = p[6];
-                block.source_primary_white_y = p[7];
-            }
+        let primaries: Vec<&str> = source_color_primary.split(self.separator).collect();
+        ensure_value_count(9, "SourceColorPrimary", primaries.len(), 8)?;
+
+        let primaries = parse_values::<f64>("SourceColorPrimary", &primaries)?;
+        let index = self.parse_primary_index(&primaries, true)?;
+
+        let mut block = ExtMetadataBlockLevel9 {
+            source_primary_index: index,
+            ..Default::default()
+        };
+
+        if index == 255 {
+            let p: Vec<u16> = primaries
+                .iter()
+                .map(|v| {
+                    |i| -> u16 {
+                        match i {
+                            // This value will not be 32768
+                            32767.. => min(32767, i - 32767),
+                            _ => i + 32769,
+                        }
+                    }((v * 32767.0 + 32767.0).round() as u16)
+                })
+                .collect();
+
+            block.source_primary_red_x = p[0];
+            block.source_primary_red_y = p[1];
+            block.source_primary_green_x = p[2];
+            block.source_primary_green_y = p[3];
+            block.source_primary_blue_x = p[4];
+            block.source_primary_blue_y = p[5];
+            block.source_primary_white_x = p[6];
+            block.source_primary_white_y = p[7];
+        }
+
+        Ok(block)
+    }
+
+    /// Absent fields default to cinema content in reference mode, with a D65 whitepoint.
+    /// The reference mode defaults to off for the content types it doesn't apply to.
+    pub fn parse_level11_trim(&self, node: &Node) -> Result<ExtMetadataBlockLevel11> {
+        let default = ExtMetadataBlockLevel11::default_reference_cinema();
+
+        let content_type: Option<u8> = optional_value(node, "ContentType")?;
+        let whitepoint: Option<u8> = optional_value(node, "IntendedWhitePoint")?;
+        let reference_mode: Option<u8> = optional_value(node, "ReferenceMode")?;
+
+        let block = ExtMetadataBlockLevel11 {
+            content_type: content_type.unwrap_or(default.content_type),
+            whitepoint: whitepoint.unwrap_or(default.whitepoint),
+            reference_mode_flag: reference_mode.map_or_else(
+                || content_type.map_or(true, |t| level11::DEFINED_CONTENT_TYPES.contains(&t)),
+                |flag| flag != 0,
+            ),
+            ..Default::default()
+        };
+
+        if block.content_type > 15 {
+            return Err(invalid_value("ContentType", block.content_type));
+        }
+
+        if block.whitepoint > 15 {
+            return Err(invalid_value("IntendedWhitePoint", block.whitepoint));
+        }
+
+        block.validate()?;
 
         Ok(block)
     }
@@ -932,4 +933,122 @@ impl CmXmlParser {
     pub fn is_cmv4(&self) -> bool {
         self.xml_version >= 0x402
     }
+
+    fn target_display(&self, id: &str) -> Result<&TargetDisplay> {
+        match self.target_displays.get(id) {
+            Some(target_display) => Ok(target_display),
+            None => Err(XmlParseError::UndefinedTarget { id: id.to_string() }.into()),
+        }
+    }
+
+    /// Splits the trim values, ensuring the expected count
+    fn split_values<'a>(
+        &self,
+        level: u8,
+        target_id: Option<&str>,
+        raw: &'a str,
+        expected: usize,
+    ) -> Result<Vec<&'a str>> {
+        let values: Vec<&str> = raw.split(self.separator).collect();
+
+        if values.len() != expected {
+            return Err(XmlParseError::TrimValueCount {
+                level,
+                target_id: target_id.map(str::to_string),
+                expected,
+                found: values.len(),
+                raw: raw.to_string(),
+            }
+            .into());
+        }
+
+        Ok(values)
+    }
+
+    /// Parses the 6 values of an L8 vector field
+    fn vector_field_values(&self, node: &Node, field: &str) -> Result<Vec<f32>> {
+        let values: Vec<&str> = child_text(node, field)?.split(self.separator).collect();
+        ensure_value_count(8, field, values.len(), 6)?;
+
+        parse_values(field, &values)
+    }
+}
+
+fn missing_node(name: &str) -> anyhow::Error {
+    XmlParseError::MissingNode {
+        name: name.to_string(),
+    }
+    .into()
+}
+
+fn invalid_value<T: ToString>(field: &str, value: T) -> anyhow::Error {
+    XmlParseError::InvalidValue {
+        field: field.to_string(),
+        value: value.to_string(),
+    }
+    .into()
+}
+
+fn invalid_version(version: &str) -> anyhow::Error {
+    XmlParseError::InvalidVersion {
+        found: version.to_string(),
+    }
+    .into()
+}
+
+/// Version number, one hex digit per component: 5.1.0 is 0x510
+fn parse_version(version: &str) -> Result<u16> {
+    let mut rev: u16 = 0;
+
+    for (i, n) in version.split('.').rev().enumerate() {
+        match n.parse::<u16>() {
+            Ok(n) if i < 4 => rev += n << (i * 4),
+            _ => return Err(invalid_version(version)),
+        }
+    }
+
+    Ok(rev)
+}
+
+/// Text of the first child node with the tag name
+fn child_text<'a>(node: &Node<'a, '_>, name: &str) -> Result<&'a str> {
+    optional_child_text(node, name).ok_or_else(|| missing_node(name))
+}
+
+fn optional_child_text<'a>(node: &Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.children()
+        .find(|e| e.has_tag_name(name))
+        .and_then(|e| e.text())
+}
+
+/// Value of the first child node with the tag name, if present
+fn optional_value<T: FromStr>(node: &Node, name: &str) -> Result<Option<T>> {
+    optional_child_text(node, name)
+        .map(|v| parse_value(name, v))
+        .transpose()
+}
+
+fn parse_value<T: FromStr>(field: &str, value: &str) -> Result<T> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| invalid_value(field, value))
+}
+
+fn parse_values<T: FromStr>(field: &str, values: &[&str]) -> Result<Vec<T>> {
+    values.iter().map(|v| parse_value(field, v)).collect()
+}
+
+fn ensure_value_count(level: u8, field: &str, found: usize, expected: usize) -> Result<()> {
+    if found != expected {
+        return Err(XmlParseError::FieldValueCount {
+            level,
+            field: field.to_string(),
+            expected,
+            found,
+        }
+        .into());
+    }
+
+    Ok(())
 }
@@ -1,8 +1,11 @@
 use std::path::PathBuf;
 
-use crate::rpu::{extension_metadata::blocks::ExtMetadataBlock, vdr_dm_data::CmVersion};
+use crate::rpu::{
+    dovi_rpu::DoviRpu, extension_metadata::blocks::ExtMetadataBlock, generate::GenerateConfig,
+    vdr_dm_data::CmVersion,
+};
 
-use super::{CmXmlParser, XmlParserOpts};
+use super::{CmXmlParser, CmXmlWriter, XmlParseError, XmlParserOpts, XmlWriterOpts};
 use anyhow::Result;
 
 fn assert_num_blocks_for_level(blocks: &[ExtMetadataBlock], level: u8, count: usize) {
@@ -11,6 +14,38 @@ fn assert_num_blocks_for_level(blocks: &[ExtMetadataBlock], level: u8, count: us
     assert_eq!(filtered, count);
 }
 
+/// Config of the XML, parsed with a 3840x2160 canvas
+fn parse_config(xml: String) -> Result<GenerateConfig> {
+    let opts = XmlParserOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        ..Default::default()
+    };
+
+    Ok(CmXmlParser::new(xml, opts)?.config)
+}
+
+fn canvas_writer_opts() -> XmlWriterOpts {
+    XmlWriterOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        ..Default::default()
+    }
+}
+
+fn assert_same_rpus(rpus: &[DoviRpu], other_rpus: &[DoviRpu]) -> Result<()> {
+    assert_eq!(rpus.len(), other_rpus.len());
+
+    for (rpu, other_rpu) in rpus.iter().zip(other_rpus.iter()) {
+        assert_eq!(
+            rpu.write_hevc_unspec62_nalu()?,
+            other_rpu.write_hevc_unspec62_nalu()?
+        );
+    }
+
+    Ok(())
+}
+
 #[test]
 fn parse_cmv2_9() -> Result<()> {
     let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -19,6 +54,9 @@ fn parse_cmv2_9() -> Result<()> {
     let opts = XmlParserOpts {
         canvas_width: Some(3840),
         canvas_height: Some(2160),
+        strict: false,
+        length: None,
+        ..Default::default()
     };
     let parser = CmXmlParser::parse_file(&assets_path.join("assets/tests/cmv2_9.xml"), opts)?;
 
@@ -79,6 +117,14 @@ fn parse_cmv4_0_2() -> Result<()> {
     assert_eq!(config.level6.max_content_light_level, 3948);
     assert_eq!(config.level6.max_frame_average_light_level, 120);
 
+    // L254
+    let level254 = config.level254.as_ref().unwrap();
+    assert_eq!(level254.dm_mode, 0);
+    assert_eq!(level254.dm_version_index, 2);
+
+    // Only preset target displays
+    assert_num_blocks_for_level(&config.default_metadata_blocks, 10, 0);
+
     let shot1 = &config.shots[0];
     let shot1_blocks = &shot1.metadata_blocks;
     assert_eq!(shot1.duration, 120);
@@ -116,6 +162,9 @@ fn parse_cmv4_0_2_with_l5() -> Result<()> {
     let opts = XmlParserOpts {
         canvas_width: Some(3840),
         canvas_height: Some(2160),
+        strict: false,
+        length: None,
+        ..Default::default()
     };
 
     let parser = CmXmlParser::parse_file(&assets_path.join("assets/tests/cmv4_0_2.xml"), opts)?;
@@ -129,3 +178,714 @@ fn parse_cmv4_0_2_with_l5() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn capture_new_xml_version_warning() -> Result<()> {
+    use crate::utils::WarningSink;
+
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<DolbyLabsMDF>
+  <Version>5.1.1</Version>
+  <Outputs>
+    <Output>
+      <Video></Video>
+    </Output>
+  </Outputs>
+</DolbyLabsMDF>"#;
+
+    let (sink, warnings) = WarningSink::capture();
+
+    let parser = CmXmlParser::new(
+        xml.to_string(),
+        XmlParserOpts::default().with_warnings(sink),
+    )?;
+    assert_eq!(parser.config.length, 0);
+
+    let warnings = warnings.lock().unwrap();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].starts_with("Possibly unhandled new XML version 5.1.1 found!"));
+
+    Ok(())
+}
+
+#[test]
+fn invalid_level2_trim_count() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv2_9.xml"))?;
+    let xml = xml.replacen(
+        "<Trim>0,0,0,-0.0159445,-0.0325405,0.419015,0,0,0</Trim>",
+        "<Trim>0,0,0,-0.0159445,-0.0325405,0.419015,0</Trim>",
+        1,
+    );
+
+    let opts = XmlParserOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        strict: false,
+        length: None,
+        ..Default::default()
+    };
+    let err = CmXmlParser::new(xml, opts).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Invalid L2 trim for target display 1: should be 9 values, found 7 in \
+        '0,0,0,-0.0159445,-0.0325405,0.419015,0'"
+    );
+    assert_eq!(
+        err.downcast_ref::<XmlParseError>(),
+        Some(&XmlParseError::TrimValueCount {
+            level: 2,
+            target_id: Some("1".to_string()),
+            expected: 9,
+            found: 7,
+            raw: "0,0,0,-0.0159445,-0.0325405,0.419015,0".to_string(),
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn typed_parse_errors() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv2_9.xml"))?;
+
+    let parse_err = |xml: String| -> XmlParseError {
+        let opts = XmlParserOpts {
+            canvas_width: Some(3840),
+            canvas_height: Some(2160),
+            strict: false,
+            length: None,
+            ..Default::default()
+        };
+
+        CmXmlParser::new(xml, opts)
+            .unwrap_err()
+            .downcast::<XmlParseError>()
+            .unwrap()
+    };
+
+    assert!(matches!(
+        parse_err("<DolbyLabsMDF></Outputs>".to_string()),
+        XmlParseError::InvalidDocument { .. }
+    ));
+
+    assert_eq!(
+        parse_err(xml.replacen("version=\"2.0.5\"", "version=\"3.0.0\"", 1)),
+        XmlParseError::InvalidVersion {
+            found: "3.0.0".to_string()
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen("version=\"2.0.5\"", "", 1)),
+        XmlParseError::MissingVersion
+    );
+
+    assert_eq!(
+        parse_err(
+            xml.replacen("<Output name=\"Timeline 1\">", "<Ignored>", 1)
+                .replacen("</Output>", "</Ignored>", 1)
+        ),
+        XmlParseError::MissingNode {
+            name: "Output".to_string()
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen("<TID>27</TID>", "<TID>99</TID>", 1)),
+        XmlParseError::UndefinedTarget {
+            id: "99".to_string()
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen(
+            "<ImageCharacter>0,0.3,0.508078</ImageCharacter>",
+            "<ImageCharacter>0,0.3</ImageCharacter>",
+            1
+        )),
+        XmlParseError::TrimValueCount {
+            level: 1,
+            target_id: None,
+            expected: 3,
+            found: 2,
+            raw: "0,0.3".to_string(),
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen(
+            "<ImageCharacter>0,0.3,0.508078</ImageCharacter>",
+            "<ImageCharacter>0,0.3,bright</ImageCharacter>",
+            1
+        )),
+        XmlParseError::InvalidValue {
+            field: "ImageCharacter".to_string(),
+            value: "bright".to_string(),
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn custom_target_display_level10() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let custom_target = r#"<TargetDisplay>
+                <ID>100</ID>
+                <Name>700-nit, Custom, D65, ST.2084, Full</Name>
+                <Primaries>
+                  <Red>0.66 0.33</Red>
+                  <Green>0.28 0.65</Green>
+                  <Blue>0.15 0.07</Blue>
+                </Primaries>
+                <WhitePoint>0.3127 0.329</WhitePoint>
+                <PeakBrightness>700</PeakBrightness>
+                <MinimumBrightness>0.005</MinimumBrightness>
+                <EOTF>pq</EOTF>
+                <DiagonalSize>42</DiagonalSize>
+              </TargetDisplay>
+            </DVGlobalData>"#;
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let xml = xml.replacen("</DVGlobalData>", custom_target, 1);
+
+    let parser = CmXmlParser::new(xml, XmlParserOpts::default())?;
+    let config = parser.config;
+
+    let level10_blocks: Vec<_> = config
+        .default_metadata_blocks
+        .iter()
+        .filter_map(|b| match b {
+            ExtMetadataBlock::Level10(b) => Some(b),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(level10_blocks.len(), 1);
+
+    let level10 = level10_blocks[0];
+    assert_eq!(level10.target_display_index, 100);
+    assert_eq!(level10.target_max_pq, 2920);
+    assert_eq!(level10.target_min_pq, 62);
+    assert_eq!(level10.target_primary_index, 255);
+
+    assert_eq!(level10.target_primary_red_x, 21626);
+    assert_eq!(level10.target_primary_red_y, 10813);
+    assert_eq!(level10.target_primary_green_x, 9175);
+    assert_eq!(level10.target_primary_green_y, 21299);
+    assert_eq!(level10.target_primary_blue_x, 4915);
+    assert_eq!(level10.target_primary_blue_y, 2294);
+    assert_eq!(level10.target_primary_white_x, 10246);
+    assert_eq!(level10.target_primary_white_y, 10780);
+
+    Ok(())
+}
+
+#[test]
+fn custom_target_display_level10_known_primaries() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let custom_target = r#"<TargetDisplay>
+                <ID>101</ID>
+                <Name>400-nit, P3, D65, ST.2084, Full</Name>
+                <Primaries>
+                  <Red>0.68 0.32</Red>
+                  <Green>0.265 0.69</Green>
+                  <Blue>0.15 0.06</Blue>
+                </Primaries>
+                <WhitePoint>0.3127 0.329</WhitePoint>
+                <PeakBrightness>400</PeakBrightness>
+                <MinimumBrightness>0.005</MinimumBrightness>
+                <EOTF>pq</EOTF>
+                <DiagonalSize>42</DiagonalSize>
+              </TargetDisplay>"#;
+
+    // Defined twice, signaled once
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let xml = xml.replacen(
+        "</DVGlobalData>",
+        &format!("{}\n{}\n</DVGlobalData>", custom_target, custom_target),
+        1,
+    );
+
+    let parser = CmXmlParser::new(xml, XmlParserOpts::default())?;
+    let config = parser.config;
+
+    let level10_blocks: Vec<_> = config
+        .default_metadata_blocks
+        .iter()
+        .filter_map(|b| match b {
+            ExtMetadataBlock::Level10(b) => Some(b),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(level10_blocks.len(), 1);
+
+    // DCI-P3 D65 index, without explicit primaries
+    let level10 = level10_blocks[0];
+    assert_eq!(level10.target_display_index, 101);
+    assert_eq!(level10.target_primary_index, 0);
+    assert_eq!(level10.target_primary_red_x, 0);
+    assert_eq!(level10.target_primary_white_y, 0);
+
+    Ok(())
+}
+
+#[test]
+fn global_level5_from_first_shot() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let xml = xml
+        .replacen("<CanvasAspectRatio>1.77778</CanvasAspectRatio>", "", 1)
+        .replacen("<ImageAspectRatio>1.33333</ImageAspectRatio>", "", 1);
+
+    let opts = XmlParserOpts {
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        strict: false,
+        length: None,
+        ..Default::default()
+    };
+    let parser = CmXmlParser::new(xml.clone(), opts)?;
+
+    // First shot: 1.77778 canvas, 1.55556 image
+    assert_eq!(parser.config.level5.get_offsets(), (240, 240, 0, 0));
+
+    // No canvas dimensions, zero offsets
+    let parser = CmXmlParser::new(xml, XmlParserOpts::default())?;
+    assert_eq!(parser.config.level5.get_offsets(), (0, 0, 0, 0));
+
+    Ok(())
+}
+
+#[test]
+fn level8_without_optional_trims() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let xml = xml
+        .replace("<MidContrastBias>0</MidContrastBias>", "")
+        .replace("<HighlightClipping>0</HighlightClipping>", "");
+
+    let parser = CmXmlParser::new(xml, XmlParserOpts::default())?;
+    let shot = &parser.config.shots[1];
+
+    let level8_blocks: Vec<_> = shot
+        .metadata_blocks
+        .iter()
+        .filter(|b| b.level() == 8)
+        .collect();
+    assert_eq!(level8_blocks.len(), 2);
+
+    for block in level8_blocks {
+        assert_eq!(block.required_bits(), 80);
+        assert_eq!(block.length_bytes(), 10);
+
+        if let ExtMetadataBlock::Level8(b) = block {
+            assert_eq!(b.target_mid_contrast, 2048);
+            assert_eq!(b.clip_trim, 2048);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn duplicate_shot_ids() -> Result<()> {
+    use crate::utils::WarningSink;
+
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let xml = xml.replacen(
+        "06dbea53-d78e-4031-8a5b-e9a87d6fc2c9",
+        "7b4b3d42-81c8-4767-aa38-f08a6f35994b",
+        1,
+    );
+
+    let (sink, warnings) = WarningSink::capture();
+
+    // Both shots are kept
+    let parser = CmXmlParser::new(xml.clone(), XmlParserOpts::default().with_warnings(sink))?;
+    assert_eq!(parser.config.shots.len(), 3);
+
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec!["Duplicate shot UniqueID 7b4b3d42-81c8-4767-aa38-f08a6f35994b"]
+    );
+
+    let opts = XmlParserOpts {
+        strict: true,
+        ..Default::default()
+    };
+    let err = CmXmlParser::new(xml, opts).unwrap_err();
+
+    assert_eq!(
+        err.downcast_ref::<XmlParseError>(),
+        Some(&XmlParseError::DuplicateShotId {
+            id: String::from("7b4b3d42-81c8-4767-aa38-f08a6f35994b"),
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn shots_without_duration() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+
+    // Second and last shots without Duration
+    let xml =
+        xml.replacen("<Duration>99</Duration>", "", 1)
+            .replacen("<Duration>40</Duration>", "", 1);
+
+    let opts = XmlParserOpts {
+        length: Some(259),
+        ..Default::default()
+    };
+    let parser = CmXmlParser::new(xml.clone(), opts)?;
+
+    // Until the next shot, then until the end of the sequence
+    let durations: Vec<usize> = parser.config.shots.iter().map(|s| s.duration).collect();
+    assert_eq!(durations, vec![120, 99, 40]);
+    assert_eq!(parser.config.length, 259);
+
+    // The last shot can't end without the sequence length
+    let err = CmXmlParser::new(xml, XmlParserOpts::default()).unwrap_err();
+
+    assert_eq!(
+        err.downcast_ref::<XmlParseError>(),
+        Some(&XmlParseError::UnknownShotDuration {
+            id: String::from("f06f8317-94df-4b18-9801-911ec0b188de"),
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "xml-gz")]
+fn parse_gzipped_xml() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let plain = CmXmlParser::parse_file(
+        &assets_path.join("assets/tests/cmv2_9.xml"),
+        XmlParserOpts::default(),
+    )?;
+    let gzipped = CmXmlParser::parse_file(
+        &assets_path.join("assets/tests/cmv2_9.xml.gz"),
+        XmlParserOpts::default(),
+    )?;
+
+    assert_eq!(gzipped.config.length, 108);
+    assert_eq!(
+        format!("{:?}", gzipped.config),
+        format!("{:?}", plain.config)
+    );
+
+    // Detected from the magic bytes, without the extension
+    let renamed_path = std::env::temp_dir().join(format!(
+        "dovi_tool_gzipped_cmv2_9_{}.xml",
+        std::process::id()
+    ));
+    std::fs::copy(
+        assets_path.join("assets/tests/cmv2_9.xml.gz"),
+        &renamed_path,
+    )?;
+
+    let renamed = CmXmlParser::parse_file(&renamed_path, XmlParserOpts::default());
+    std::fs::remove_file(&renamed_path)?;
+
+    assert_eq!(
+        format!("{:?}", renamed?.config),
+        format!("{:?}", plain.config)
+    );
+
+    Ok(())
+}
+
+#[test]
+#[cfg(not(feature = "xml-gz"))]
+fn gzipped_xml_without_feature() {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let res = CmXmlParser::parse_file(
+        &assets_path.join("assets/tests/cmv2_9.xml.gz"),
+        XmlParserOpts::default(),
+    );
+
+    let err = res.unwrap_err().to_string();
+    assert!(err.contains("the `xml-gz` feature is not enabled"));
+}
+
+#[test]
+fn malformed_values_errors() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+
+    let parse_err = |xml: String| -> XmlParseError {
+        CmXmlParser::new(xml, XmlParserOpts::default())
+            .unwrap_err()
+            .downcast::<XmlParseError>()
+            .unwrap()
+    };
+
+    assert_eq!(
+        parse_err(xml.replacen("<MaxCLL>3948</MaxCLL>", "<MaxCLL>3948 nits</MaxCLL>", 1)),
+        XmlParseError::InvalidValue {
+            field: "MaxCLL".to_string(),
+            value: "3948 nits".to_string(),
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen("<DMVersion>2</DMVersion>", "<DMVersion>v2</DMVersion>", 1)),
+        XmlParseError::InvalidValue {
+            field: "DMVersion".to_string(),
+            value: "v2".to_string(),
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen(
+            "0.15 0.06 0.3127 0.329</SourceColorPrimary>",
+            "0.15 0.06 D65 0.329</SourceColorPrimary>",
+            1
+        )),
+        XmlParseError::InvalidValue {
+            field: "SourceColorPrimary".to_string(),
+            value: "D65".to_string(),
+        }
+    );
+
+    assert_eq!(
+        parse_err(xml.replacen(
+            "<UniqueID>06dbea53-d78e-4031-8a5b-e9a87d6fc2c9</UniqueID>",
+            "",
+            1
+        )),
+        XmlParseError::MissingNode {
+            name: "UniqueID".to_string()
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+fn legacy_cmv2_9_versions() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv2_9.xml"))?;
+    let current_config = parse_config(xml.clone())?;
+
+    for fixture in &["cmv2_9_legacy_2_0_1.xml", "cmv2_9_legacy_2_0_4.xml"] {
+        let legacy_xml = std::fs::read_to_string(assets_path.join("assets/tests").join(fixture))?;
+        let config = parse_config(legacy_xml)?;
+
+        assert_eq!(config.cm_version, CmVersion::V29);
+
+        // Same dynamic metadata as the 2.0.5 layout
+        assert_eq!(config.shots.len(), 2);
+        assert_eq!(
+            format!("{:?}", config.shots),
+            format!("{:?}", current_config.shots)
+        );
+        assert_num_blocks_for_level(&config.shots[0].metadata_blocks, 1, 1);
+        assert_num_blocks_for_level(&config.shots[0].metadata_blocks, 2, 3);
+
+        // No Level6 node, only the mastering display
+        assert_eq!(config.level6.max_display_mastering_luminance, 1000);
+        assert_eq!(config.level6.min_display_mastering_luminance, 1);
+        assert_eq!(config.level6.max_content_light_level, 0);
+        assert_eq!(config.level6.max_frame_average_light_level, 0);
+
+        let rpus = config.generate_rpu_list()?;
+        assert_eq!(rpus.len(), 108);
+        assert_eq!(rpus[0].dovi_profile, 8);
+
+        let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+        assert!(vdr_dm_data.cmv40_metadata.is_none());
+    }
+
+    // Still unhandled
+    let err = CmXmlParser::new(
+        xml.replacen("version=\"2.0.5\"", "version=\"0.2.0\"", 1),
+        XmlParserOpts::default(),
+    )
+    .unwrap_err();
+
+    assert_eq!(
+        err.downcast_ref::<XmlParseError>(),
+        Some(&XmlParseError::UnhandledLegacyVersion {
+            found: "0.2.0".to_string()
+        })
+    );
+
+    Ok(())
+}
+
+#[test]
+fn write_cmv4_0_2() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let config = parse_config(xml)?;
+
+    let written_xml = CmXmlWriter::from_config(&config, canvas_writer_opts())?.to_xml();
+    assert!(written_xml.contains("<Version>4.0.2</Version>"));
+    assert!(written_xml.contains("<L8Trim>"));
+
+    let written_config = parse_config(written_xml)?;
+    assert_eq!(written_config.cm_version, CmVersion::V40);
+    assert_eq!(written_config.length, 259);
+    assert_eq!(written_config.shots.len(), 3);
+    assert_eq!(written_config.shots[0].id, config.shots[0].id);
+    assert_eq!(written_config.shots[0].start, 86400);
+    assert_eq!(written_config.shots[2].frame_edits.len(), 1);
+    assert_eq!(written_config.shots[2].frame_edits[0].edit_offset, 10);
+
+    assert_same_rpus(
+        &config.generate_rpu_list()?,
+        &written_config.generate_rpu_list()?,
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn write_cmv2_9() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv2_9.xml"))?;
+    let config = parse_config(xml)?;
+
+    let written_xml = CmXmlWriter::from_config(&config, canvas_writer_opts())?.to_xml();
+    assert!(written_xml.contains("<DolbyLabsMDF version=\"2.0.5\">"));
+    assert!(!written_xml.contains("<L8Trim>"));
+
+    let written_config = parse_config(written_xml)?;
+    assert_eq!(written_config.cm_version, CmVersion::V29);
+    assert_eq!(written_config.level5.get_offsets(), (0, 0, 276, 276));
+    assert_eq!(written_config.level6.max_content_light_level, 756);
+    assert_eq!(written_config.level6.max_frame_average_light_level, 97);
+
+    assert_same_rpus(
+        &config.generate_rpu_list()?,
+        &written_config.generate_rpu_list()?,
+    )?;
+
+    Ok(())
+}
+
+#[test]
+fn write_rpus_to_xml() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+    let rpus = parse_config(xml)?.generate_rpu_list()?;
+
+    // Shots from the scene cuts
+    let writer = CmXmlWriter::from_rpus(&rpus, canvas_writer_opts())?;
+    let written_config = parse_config(writer.to_xml())?;
+
+    assert_eq!(written_config.shots.len(), 3);
+    assert_eq!(written_config.shots[1].start, 120);
+
+    assert_same_rpus(&rpus, &written_config.generate_rpu_list()?)?;
+
+    // L5 omitted without the canvas dimensions
+    let writer = CmXmlWriter::from_rpus(&rpus, XmlWriterOpts::default())?;
+    assert!(!writer.to_xml().contains("AspectRatio"));
+
+    Ok(())
+}
+
+#[test]
+fn parse_level11() -> Result<()> {
+    let lib_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let assets_path = lib_path.parent().unwrap();
+
+    let xml = std::fs::read_to_string(assets_path.join("assets/tests/cmv4_0_2.xml"))?;
+
+    let with_level11 = |fields: &str| {
+        xml.replacen(
+            "</Level9>",
+            &format!("</Level9>\n<Level11 level=\"11\">{}</Level11>", fields),
+            1,
+        )
+    };
+    let shot_level11 = |config: &GenerateConfig| {
+        config.shots[0]
+            .metadata_blocks
+            .iter()
+            .find_map(|b| match b {
+                ExtMetadataBlock::Level11(b) => Some(b.clone()),
+                _ => None,
+            })
+            .unwrap()
+    };
+
+    let fields = "<ContentType>4</ContentType><IntendedWhitePoint>2</IntendedWhitePoint>\
+        <ReferenceMode>0</ReferenceMode>";
+    let config = parse_config(with_level11(fields))?;
+
+    let level11 = shot_level11(&config);
+    assert_eq!(level11.content_type, 4);
+    assert_eq!(level11.whitepoint, 2);
+    assert!(!level11.reference_mode_flag);
+
+    // Kept when writing back
+    let writer = CmXmlWriter::from_config(&config, canvas_writer_opts())?;
+    let written_config = parse_config(writer.to_xml())?;
+    assert_eq!(shot_level11(&written_config).content_type, 4);
+
+    // Cinema, reference mode and D65 by default
+    let level11 = shot_level11(&parse_config(with_level11(""))?);
+    assert_eq!(level11.content_type, 1);
+    assert_eq!(level11.whitepoint, 0);
+    assert!(level11.reference_mode_flag);
+
+    // No reference mode for an undefined content type
+    let level11 = shot_level11(&parse_config(with_level11("<ContentType>8</ContentType>"))?);
+    assert_eq!(level11.content_type, 8);
+    assert!(!level11.reference_mode_flag);
+
+    let err = parse_config(with_level11("<ContentType>16</ContentType>"))
+        .unwrap_err()
+        .downcast::<XmlParseError>()
+        .unwrap();
+    assert_eq!(
+        err,
+        XmlParseError::InvalidValue {
+            field: "ContentType".to_string(),
+            value: "16".to_string(),
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,778 @@
+use anyhow::{anyhow, ensure, Result};
+use std::fmt::Display;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+
+use crate::rpu::dovi_rpu::DoviRpu;
+use crate::rpu::extension_metadata::blocks::*;
+use crate::rpu::generate::{same_metadata_blocks, GenerateConfig, ShotFrameEdit, VideoShot};
+use crate::rpu::trims::trim_power_to_gamma;
+use crate::rpu::vdr_dm_data::{CmVersion, VdrDmData};
+use crate::utils::{pq_to_nits, target_pq_to_nits, WarningSink};
+
+use level10::PRESET_TARGET_DISPLAYS;
+use level9::primaries_from_signed_values;
+
+/// IDs of the standard target displays, as exported by the grading tools
+const STANDARD_TARGET_DISPLAY_IDS: &[(u16, u8)] = &[(100, 1), (600, 27), (1000, 48)];
+
+/// Writes the metadata back as a Dolby Vision XML, the reverse of `CmXmlParser`.
+///
+/// Only the nodes read by `CmXmlParser` are written.
+/// Each shot has the metadata of its first frame, other frames of the shot
+/// with different metadata are written as frame edits.
+///
+/// The metadata is preserved within rounding, with some limitations of the XML format:
+/// - L5 is written as aspect ratios, the offsets are centered when parsed again.
+/// - L2 and L8 trim powers below 683 are written as the minimum gamma of -1.0.
+#[derive(Debug)]
+pub struct CmXmlWriter {
+    opts: XmlWriterOpts,
+    cm_version: CmVersion,
+
+    level5: ExtMetadataBlockLevel5,
+    level6: ExtMetadataBlockLevel6,
+    level254: Option<ExtMetadataBlockLevel254>,
+
+    target_displays: Vec<TargetDisplay>,
+    shots: Vec<VideoShot>,
+}
+
+#[derive(Default, Debug)]
+pub struct XmlWriterOpts {
+    /// Canvas dimensions, required to write the L5 metadata as aspect ratios.
+    /// The L5 metadata is omitted otherwise.
+    pub canvas_width: Option<u16>,
+    pub canvas_height: Option<u16>,
+
+    /// Receives the warnings, such as skipped L8 trims
+    pub warnings: WarningSink,
+}
+
+#[derive(Debug)]
+struct TargetDisplay {
+    id: u8,
+    peak_nits: u16,
+    min_nits: Option<f64>,
+    primaries: Option<[f64; 8]>,
+}
+
+impl CmXmlWriter {
+    /// Writer for the RPUs generated from the config, keeping its shots.
+    pub fn from_config(config: &GenerateConfig, opts: XmlWriterOpts) -> Result<CmXmlWriter> {
+        let rpus = config.generate_rpu_list()?;
+
+        let mut start = config.shots.first().map_or(0, |shot| shot.start);
+        let shots = config
+            .shots
+            .iter()
+            .enumerate()
+            .map(|(i, shot)| {
+                let id = if shot.id.is_empty() {
+                    i.to_string()
+                } else {
+                    shot.id.clone()
+                };
+
+                let range = (id, start, shot.duration);
+                start += shot.duration;
+
+                range
+            })
+            .collect();
+
+        Self::new(&rpus, shots, opts)
+    }
+
+    /// Writer for a list of RPUs, with a shot starting at every scene cut.
+    pub fn from_rpus(rpus: &[DoviRpu], opts: XmlWriterOpts) -> Result<CmXmlWriter> {
+        let scene_cuts: Vec<usize> = rpus
+            .iter()
+            .enumerate()
+            .filter(|(i, rpu)| {
+                *i == 0
+                    || rpu
+                        .vdr_dm_data
+                        .as_ref()
+                        .map_or(false, |vdr_dm_data| vdr_dm_data.scene_refresh_flag != 0)
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        let shots = scene_cuts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = scene_cuts.get(i + 1).copied().unwrap_or(rpus.len());
+
+                (i.to_string(), start, end - start)
+            })
+            .collect();
+
+        Self::new(rpus, shots, opts)
+    }
+
+    /// `shots` are the ID, start and duration of the consecutive shots
+    fn new(
+        rpus: &[DoviRpu],
+        shots: Vec<(String, usize, usize)>,
+        opts: XmlWriterOpts,
+    ) -> Result<CmXmlWriter> {
+        ensure!(!rpus.is_empty(), "No RPUs to write to XML");
+
+        let first = frame_dm_data(rpus, 0)?;
+
+        let cm_version = if first.cmv40_metadata.is_some() {
+            CmVersion::V40
+        } else {
+            CmVersion::V29
+        };
+
+        // The levels written per shot by the XML
+        let levels: &[u8] = match cm_version {
+            CmVersion::V29 => &[1, 2, 5],
+            CmVersion::V40 => &[1, 2, 3, 5, 8, 9, 11],
+        };
+
+        let mut index = 0;
+        let mut xml_shots = Vec::with_capacity(shots.len());
+
+        for (id, start, duration) in shots {
+            let metadata_blocks = frame_blocks(rpus, index, levels)?;
+            let mut frame_edits = Vec::new();
+
+            for edit_offset in 1..duration {
+                let blocks = frame_blocks(rpus, index + edit_offset, levels)?;
+
+                if !same_metadata_blocks(&metadata_blocks, &blocks) {
+                    frame_edits.push(ShotFrameEdit {
+                        edit_offset,
+                        metadata_blocks: blocks,
+                    });
+                }
+            }
+
+            xml_shots.push(VideoShot {
+                id,
+                start,
+                duration,
+                metadata_blocks,
+                frame_edits,
+                ..Default::default()
+            });
+
+            index += duration;
+        }
+
+        let level5 = match first.get_block(5) {
+            Some(ExtMetadataBlock::Level5(b)) => b.clone(),
+            _ => Default::default(),
+        };
+
+        let level6 = match first.get_block(6) {
+            Some(ExtMetadataBlock::Level6(b)) => b.clone(),
+            _ => Default::default(),
+        };
+
+        let level254 = match first.get_block(254) {
+            Some(ExtMetadataBlock::Level254(b)) if cm_version == CmVersion::V40 => Some(b.clone()),
+            _ => None,
+        };
+
+        let mut level10_blocks: Vec<&ExtMetadataBlockLevel10> = Vec::new();
+
+        if cm_version == CmVersion::V40 {
+            let blocks = rpus
+                .iter()
+                .filter_map(|rpu| rpu.vdr_dm_data.as_ref())
+                .flat_map(|vdr_dm_data| vdr_dm_data.level_blocks_iter(10));
+
+            for block in blocks {
+                if let ExtMetadataBlock::Level10(b) = block {
+                    let index = b.target_display_index;
+
+                    if !level10_blocks
+                        .iter()
+                        .any(|l| l.target_display_index == index)
+                    {
+                        level10_blocks.push(b);
+                    }
+                }
+            }
+        }
+
+        let target_displays = target_displays(&level10_blocks, &xml_shots, &opts.warnings);
+
+        Ok(CmXmlWriter {
+            opts,
+            cm_version,
+            level5,
+            level6,
+            level254,
+            target_displays,
+            shots: xml_shots,
+        })
+    }
+
+    pub fn write_file(&self, path: &Path) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(self.to_xml().as_bytes())?;
+
+        Ok(())
+    }
+
+    /// The XML document, as version 4.0.2 for CM v4.0 and version 2.0.5 for CM v2.9
+    pub fn to_xml(&self) -> String {
+        let mut xml = XmlBuilder::default();
+
+        xml.line("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+
+        if self.is_cmv4() {
+            xml.open("DolbyLabsMDF xmlns=\"http://www.dolby.com/schemas/dvmd/4_0_2\"");
+            xml.node("Version", "4.0.2");
+        } else {
+            xml.open("DolbyLabsMDF version=\"2.0.5\"");
+        }
+
+        xml.open("Outputs");
+        xml.open("Output");
+
+        if let Some((canvas_ar, image_ar)) = self.aspect_ratios(&self.level5) {
+            xml.node("CanvasAspectRatio", canvas_ar);
+            xml.node("ImageAspectRatio", image_ar);
+        }
+
+        xml.open("Video");
+        xml.open("Track");
+
+        self.write_global_metadata(&mut xml);
+
+        for shot in &self.shots {
+            self.write_shot(&mut xml, shot);
+        }
+
+        xml.close("Track");
+        xml.close("Video");
+        xml.close("Output");
+        xml.close("Outputs");
+        xml.close("DolbyLabsMDF");
+
+        xml.xml
+    }
+
+    fn write_global_metadata(&self, xml: &mut XmlBuilder) {
+        let level6 = &self.level6;
+
+        xml.open(if self.is_cmv4() {
+            "Level6 level=\"6\""
+        } else {
+            "Level6"
+        });
+        xml.node("MaxCLL", level6.max_content_light_level);
+        xml.node("MaxFALL", level6.max_frame_average_light_level);
+        xml.close("Level6");
+
+        xml.open("PluginNode");
+
+        if self.is_cmv4() {
+            xml.open("DVGlobalData level=\"0\"");
+            xml.open("MasteringDisplay");
+        } else {
+            xml.open("DolbyEDR");
+            xml.open("Characteristics level=\"0\"");
+            xml.open("MasteringDisplay level=\"0\"");
+        }
+
+        xml.node("PeakBrightness", level6.max_display_mastering_luminance);
+        xml.node(
+            "MinimumBrightness",
+            level6.min_display_mastering_luminance as f32 / 10000.0,
+        );
+        xml.close("MasteringDisplay");
+
+        for target in &self.target_displays {
+            self.write_target_display(xml, target);
+        }
+
+        if self.is_cmv4() {
+            xml.close("DVGlobalData");
+
+            if let Some(level254) = &self.level254 {
+                xml.open("Level254 level=\"254\"");
+                xml.node("DMMode", level254.dm_mode);
+                xml.node("DMVersion", level254.dm_version_index);
+                xml.node("CMVersion", "4 0");
+                xml.close("Level254");
+            }
+        } else {
+            xml.close("Characteristics");
+            xml.close("DolbyEDR");
+        }
+
+        xml.close("PluginNode");
+    }
+
+    fn write_target_display(&self, xml: &mut XmlBuilder, target: &TargetDisplay) {
+        xml.open(if self.is_cmv4() {
+            "TargetDisplay"
+        } else {
+            "TargetDisplay level=\"0\""
+        });
+        xml.node("ID", target.id);
+
+        if let Some(p) = &target.primaries {
+            xml.open("Primaries");
+            xml.node("Red", self.join(&p[0..2]));
+            xml.node("Green", self.join(&p[2..4]));
+            xml.node("Blue", self.join(&p[4..6]));
+            xml.close("Primaries");
+            xml.node("WhitePoint", self.join(&p[6..8]));
+        }
+
+        xml.node("PeakBrightness", target.peak_nits);
+
+        if let Some(min_nits) = target.min_nits {
+            xml.node("MinimumBrightness", min_nits);
+        }
+
+        xml.close("TargetDisplay");
+    }
+
+    fn write_shot(&self, xml: &mut XmlBuilder, shot: &VideoShot) {
+        xml.open("Shot");
+        xml.node("UniqueID", escape(&shot.id));
+
+        xml.open("Record");
+        xml.node("In", shot.start);
+        xml.node("Duration", shot.duration);
+        xml.close("Record");
+
+        self.write_dynamic_metadata(xml, &shot.metadata_blocks);
+
+        for edit in &shot.frame_edits {
+            xml.open("Frame");
+            xml.node("EditOffset", edit.edit_offset);
+            self.write_dynamic_metadata(xml, &edit.metadata_blocks);
+            xml.close("Frame");
+        }
+
+        xml.close("Shot");
+    }
+
+    fn write_dynamic_metadata(&self, xml: &mut XmlBuilder, blocks: &[ExtMetadataBlock]) {
+        xml.open("PluginNode");
+
+        if self.is_cmv4() {
+            xml.open("DVDynamicData");
+        }
+
+        for block in blocks {
+            self.write_block(xml, block);
+        }
+
+        if self.is_cmv4() {
+            xml.close("DVDynamicData");
+        }
+
+        xml.close("PluginNode");
+    }
+
+    fn write_block(&self, xml: &mut XmlBuilder, block: &ExtMetadataBlock) {
+        let level = block.level();
+
+        // Skipped when the values can't be written
+        let target_id = match block {
+            ExtMetadataBlock::Level2(b) => match self.level2_target_id(b.target_max_pq) {
+                Some(id) => Some(id),
+                None => return,
+            },
+            ExtMetadataBlock::Level5(b) if self.aspect_ratios(b).is_none() => return,
+            ExtMetadataBlock::Level8(b) => match self.target_display(b.target_display_index) {
+                Some(target) => Some(target.id),
+                None => return,
+            },
+            ExtMetadataBlock::Level9(b) if level9_primaries(b).is_none() => return,
+            _ => None,
+        };
+
+        let (name, close) = if self.is_cmv4() {
+            (
+                format!("Level{} level=\"{}\"", level, level),
+                format!("Level{}", level),
+            )
+        } else {
+            (
+                format!("DolbyEDR level=\"{}\"", level),
+                "DolbyEDR".to_string(),
+            )
+        };
+
+        xml.open(&name);
+
+        if let Some(id) = target_id {
+            xml.node("TID", id);
+        }
+
+        match block {
+            ExtMetadataBlock::Level1(b) => {
+                let values: Vec<f32> = [b.min_pq, b.avg_pq, b.max_pq]
+                    .iter()
+                    .map(|&v| v as f32 / 4095.0)
+                    .collect();
+                xml.node("ImageCharacter", self.join(&values));
+            }
+            ExtMetadataBlock::Level2(b) => {
+                let (lift, gain) = trim_lift_gain(b.trim_slope, b.trim_offset);
+                let values = [
+                    0.0,
+                    0.0,
+                    0.0,
+                    lift,
+                    gain,
+                    trim_power_to_gamma(b.trim_power),
+                    from_12_bit_offset(b.trim_chroma_weight),
+                    from_12_bit_offset(b.trim_saturation_gain),
+                    (b.ms_weight as f32 - 2048.0) / 2048.0,
+                ];
+
+                xml.node("Trim", self.join(&values));
+            }
+            ExtMetadataBlock::Level3(b) => {
+                let values: Vec<f32> = [b.min_pq_offset, b.max_pq_offset, b.avg_pq_offset]
+                    .iter()
+                    .map(|&v| from_12_bit_offset(v))
+                    .collect();
+                xml.node("L1Offset", self.join(&values));
+            }
+            ExtMetadataBlock::Level5(b) => {
+                if let Some((canvas_ar, image_ar)) = self.aspect_ratios(b) {
+                    xml.node("AspectRatios", self.join(&[canvas_ar, image_ar]));
+                }
+            }
+            ExtMetadataBlock::Level8(b) => {
+                let (lift, gain) = trim_lift_gain(b.trim_slope, b.trim_offset);
+                let values = [
+                    lift,
+                    gain,
+                    trim_power_to_gamma(b.trim_power),
+                    from_12_bit_offset(b.trim_chroma_weight),
+                    from_12_bit_offset(b.trim_saturation_gain),
+                    from_12_bit_offset(b.ms_weight),
+                ];
+
+                let satvec: Vec<f32> = [
+                    b.saturation_vector_field0,
+                    b.saturation_vector_field1,
+                    b.saturation_vector_field2,
+                    b.saturation_vector_field3,
+                    b.saturation_vector_field4,
+                    b.saturation_vector_field5,
+                ]
+                .iter()
+                .map(|&v| from_8_bit_offset(v))
+                .collect();
+                let huevec: Vec<f32> = [
+                    b.hue_vector_field0,
+                    b.hue_vector_field1,
+                    b.hue_vector_field2,
+                    b.hue_vector_field3,
+                    b.hue_vector_field4,
+                    b.hue_vector_field5,
+                ]
+                .iter()
+                .map(|&v| from_8_bit_offset(v))
+                .collect();
+
+                xml.node("L8Trim", self.join(&values));
+                xml.node("MidContrastBias", from_12_bit_offset(b.target_mid_contrast));
+                xml.node("HighlightClipping", from_12_bit_offset(b.clip_trim));
+                xml.node("SaturationVectorField", self.join(&satvec));
+                xml.node("HueVectorField", self.join(&huevec));
+            }
+            ExtMetadataBlock::Level9(b) => {
+                if let Some(primaries) = level9_primaries(b) {
+                    xml.node("SourceColorModel", 255);
+                    xml.node("SourceColorPrimary", self.join(&primaries));
+                }
+            }
+            ExtMetadataBlock::Level11(b) => {
+                xml.node("ContentType", b.content_type);
+                xml.node("IntendedWhitePoint", b.whitepoint);
+                xml.node("ReferenceMode", b.reference_mode_flag as u8);
+            }
+            _ => (),
+        }
+
+        xml.close(&close);
+    }
+
+    fn is_cmv4(&self) -> bool {
+        self.cm_version == CmVersion::V40
+    }
+
+    fn join<T: Display>(&self, values: &[T]) -> String {
+        let separator = if self.is_cmv4() { " " } else { "," };
+
+        values
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<String>>()
+            .join(separator)
+    }
+
+    fn target_display(&self, id: u8) -> Option<&TargetDisplay> {
+        self.target_displays.iter().find(|target| target.id == id)
+    }
+
+    fn level2_target_id(&self, target_max_pq: u16) -> Option<u8> {
+        self.target_displays
+            .iter()
+            .find(|target| {
+                ExtMetadataBlockLevel2::from_nits(target.peak_nits).target_max_pq == target_max_pq
+            })
+            .map(|target| target.id)
+    }
+
+    /// Canvas and image aspect ratios of the L5 offsets
+    fn aspect_ratios(&self, level5: &ExtMetadataBlockLevel5) -> Option<(f32, f32)> {
+        let canvas_width = self.opts.canvas_width? as f32;
+        let canvas_height = self.opts.canvas_height? as f32;
+
+        let (left, right, top, bottom) = level5.get_offsets();
+        let image_width = canvas_width - (left as f32 + right as f32);
+        let image_height = canvas_height - (top as f32 + bottom as f32);
+
+        if image_width <= 0.0 || image_height <= 0.0 {
+            return None;
+        }
+
+        Some((canvas_width / canvas_height, image_width / image_height))
+    }
+}
+
+fn frame_dm_data(rpus: &[DoviRpu], index: usize) -> Result<&VdrDmData> {
+    rpus.get(index)
+        .and_then(|rpu| rpu.vdr_dm_data.as_ref())
+        .ok_or_else(|| anyhow!("Frame {}: No DM metadata to write to XML", index))
+}
+
+/// Blocks of the levels written per shot
+fn frame_blocks(rpus: &[DoviRpu], index: usize, levels: &[u8]) -> Result<Vec<ExtMetadataBlock>> {
+    let vdr_dm_data = frame_dm_data(rpus, index)?;
+
+    Ok(levels
+        .iter()
+        .flat_map(|&level| vdr_dm_data.level_blocks_iter(level).cloned())
+        .collect())
+}
+
+/// Target displays of the custom L10 targets, the L2 targets and the L8 targets.
+///
+/// L2 targets use the ID of a target display with the same peak brightness,
+/// or of a standard target display, otherwise a free ID is assigned.
+/// L8 targets of preset target displays with unknown peak brightness are skipped.
+fn target_displays(
+    level10_blocks: &[&ExtMetadataBlockLevel10],
+    shots: &[VideoShot],
+    warnings: &WarningSink,
+) -> Vec<TargetDisplay> {
+    let mut targets: Vec<TargetDisplay> = level10_blocks
+        .iter()
+        .map(|b| TargetDisplay {
+            id: b.target_display_index,
+            peak_nits: target_pq_to_nits(b.target_max_pq),
+            min_nits: Some(pq_to_nits(b.target_min_pq as f64 / 4095.0)),
+            primaries: level10_primaries(b),
+        })
+        .collect();
+
+    let blocks = shots.iter().flat_map(|shot| {
+        shot.metadata_blocks.iter().chain(
+            shot.frame_edits
+                .iter()
+                .flat_map(|e| e.metadata_blocks.iter()),
+        )
+    });
+
+    let mut level2_targets: Vec<u16> = Vec::new();
+    let mut level8_targets: Vec<u8> = Vec::new();
+
+    for block in blocks {
+        match block {
+            ExtMetadataBlock::Level2(b) if !level2_targets.contains(&b.target_max_pq) => {
+                level2_targets.push(b.target_max_pq)
+            }
+            ExtMetadataBlock::Level8(b) if !level8_targets.contains(&b.target_display_index) => {
+                level8_targets.push(b.target_display_index)
+            }
+            _ => (),
+        }
+    }
+
+    level2_targets.sort_unstable();
+    level8_targets.sort_unstable();
+
+    let standard_target = |id: u8| {
+        STANDARD_TARGET_DISPLAY_IDS
+            .iter()
+            .find(|(_, standard_id)| *standard_id == id)
+            .map(|(nits, _)| TargetDisplay {
+                id,
+                peak_nits: *nits,
+                min_nits: None,
+                primaries: None,
+            })
+    };
+
+    for id in level8_targets {
+        if targets.iter().any(|target| target.id == id) {
+            continue;
+        }
+
+        match standard_target(id) {
+            Some(target) => targets.push(target),
+            None => warnings.warn(&format!(
+                "XML: Unknown peak brightness for target display {}, L8 trims skipped",
+                id
+            )),
+        }
+    }
+
+    for target_max_pq in level2_targets {
+        let nits = target_pq_to_nits(target_max_pq);
+        let same_target = |target: &TargetDisplay| {
+            ExtMetadataBlockLevel2::from_nits(target.peak_nits).target_max_pq == target_max_pq
+        };
+
+        if targets.iter().any(same_target) {
+            continue;
+        }
+
+        let standard_id = STANDARD_TARGET_DISPLAY_IDS
+            .iter()
+            .find(|(standard_nits, _)| *standard_nits == nits)
+            .map(|(_, id)| *id)
+            .filter(|id| !targets.iter().any(|target| target.id == *id));
+
+        // Presets are avoided, as their ID implies the target display characteristics
+        let id = standard_id.or_else(|| {
+            (1..=u8::MAX).find(|id| {
+                !PRESET_TARGET_DISPLAYS.contains(id) && !targets.iter().any(|t| t.id == *id)
+            })
+        });
+
+        if let Some(id) = id {
+            targets.push(TargetDisplay {
+                id,
+                peak_nits: nits,
+                min_nits: None,
+                primaries: None,
+            });
+        }
+    }
+
+    targets.sort_by_key(|target| target.id);
+
+    targets
+}
+
+/// Trim lift and gain of the XML trims, from the 12 bit trim slope and offset
+fn trim_lift_gain(trim_slope: u16, trim_offset: u16) -> (f32, f32) {
+    let slope = from_12_bit_offset(trim_slope);
+    let offset = from_12_bit_offset(trim_offset);
+
+    let gain = slope + offset;
+    let lift = if gain + 2.0 != 0.0 {
+        2.0 * offset / (gain + 2.0)
+    } else {
+        0.0
+    };
+
+    (lift, gain)
+}
+
+/// XML value of a 12 bit value centered on 2048
+fn from_12_bit_offset(value: u16) -> f32 {
+    (value as f32 - 2048.0) / 2048.0
+}
+
+/// XML value of an 8 bit value centered on 128
+fn from_8_bit_offset(value: u8) -> f32 {
+    (value as f32 - 128.0) / 128.0
+}
+
+fn level9_primaries(level9: &ExtMetadataBlockLevel9) -> Option<[f64; 8]> {
+    match level9.source_primary_index {
+        255 => Some(primaries_from_signed_values(&[
+            level9.source_primary_red_x,
+            level9.source_primary_red_y,
+            level9.source_primary_green_x,
+            level9.source_primary_green_y,
+            level9.source_primary_blue_x,
+            level9.source_primary_blue_y,
+            level9.source_primary_white_x,
+            level9.source_primary_white_y,
+        ])),
+        index => PREDEFINED_COLORSPACE_PRIMARIES
+            .iter()
+            .chain(PREDEFINED_REALDEVICE_PRIMARIES.iter())
+            .nth(index as usize)
+            .copied(),
+    }
+}
+
+fn level10_primaries(level10: &ExtMetadataBlockLevel10) -> Option<[f64; 8]> {
+    match level10.target_primary_index {
+        255 => Some(primaries_from_signed_values(&[
+            level10.target_primary_red_x,
+            level10.target_primary_red_y,
+            level10.target_primary_green_x,
+            level10.target_primary_green_y,
+            level10.target_primary_blue_x,
+            level10.target_primary_blue_y,
+            level10.target_primary_white_x,
+            level10.target_primary_white_y,
+        ])),
+        index => PREDEFINED_COLORSPACE_PRIMARIES.get(index as usize).copied(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Indented XML, two spaces per level
+#[derive(Default)]
+struct XmlBuilder {
+    xml: String,
+    depth: usize,
+}
+
+impl XmlBuilder {
+    fn line(&mut self, line: &str) {
+        for _ in 0..self.depth {
+            self.xml.push_str("  ");
+        }
+
+        self.xml.push_str(line);
+        self.xml.push('\n');
+    }
+
+    /// `tag` can have attributes, closed by its name
+    fn open(&mut self, tag: &str) {
+        self.line(&format!("<{}>", tag));
+        self.depth += 1;
+    }
+
+    fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.line(&format!("</{}>", name));
+    }
+
+    fn node<T: Display>(&mut self, name: &str, value: T) {
+        self.line(&format!("<{}>{}</{}>", name, value, name));
+    }
+}
@@ -127,6 +127,23 @@ pub enum Command {
         discard: bool,
     },
 
+    #[structopt(name = "to-81")]
+    To81 {
+        #[structopt(
+            name = "input",
+            help = "Sets the dual layer profile 7 input file to use",
+            parse(from_os_str)
+        )]
+        input: PathBuf,
+
+        #[structopt(
+            name = "output",
+            help = "Single layer profile 8.1 output file location, defaults to BL_RPU.hevc",
+            parse(from_os_str)
+        )]
+        output: Option<PathBuf>,
+    },
+
     InjectRpu {
         #[structopt(
             name = "input",
@@ -149,6 +166,56 @@ pub enum Command {
         output: Option<PathBuf>,
     },
 
+    Stats {
+        #[structopt(
+            name = "input",
+            long,
+            short = "i",
+            help = "Sets the input RPU file to use",
+            parse(from_os_str)
+        )]
+        input: PathBuf,
+
+        #[structopt(
+            long,
+            help = "Prints a histogram of the L1 max PQ, with the specified number of buckets"
+        )]
+        histogram: Option<usize>,
+
+        #[structopt(
+            long,
+            short = "c",
+            help = "Generator JSON config, to report its QC markers",
+            parse(from_os_str)
+        )]
+        config: Option<PathBuf>,
+    },
+
+    CheckSync {
+        #[structopt(
+            name = "input",
+            long,
+            short = "i",
+            help = "Sets the input BL HEVC file to use",
+            parse(from_os_str)
+        )]
+        input: PathBuf,
+
+        #[structopt(long, help = "Sets the input RPU file to use", parse(from_os_str))]
+        rpu_in: PathBuf,
+    },
+
+    ContainerInfo {
+        #[structopt(
+            name = "input",
+            long,
+            short = "i",
+            help = "Sets the input MP4 file to use",
+            parse(from_os_str)
+        )]
+        input: PathBuf,
+    },
+
     Info {
         #[structopt(
             name = "input",
@@ -166,6 +233,32 @@ pub enum Command {
             help = "Frame number to show info for"
         )]
         frame: Option<usize>,
+
+        #[structopt(
+            long,
+            help = "List the conversion modes available for the frame, defaults to the first frame"
+        )]
+        conversions: bool,
+    },
+
+    Bits {
+        #[structopt(
+            name = "input",
+            long,
+            short = "i",
+            help = "Sets the input RPU file to use",
+            parse(from_os_str)
+        )]
+        input: PathBuf,
+
+        #[structopt(
+            name = "frame",
+            long,
+            short = "f",
+            default_value = "0",
+            help = "Frame number to show the bit layout for"
+        )]
+        frame: usize,
     },
 
     Generate {
@@ -182,7 +275,7 @@ pub enum Command {
         #[structopt(
             long,
             short = "o",
-            help = "Generated RPU output file location",
+            help = "Generated RPU output file, or directory with --xml-dir without --concat",
             parse(from_os_str)
         )]
         rpu_out: Option<PathBuf>,
@@ -199,12 +292,49 @@ pub enum Command {
         )]
         xml: Option<PathBuf>,
 
+        #[structopt(
+            long,
+            help = "Directory of XML metadata files to generate from, one RPU per XML",
+            conflicts_with_all = &["json", "xml"],
+            parse(from_os_str)
+        )]
+        xml_dir: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "XML directory: concatenate the RPUs in filename order into the output file",
+            requires = "xml-dir"
+        )]
+        concat: bool,
+
+        #[structopt(
+            long,
+            help = "Cut list file of scene start frames to create the shots from",
+            requires = "json",
+            conflicts_with_all = &["hdr10plus-json", "madvr-file"],
+            parse(from_os_str)
+        )]
+        cut_list: Option<PathBuf>,
+
         #[structopt(long, help = "Canvas width for L5 metadata generation")]
         canvas_width: Option<u16>,
 
         #[structopt(long, help = "Canvas height for L5 metadata generation")]
         canvas_height: Option<u16>,
 
+        #[structopt(
+            long,
+            help = "XML: fail on suspicious metadata instead of warning, such as duplicate shot IDs"
+        )]
+        strict: bool,
+
+        #[structopt(
+            long,
+            help = "XML: length of the sequence in frames, for a last shot without duration",
+            conflicts_with = "xml-dir"
+        )]
+        length: Option<usize>,
+
         #[structopt(
             long,
             help = "madVR measurement file to generate from",
@@ -218,6 +348,51 @@ pub enum Command {
             help = "madVR source: use custom per-frame target nits if available"
         )]
         use_custom_targets: bool,
+
+        #[structopt(
+            long,
+            help = "Snap the L2/L10 target displays to the nearest standard peak brightness"
+        )]
+        snap_targets: bool,
+
+        #[structopt(long, help = "Add a 100 nits L2 trim to every frame without one")]
+        sdr_fallback_trim: bool,
+
+        #[structopt(
+            long,
+            help = "Only keep the metadata of the target display with this peak brightness"
+        )]
+        single_target_nits: Option<u16>,
+
+        #[structopt(
+            long,
+            help = "HEVC base layer to inject the generated RPU into. Respects --drop-hdr10plus",
+            conflicts_with = "xml-dir",
+            parse(from_os_str)
+        )]
+        inject_bl: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Output HEVC file of the injection, defaults to injected_output.hevc",
+            requires = "inject-bl",
+            parse(from_os_str)
+        )]
+        inject_output: Option<PathBuf>,
+    },
+
+    XmlDiff {
+        #[structopt(long, help = "Sets the original XML metadata file", parse(from_os_str))]
+        old: PathBuf,
+
+        #[structopt(long, help = "Sets the modified XML metadata file", parse(from_os_str))]
+        new: PathBuf,
+
+        #[structopt(long, help = "Canvas width for L5 metadata parsing")]
+        canvas_width: Option<u16>,
+
+        #[structopt(long, help = "Canvas height for L5 metadata parsing")]
+        canvas_height: Option<u16>,
     },
 
     Export {
@@ -234,9 +409,40 @@ pub enum Command {
             name = "output",
             long,
             short = "o",
-            help = "Output JSON file name",
+            help = "Output file name, JSON by default or CSV with --l5-csv",
             parse(from_os_str)
         )]
         output: Option<PathBuf>,
+
+        #[structopt(
+            long,
+            help = "Exports the L5 offsets of every frame as CSV: frame,top,bottom,left,right"
+        )]
+        l5_csv: bool,
+
+        #[structopt(
+            long,
+            help = "Canvas width, to export the L5 offsets relative to the canvas size",
+            requires_all = &["l5-csv", "canvas-height"]
+        )]
+        canvas_width: Option<u16>,
+
+        #[structopt(
+            long,
+            help = "Canvas height, to export the L5 offsets relative to the canvas size",
+            requires_all = &["l5-csv", "canvas-width"]
+        )]
+        canvas_height: Option<u16>,
+    },
+
+    ConvertFormat {
+        #[structopt(help = "Input RPU file, binary or JSON (.json)", parse(from_os_str))]
+        input: PathBuf,
+
+        #[structopt(
+            help = "Output RPU file, JSON (.json) for a binary input and binary otherwise",
+            parse(from_os_str)
+        )]
+        output: PathBuf,
     },
 }
@@ -0,0 +1,142 @@
+use anyhow::{bail, ensure, Result};
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Dolby Vision configuration box types, depending on the base layer codec
+const DV_CONFIG_BOX_TYPES: &[&[u8]] = &[b"dvcC", b"dvvC", b"dvwC"];
+
+/// Size of the DOVIDecoderConfigurationRecord
+const DV_CONFIG_RECORD_SIZE: usize = 24;
+
+pub struct ContainerInfo;
+
+/// Dolby Vision configuration signaled by the container
+#[derive(Debug, PartialEq)]
+pub struct DvContainerInfo {
+    pub version_major: u8,
+    pub version_minor: u8,
+    pub profile: u8,
+    pub level: u8,
+    pub rpu_present: bool,
+    pub el_present: bool,
+    pub bl_present: bool,
+    pub bl_signal_compatibility_id: u8,
+}
+
+impl ContainerInfo {
+    pub fn info(input: PathBuf) -> Result<()> {
+        let info = detect_dv_in_mp4(&input)?;
+
+        println!(
+            "Dolby Vision configuration version {}.{}",
+            info.version_major, info.version_minor
+        );
+        println!("  Profile: {}", info.profile);
+        println!("  Level: {}", info.level);
+        println!("  RPU present: {}", info.rpu_present);
+        println!("  EL present: {}", info.el_present);
+        println!("  BL present: {}", info.bl_present);
+        println!(
+            "  BL signal compatibility ID: {}",
+            info.bl_signal_compatibility_id
+        );
+
+        Ok(())
+    }
+}
+
+/// Reads the Dolby Vision configuration from the `dvcC`/`dvvC` box of an MP4 file,
+/// without demuxing the samples.
+pub fn detect_dv_in_mp4(path: &Path) -> Result<DvContainerInfo> {
+    let moov = read_moov_box(path)?;
+
+    parse_dv_config(&moov)
+}
+
+fn read_moov_box(path: &Path) -> Result<Vec<u8>> {
+    let file = File::open(path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut reader = BufReader::new(file);
+    let mut offset = 0;
+
+    while offset + 8 <= file_size {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+
+        let mut size = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        let mut header_size = 8;
+
+        if size == 1 {
+            let mut large_size = [0; 8];
+            reader.read_exact(&mut large_size)?;
+
+            size = u64::from_be_bytes(large_size);
+            header_size = 16;
+        } else if size == 0 {
+            // Box extends to the end of the file
+            size = file_size - offset;
+        }
+
+        ensure!(
+            size >= header_size && offset + size <= file_size,
+            "Invalid MP4 box size {} at offset {}",
+            size,
+            offset
+        );
+
+        let payload_size = size - header_size;
+
+        if &header[4..] == b"moov" {
+            let mut moov = vec![0; payload_size as usize];
+            reader.read_exact(&mut moov)?;
+
+            return Ok(moov);
+        }
+
+        reader.seek(SeekFrom::Current(payload_size as i64))?;
+        offset += size;
+    }
+
+    bail!("No moov box found, not a valid MP4 file")
+}
+
+fn parse_dv_config(moov: &[u8]) -> Result<DvContainerInfo> {
+    // The configuration box is nested in the video sample entry,
+    // look for the box type preceded by a valid box size.
+    let config_start = moov
+        .windows(4)
+        .enumerate()
+        .skip(4)
+        .find(|(i, box_type)| {
+            let start = i - 4;
+            let size = u32::from_be_bytes([
+                moov[start],
+                moov[start + 1],
+                moov[start + 2],
+                moov[start + 3],
+            ]) as usize;
+
+            DV_CONFIG_BOX_TYPES.contains(box_type)
+                && size >= 8 + DV_CONFIG_RECORD_SIZE
+                && start + size <= moov.len()
+        })
+        .map(|(i, _)| i + 4);
+
+    let record = match config_start {
+        Some(start) => &moov[start..start + DV_CONFIG_RECORD_SIZE],
+        None => bail!("No Dolby Vision configuration box found"),
+    };
+
+    Ok(DvContainerInfo {
+        version_major: record[0],
+        version_minor: record[1],
+        profile: record[2] >> 1,
+        level: ((record[2] & 0x01) << 5) | (record[3] >> 3),
+        rpu_present: record[3] & 0x04 != 0,
+        el_present: record[3] & 0x02 != 0,
+        bl_present: record[3] & 0x01 != 0,
+        bl_signal_compatibility_id: record[4] >> 4,
+    })
+}
@@ -1,8 +1,7 @@
 use anyhow::{bail, Result};
-use indicatif::ProgressBar;
 use std::path::PathBuf;
 
-use super::{input_format, io, CliOptions, Format};
+use super::{input_format, io, progress::ProgressSink, CliOptions, Format};
 
 use io::{DoviReader, DoviWriter};
 
@@ -49,8 +48,26 @@ impl Converter {
         demuxer.process_input(options)
     }
 
+    /// Converts a dual layer profile 7 stream to a single layer profile 8.1 stream.
+    /// The EL is discarded, and the RPUs are converted with mode 2.
+    pub fn convert_to_81(
+        input: PathBuf,
+        output: Option<PathBuf>,
+        mut options: CliOptions,
+    ) -> Result<()> {
+        options.mode = Some(2);
+        options.discard_el = true;
+
+        Converter::convert(Some(input), None, output, options)
+    }
+
     fn process_input(&self, options: CliOptions) -> Result<()> {
-        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
+        let pb = super::initialize_progress(
+            &self.format,
+            &self.input,
+            "convert",
+            options.progress_json,
+        )?;
 
         match self.format {
             Format::Matroska => bail!("unsupported"),
@@ -58,7 +75,7 @@ impl Converter {
         }
     }
 
-    fn convert_raw_hevc(&self, pb: Option<&ProgressBar>, options: CliOptions) -> Result<()> {
+    fn convert_raw_hevc(&self, pb: Option<&ProgressSink>, options: CliOptions) -> Result<()> {
         let mut dovi_reader = DoviReader::new(options);
         let mut dovi_writer = DoviWriter::new(None, None, None, Some(&self.output));
 
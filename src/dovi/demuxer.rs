@@ -1,11 +1,19 @@
 use anyhow::{bail, Result};
-use indicatif::ProgressBar;
 use std::path::PathBuf;
 
-use super::{input_format, io, CliOptions, Format};
+use super::{input_format, io, progress::ProgressSink, CliOptions, Format};
 
 use io::{DoviReader, DoviWriter};
 
+/// Sizes of the layers read while demuxing, to estimate the cost of keeping the EL.
+/// The sizes are the NAL units without start codes, the RPUs are not part of the EL size.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct DemuxSummary {
+    pub bl_bytes: u64,
+    pub el_bytes: u64,
+    pub rpu_count: usize,
+}
+
 pub struct Demuxer {
     format: Format,
     input: PathBuf,
@@ -38,7 +46,7 @@ impl Demuxer {
         el_out: Option<PathBuf>,
         el_only: bool,
         options: CliOptions,
-    ) -> Result<()> {
+    ) -> Result<DemuxSummary> {
         let input = match input {
             Some(input) => input,
             None => match stdin {
@@ -60,11 +68,16 @@ impl Demuxer {
         };
 
         let demuxer = Demuxer::new(format, input, bl_out, el_out, el_only);
-        demuxer.process_input(options)
+        let summary = demuxer.process_input(options)?;
+
+        summary.print();
+
+        Ok(summary)
     }
 
-    fn process_input(&self, options: CliOptions) -> Result<()> {
-        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
+    fn process_input(&self, options: CliOptions) -> Result<DemuxSummary> {
+        let pb =
+            super::initialize_progress(&self.format, &self.input, "demux", options.progress_json)?;
 
         match self.format {
             Format::Matroska => bail!("unsupported"),
@@ -72,7 +85,11 @@ impl Demuxer {
         }
     }
 
-    fn demux_raw_hevc(&self, pb: Option<&ProgressBar>, options: CliOptions) -> Result<()> {
+    fn demux_raw_hevc(
+        &self,
+        pb: Option<&ProgressSink>,
+        options: CliOptions,
+    ) -> Result<DemuxSummary> {
         let mut dovi_reader = DoviReader::new(options);
 
         let bl_out = if self.el_only {
@@ -83,6 +100,30 @@ impl Demuxer {
 
         let mut dovi_writer = DoviWriter::new(bl_out, Some(self.el_out.as_path()), None, None);
 
-        dovi_reader.read_write_from_io(&self.format, &self.input, pb, &mut dovi_writer)
+        dovi_reader.read_write_from_io(&self.format, &self.input, pb, &mut dovi_writer)?;
+
+        Ok(dovi_reader.demux_summary)
+    }
+}
+
+impl DemuxSummary {
+    /// Size of the EL relative to the BL, `None` without BL data
+    pub fn el_bl_ratio(&self) -> Option<f64> {
+        if self.bl_bytes > 0 {
+            Some(self.el_bytes as f64 / self.bl_bytes as f64)
+        } else {
+            None
+        }
+    }
+
+    pub fn print(&self) {
+        println!(
+            "BL: {} bytes, EL: {} bytes, RPUs: {}",
+            self.bl_bytes, self.el_bytes, self.rpu_count
+        );
+
+        if let Some(ratio) = self.el_bl_ratio() {
+            println!("EL/BL ratio: {:.2}%", ratio * 100.0);
+        }
     }
 }
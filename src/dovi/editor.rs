@@ -181,10 +181,36 @@ impl EditConfig {
 
     fn convert_with_mode(&self, rpus: &mut Vec<Option<DoviRpu>>) -> Result<()> {
         println!("Converting with mode {}...", self.mode);
-        let list = rpus.iter_mut().filter_map(|e| e.as_mut());
+        let list = rpus
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, e)| e.as_mut().map(|rpu| (i, rpu)));
 
-        for rpu in list {
+        let mut invalid_count = 0;
+
+        for (i, rpu) in list {
             rpu.convert_with_mode(self.mode)?;
+
+            // Profile 5 to 8.1
+            if self.mode == 3 {
+                let issues = rpu.p81_conversion_issues();
+
+                if !issues.is_empty() {
+                    if invalid_count == 0 {
+                        println!(
+                            "Invalid profile 8.1 conversion for frame {}: {}",
+                            i,
+                            issues.join(", ")
+                        );
+                    }
+
+                    invalid_count += 1;
+                }
+            }
+        }
+
+        if invalid_count > 1 {
+            println!("{} converted frames are invalid", invalid_count);
         }
 
         Ok(())
@@ -1,5 +1,5 @@
 use std::fs::File;
-use std::io::BufWriter;
+use std::io::{BufWriter, Write};
 use std::path::PathBuf;
 
 use anyhow::Result;
@@ -8,6 +8,8 @@ use serde::Serializer;
 
 use crate::dovi::parse_rpu_file;
 
+use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlock;
+
 use super::DoviRpu;
 
 pub struct Exporter {
@@ -38,6 +40,73 @@ impl Exporter {
         Ok(())
     }
 
+    /// Exports the L5 offsets of every frame as CSV, to plot the crops over time.
+    /// With a canvas size, the offsets are relative to the canvas width or height.
+    pub fn export_l5_csv(
+        input: PathBuf,
+        output: Option<PathBuf>,
+        canvas: Option<(u16, u16)>,
+    ) -> Result<()> {
+        let out_path = output.unwrap_or_else(|| PathBuf::from("L5_export.csv"));
+
+        if let Some(rpus) = parse_rpu_file(&input)? {
+            println!("Exporting L5 metadata...");
+
+            let mut writer = BufWriter::with_capacity(100_000, File::create(&out_path)?);
+
+            for line in Self::l5_csv_lines(&rpus, canvas) {
+                writeln!(writer, "{}", line)?;
+            }
+
+            writer.flush()?;
+        }
+
+        println!("Done.");
+
+        Ok(())
+    }
+
+    /// Lines of the L5 CSV export, starting with the header.
+    /// The offset fields are left empty for frames without L5 metadata.
+    pub fn l5_csv_lines(rpus: &[DoviRpu], canvas: Option<(u16, u16)>) -> Vec<String> {
+        let mut lines = vec![String::from("frame,top,bottom,left,right")];
+
+        for (frame, rpu) in rpus.iter().enumerate() {
+            let level5 = rpu
+                .vdr_dm_data
+                .as_ref()
+                .and_then(|vdr_dm_data| vdr_dm_data.get_block(5));
+
+            let line = match level5 {
+                Some(ExtMetadataBlock::Level5(level5)) => {
+                    let (left, right, top, bottom) = level5.get_offsets();
+
+                    match canvas {
+                        Some((width, height)) => {
+                            let width = width as f64;
+                            let height = height as f64;
+
+                            format!(
+                                "{},{:.6},{:.6},{:.6},{:.6}",
+                                frame,
+                                top as f64 / height,
+                                bottom as f64 / height,
+                                left as f64 / width,
+                                right as f64 / width
+                            )
+                        }
+                        None => format!("{},{},{},{},{}", frame, top, bottom, left, right),
+                    }
+                }
+                _ => format!("{},,,,", frame),
+            };
+
+            lines.push(line);
+        }
+
+        lines
+    }
+
     fn execute(&self) -> Result<()> {
         println!("Exporting metadata...");
 
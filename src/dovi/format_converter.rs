@@ -0,0 +1,64 @@
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+
+use super::exporter::Exporter;
+use super::{write_rpu_file, DoviRpu};
+
+pub struct FormatConverter {
+    input: PathBuf,
+    output: PathBuf,
+}
+
+impl FormatConverter {
+    /// Converts a binary RPU file to JSON, or a JSON RPU file back to binary.
+    /// The direction is detected from the `.json` extension of either path.
+    pub fn convert_format(input: PathBuf, output: PathBuf) -> Result<()> {
+        let converter = FormatConverter { input, output };
+
+        match (is_json(&converter.input), is_json(&converter.output)) {
+            (false, true) => Exporter::export(converter.input, Some(converter.output)),
+            (true, false) => converter.json_to_binary(),
+            (true, true) => bail!("Both input and output are JSON files"),
+            (false, false) => bail!("Either the input or the output must be a JSON file"),
+        }
+    }
+
+    fn json_to_binary(&self) -> Result<()> {
+        println!("Parsing JSON file...");
+
+        let reader = BufReader::new(File::open(&self.input)?);
+        let values: Vec<serde_json::Value> = serde_json::from_reader(reader)?;
+
+        if values.is_empty() {
+            bail!("No RPU found");
+        }
+
+        let data = values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| {
+                let rpu = match DoviRpu::from_json(value) {
+                    Ok(rpu) => rpu,
+                    Err(e) => bail!("Invalid RPU for frame {}: {}", i, e),
+                };
+
+                rpu.write_hevc_unspec62_nalu()
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        write_rpu_file(&self.output, data)?;
+
+        println!("Done.");
+
+        Ok(())
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case("json"))
+}
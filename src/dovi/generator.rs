@@ -1,9 +1,11 @@
 use anyhow::{bail, ensure, Result};
 use serde_json::Value;
-use std::fs::File;
+use std::fs::{create_dir_all, read_dir, File};
 use std::io::{stdout, Read, Write};
 use std::path::{Path, PathBuf};
 
+use super::rpu_injector::RpuInjector;
+use super::{write_rpu_file, CliOptions};
 use crate::commands::Command;
 use dolby_vision::rpu::extension_metadata::blocks::{
     ExtMetadataBlock, ExtMetadataBlockLevel1, ExtMetadataBlockLevel6,
@@ -18,10 +20,23 @@ pub struct Generator {
     rpu_out: PathBuf,
     hdr10plus_path: Option<PathBuf>,
     xml_path: Option<PathBuf>,
+    xml_dir: Option<PathBuf>,
+    concat: bool,
+    cut_list_path: Option<PathBuf>,
     canvas_width: Option<u16>,
     canvas_height: Option<u16>,
+    strict: bool,
+    length: Option<usize>,
     madvr_path: Option<PathBuf>,
     use_custom_targets: bool,
+    snap_targets: bool,
+    sdr_fallback_trim: bool,
+    single_target_nits: Option<u16>,
+    inject_bl: Option<PathBuf>,
+    inject_output: Option<PathBuf>,
+
+    /// Global options used when injecting into the base layer, such as `drop_hdr10plus`
+    pub cli_options: CliOptions,
 
     pub config: Option<GenerateConfig>,
 }
@@ -33,14 +48,27 @@ impl Generator {
             rpu_out,
             hdr10plus_json,
             xml,
+            xml_dir,
+            concat,
+            cut_list,
             canvas_width,
             canvas_height,
+            strict,
+            length,
             madvr_file,
             use_custom_targets,
+            snap_targets,
+            sdr_fallback_trim,
+            single_target_nits,
+            inject_bl,
+            inject_output,
         } = cmd
         {
             let out_path = if let Some(out_path) = rpu_out {
                 out_path
+            } else if xml_dir.is_some() && !concat {
+                // Output directory of the RPUs generated per XML
+                PathBuf::from(".")
             } else {
                 PathBuf::from("RPU_generated.bin".to_string())
             };
@@ -50,10 +78,21 @@ impl Generator {
                 rpu_out: out_path,
                 hdr10plus_path: hdr10plus_json,
                 xml_path: xml,
+                xml_dir,
+                concat,
+                cut_list_path: cut_list,
                 canvas_width,
                 canvas_height,
+                strict,
+                length,
                 madvr_path: madvr_file,
                 use_custom_targets,
+                snap_targets,
+                sdr_fallback_trim,
+                single_target_nits,
+                inject_bl,
+                inject_output,
+                cli_options: CliOptions::default(),
                 config: None,
             };
 
@@ -64,7 +103,15 @@ impl Generator {
     }
 
     pub fn generate(&mut self) -> Result<()> {
-        let config = if let Some(json_path) = &self.json_path {
+        if let Some(xml_dir) = &self.xml_dir {
+            self.generate_from_xml_dir(xml_dir)?;
+
+            println!("Done.");
+
+            return Ok(());
+        }
+
+        let mut config = if let Some(json_path) = &self.json_path {
             let json_file = File::open(json_path)?;
             let mut config: GenerateConfig = serde_json::from_reader(&json_file)?;
 
@@ -72,6 +119,8 @@ impl Generator {
                 parse_hdr10plus_for_l1(hdr10plus_path, &mut config)?;
             } else if let Some(madvr_path) = &self.madvr_path {
                 generate_metadata_from_madvr(madvr_path, self.use_custom_targets, &mut config)?;
+            } else if let Some(cut_list_path) = &self.cut_list_path {
+                generate_shots_from_cut_list(cut_list_path, &mut config)?;
             } else if config.length == 0 && !config.shots.is_empty() {
                 // Set length from sum of shot durations
                 config.length = config.shots.iter().map(|s| s.duration).sum();
@@ -98,6 +147,18 @@ impl Generator {
             bail!("Missing configuration or XML file!");
         };
 
+        if self.snap_targets {
+            config.snap_targets();
+        }
+
+        if self.sdr_fallback_trim {
+            config.sdr_fallback_trim = true;
+        }
+
+        if self.single_target_nits.is_some() {
+            config.single_target_nits = self.single_target_nits;
+        }
+
         self.config = Some(config);
         self.execute()?;
 
@@ -113,6 +174,15 @@ impl Generator {
             config.write_rpus(&self.rpu_out)?;
 
             println!("Generated metadata for {} frames", config.length);
+
+            if let Some(inject_bl) = &self.inject_bl {
+                RpuInjector::inject_rpu(
+                    inject_bl.clone(),
+                    self.rpu_out.clone(),
+                    self.inject_output.clone(),
+                    self.cli_options.clone(),
+                )?;
+            }
         } else {
             bail!("No generation config to execute!");
         }
@@ -120,13 +190,84 @@ impl Generator {
         Ok(())
     }
 
+    /// Generates one RPU file per XML in the output directory, or a single concatenated RPU file.
+    /// The XMLs are processed in filename order.
+    fn generate_from_xml_dir(&self, xml_dir: &Path) -> Result<()> {
+        let mut xml_paths: Vec<PathBuf> = read_dir(xml_dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.is_file() && xml_file_stem(p).is_some())
+            .collect();
+
+        ensure!(
+            !xml_paths.is_empty(),
+            "No XML files found in {}",
+            xml_dir.display()
+        );
+
+        xml_paths.sort();
+
+        let mut configs = Vec::with_capacity(xml_paths.len());
+
+        for xml_path in &xml_paths {
+            let mut config = self.config_from_xml(xml_path)?;
+
+            if self.snap_targets {
+                config.snap_targets();
+            }
+
+            if self.sdr_fallback_trim {
+                config.sdr_fallback_trim = true;
+            }
+
+            if self.single_target_nits.is_some() {
+                config.single_target_nits = self.single_target_nits;
+            }
+
+            println!("{}: {} frames", xml_path.display(), config.length);
+
+            configs.push(config);
+        }
+
+        println!("Generating metadata...");
+
+        if self.concat {
+            let cm_version = &configs[0].cm_version;
+
+            ensure!(
+                configs.iter().all(|c| &c.cm_version == cm_version),
+                "Cannot concatenate XMLs with different CM versions"
+            );
+
+            let mut encoded_rpus = Vec::new();
+
+            for config in &configs {
+                let mut rpus = config.generate_rpu_list()?;
+                encoded_rpus.extend(GenerateConfig::encode_rpus(&mut rpus));
+            }
+
+            let length = encoded_rpus.len();
+            write_rpu_file(&self.rpu_out, encoded_rpus)?;
+
+            println!("Generated metadata for {} frames", length);
+        } else {
+            create_dir_all(&self.rpu_out)?;
+
+            for (xml_path, config) in xml_paths.iter().zip(configs.iter()) {
+                let file_name = format!("{}.bin", xml_file_stem(xml_path).unwrap());
+                config.write_rpus(&self.rpu_out.join(file_name))?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn config_from_xml(&self, xml_path: &Path) -> Result<GenerateConfig> {
         println!("Parsing XML metadata...");
 
-        let parser_opts = XmlParserOpts {
-            canvas_width: self.canvas_width,
-            canvas_height: self.canvas_height,
-        };
+        let parser_opts = XmlParserOpts::default()
+            .with_canvas(self.canvas_width, self.canvas_height)
+            .with_strict(self.strict)
+            .with_length(self.length);
 
         let parser = CmXmlParser::parse_file(xml_path, parser_opts)?;
 
@@ -196,10 +337,8 @@ fn parse_hdr10plus_for_l1(hdr10plus_path: &Path, config: &mut GenerateConfig) ->
                     let max_rgb = maxscl.iter().filter_map(|e| e.as_u64()).max().unwrap();
 
                     let min_pq = 0;
-                    let max_pq = (nits_to_pq(max_rgb as f64 / 10.0) * 4095.0)
-                        .round() as u16;
-                    let avg_pq = (nits_to_pq(avg_rgb as f64 / 10.0) * 4095.0)
-                        .round() as u16;
+                    let max_pq = (nits_to_pq(max_rgb as f64 / 10.0) * 4095.0).round() as u16;
+                    let avg_pq = (nits_to_pq(avg_rgb as f64 / 10.0) * 4095.0).round() as u16;
 
                     let mut shot = VideoShot {
                         start: frame_no,
@@ -233,6 +372,74 @@ fn parse_hdr10plus_for_l1(hdr10plus_path: &Path, config: &mut GenerateConfig) ->
     Ok(())
 }
 
+/// File name without the `.xml` or `.xml.gz` extension, `None` for other files
+fn xml_file_stem(path: &Path) -> Option<&str> {
+    let name = path.file_name()?.to_str()?;
+    let lower = name.to_ascii_lowercase();
+
+    [".xml", ".xml.gz"]
+        .iter()
+        .find(|ext| lower.ends_with(*ext))
+        .map(|ext| &name[..name.len() - ext.len()])
+}
+
+/// Parses a cut list file: the start frames of the scenes, separated by whitespace or commas.
+/// Lines starting with `#` are ignored.
+pub fn parse_cut_list(cut_list_path: &Path) -> Result<Vec<usize>> {
+    let mut s = String::new();
+    File::open(cut_list_path)?.read_to_string(&mut s)?;
+
+    let mut cuts = Vec::new();
+
+    for (line_no, line) in s.lines().enumerate() {
+        if line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        for value in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if value.is_empty() {
+                continue;
+            }
+
+            match value.parse::<usize>() {
+                Ok(frame) => cuts.push(frame),
+                Err(_) => bail!(
+                    "Invalid frame number '{}' in cut list, line {}",
+                    value,
+                    line_no + 1
+                ),
+            }
+        }
+    }
+
+    Ok(cuts)
+}
+
+pub fn generate_shots_from_cut_list(
+    cut_list_path: &Path,
+    config: &mut GenerateConfig,
+) -> Result<()> {
+    println!("Parsing cut list...");
+    stdout().flush().ok();
+
+    ensure!(
+        config.length > 0,
+        "The config length is required to generate from a cut list"
+    );
+
+    let cuts = parse_cut_list(cut_list_path)?;
+    let mut cut_list_shots = VideoShot::from_cut_list(&cuts, config.length)?;
+
+    // Metadata from the config shots, in list order
+    for (shot, config_shot) in cut_list_shots.iter_mut().zip(config.shots.iter()) {
+        shot.copy_metadata_from_shot(config_shot, None);
+    }
+
+    config.shots = cut_list_shots;
+
+    Ok(())
+}
+
 pub fn generate_metadata_from_madvr(
     madvr_path: &Path,
     use_custom_targets: bool,
@@ -1,20 +1,22 @@
 use anyhow::{bail, Result};
-use indicatif::ProgressBar;
 use std::io::Read;
 use std::io::{stdout, BufRead, BufReader, BufWriter, Write};
 use std::{fs::File, path::Path};
 
-use hevc_parser::hevc::NALUnit;
-use hevc_parser::hevc::{NAL_SEI_PREFIX, NAL_UNSPEC62, NAL_UNSPEC63};
+use hevc_parser::hevc::{Frame, NALUnit};
+use hevc_parser::hevc::{NAL_UNSPEC62, NAL_UNSPEC63};
 use hevc_parser::HevcParser;
 
 use dolby_vision::rpu::dovi_rpu::DoviRpu;
 
-use super::{is_st2094_40_sei, CliOptions, Format, OUT_NAL_HEADER};
+use super::demuxer::DemuxSummary;
+use super::progress::ProgressSink;
+use super::{CliOptions, Format, OUT_NAL_HEADER};
 
 pub struct DoviReader {
     options: CliOptions,
     rpu_nals: Vec<RpuNal>,
+    pub demux_summary: DemuxSummary,
 }
 
 pub struct DoviWriter {
@@ -81,6 +83,7 @@ impl DoviReader {
         DoviReader {
             options,
             rpu_nals: Vec::new(),
+            demux_summary: DemuxSummary::default(),
         }
     }
 
@@ -88,7 +91,7 @@ impl DoviReader {
         &mut self,
         format: &Format,
         input: &Path,
-        pb: Option<&ProgressBar>,
+        pb: Option<&ProgressSink>,
         dovi_writer: &mut DoviWriter,
     ) -> Result<()> {
         //BufReader & BufWriter
@@ -201,9 +204,9 @@ impl DoviReader {
         nals: &[NALUnit],
     ) -> Result<()> {
         for nal in nals {
-            if self.options.drop_hdr10plus
-                && nal.nal_type == NAL_SEI_PREFIX
-                && is_st2094_40_sei(&chunk[nal.start..nal.end])?
+            if self
+                .options
+                .drop_sei_nal(nal.nal_type, &chunk[nal.start..nal.end])
             {
                 continue;
             }
@@ -220,6 +223,8 @@ impl DoviReader {
                         match DoviRpu::parse_unspec62_nalu(&chunk[nal.start..nal.end]) {
                             Ok(mut dovi_rpu) => {
                                 dovi_rpu.convert_with_mode(mode)?;
+                                dovi_rpu.validate_required_blocks =
+                                    self.options.validate_required_blocks;
 
                                 if self.options.crop {
                                     dovi_rpu.crop()?;
@@ -240,14 +245,20 @@ impl DoviReader {
                 continue;
             }
 
+            let nal_size = (nal.end - nal.start) as u64;
+
             match nal.nal_type {
                 NAL_UNSPEC63 => {
+                    self.demux_summary.el_bytes += nal_size;
+
                     if let Some(ref mut el_writer) = dovi_writer.el_writer {
                         el_writer.write_all(OUT_NAL_HEADER)?;
                         el_writer.write_all(&chunk[nal.start + 2..nal.end])?;
                     }
                 }
                 NAL_UNSPEC62 => {
+                    self.demux_summary.rpu_count += 1;
+
                     if let Some(ref mut el_writer) = dovi_writer.el_writer {
                         el_writer.write_all(OUT_NAL_HEADER)?;
                     }
@@ -261,6 +272,8 @@ impl DoviReader {
                         match DoviRpu::parse_unspec62_nalu(&chunk[nal.start..nal.end]) {
                             Ok(mut dovi_rpu) => {
                                 dovi_rpu.convert_with_mode(mode)?;
+                                dovi_rpu.validate_required_blocks =
+                                    self.options.validate_required_blocks;
 
                                 if self.options.crop {
                                     dovi_rpu.crop()?;
@@ -293,6 +306,8 @@ impl DoviReader {
                     }
                 }
                 _ => {
+                    self.demux_summary.bl_bytes += nal_size;
+
                     if let Some(ref mut bl_writer) = dovi_writer.bl_writer {
                         bl_writer.write_all(OUT_NAL_HEADER)?;
                         bl_writer.write_all(&chunk[nal.start..nal.end])?;
@@ -313,41 +328,11 @@ impl DoviReader {
             el_writer.flush()?;
         }
 
-        // Reorder RPUs to display output order
         if let Some(ref mut rpu_writer) = dovi_writer.rpu_writer {
-            let frames = parser.ordered_frames();
-
-            if frames.is_empty() {
-                bail!("No frames parsed!");
+            if self.options.reorder {
+                self.reorder_rpu_nals(parser)?;
             }
 
-            print!("Reordering metadata... ");
-            stdout().flush().ok();
-
-            // Sort by matching frame POC
-            self.rpu_nals.sort_by_cached_key(|rpu| {
-                let matching_index = frames
-                    .iter()
-                    .position(|f| rpu.decoded_index == f.decoded_number as usize);
-
-                if let Some(i) = matching_index {
-                    frames[i].presentation_number
-                } else {
-                    panic!(
-                        "Missing frame/slices for metadata! Decoded index {}",
-                        rpu.decoded_index
-                    );
-                }
-            });
-
-            // Set presentation number to new index
-            self.rpu_nals
-                .iter_mut()
-                .enumerate()
-                .for_each(|(idx, rpu)| rpu.presentation_number = idx);
-
-            println!("Done.");
-
             // Write data to file
             for rpu in self.rpu_nals.iter_mut() {
                 rpu_writer.write_all(OUT_NAL_HEADER)?;
@@ -359,4 +344,64 @@ impl DoviReader {
 
         Ok(())
     }
+
+    /// Reorder RPUs to display output order
+    fn reorder_rpu_nals(&mut self, parser: &HevcParser) -> Result<()> {
+        let frames = parser.ordered_frames();
+
+        if frames.is_empty() {
+            bail!("No frames parsed!");
+        }
+
+        print!("Reordering metadata... ");
+        stdout().flush().ok();
+
+        let decoded_indices: Vec<usize> =
+            self.rpu_nals.iter().map(|rpu| rpu.decoded_index).collect();
+        let permutation = reorder_rpus_by_frames(&decoded_indices, frames)?;
+
+        let mut rpu_nals: Vec<Option<RpuNal>> = self.rpu_nals.drain(..).map(Some).collect();
+        self.rpu_nals = permutation
+            .iter()
+            .filter_map(|&i| rpu_nals[i].take())
+            .collect();
+
+        // Set presentation number to new index
+        self.rpu_nals
+            .iter_mut()
+            .enumerate()
+            .for_each(|(idx, rpu)| rpu.presentation_number = idx);
+
+        println!("Done.");
+
+        Ok(())
+    }
+}
+
+/// Permutation sorting the RPUs by the POC of their matching frame, from decoded order.
+///
+/// `decoded_indices` are the decoded frame numbers of the RPUs, in their current order.
+/// The returned indices are the positions of the RPUs, in presentation order.
+pub fn reorder_rpus_by_frames(decoded_indices: &[usize], frames: &[Frame]) -> Result<Vec<usize>> {
+    let presentation_numbers = decoded_indices
+        .iter()
+        .map(|decoded_index| {
+            let matching_frame = frames
+                .iter()
+                .find(|f| *decoded_index == f.decoded_number as usize);
+
+            match matching_frame {
+                Some(frame) => Ok(frame.presentation_number),
+                None => bail!(
+                    "Missing frame/slices for metadata! Decoded index {}",
+                    decoded_index
+                ),
+            }
+        })
+        .collect::<Result<Vec<u64>>>()?;
+
+    let mut permutation: Vec<usize> = (0..decoded_indices.len()).collect();
+    permutation.sort_by_key(|&i| presentation_numbers[i]);
+
+    Ok(permutation)
 }
@@ -1,19 +1,29 @@
+pub mod container_info;
 pub mod converter;
 pub mod demuxer;
 pub mod editor;
 pub mod exporter;
+pub mod format_converter;
 pub mod generator;
+pub mod progress;
+pub mod rpu_bits;
 pub mod rpu_extractor;
+pub mod rpu_file_iter;
 pub mod rpu_info;
 pub mod rpu_injector;
+pub mod rpu_side_file;
+pub mod rpu_stats;
+pub mod sync_checker;
+pub mod xml_diff;
 
 mod io;
 
 #[cfg(test)]
 mod tests;
 
-use hevc_parser::hevc::{SeiMessage, USER_DATA_REGISTERED_ITU_T_35};
-use indicatif::{ProgressBar, ProgressStyle};
+use hevc_parser::hevc::{
+    SeiMessage, NAL_SEI_PREFIX, NAL_SEI_SUFFIX, USER_DATA_REGISTERED_ITU_T_35,
+};
 use std::convert::TryInto;
 use std::io::{stdout, BufReader, Read, Write};
 use std::{fs::File, io::BufWriter, path::Path};
@@ -23,12 +33,14 @@ use anyhow::{bail, Result};
 use super::bitvec_writer::BitVecWriter;
 
 use dolby_vision::rpu;
+use dolby_vision::utils::WarningSink;
 
 use super::input_format;
 use hevc_parser::{
     hevc::{Frame, NAL_AUD},
     HevcParser, NALUStartCode,
 };
+use progress::ProgressSink;
 use rpu::dovi_rpu::DoviRpu;
 
 const OUT_NAL_HEADER: &[u8] = &[0, 0, 0, 1];
@@ -40,34 +52,78 @@ pub enum Format {
     Matroska,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CliOptions {
     pub mode: Option<u8>,
     pub crop: bool,
     pub discard_el: bool,
     pub drop_hdr10plus: bool,
+    pub drop_sei: bool,
+    pub validate_required_blocks: bool,
+    pub reorder: bool,
+    pub progress_json: bool,
+
+    /// Receives the warnings, such as mismatched lengths when injecting
+    pub warnings: WarningSink,
 }
 
-pub fn initialize_progress_bar(format: &Format, input: &Path) -> Result<ProgressBar> {
-    let pb: ProgressBar;
-    let bytes_count;
+impl Default for CliOptions {
+    /// Defaults of the command line flags
+    fn default() -> Self {
+        Self {
+            mode: None,
+            crop: false,
+            discard_el: false,
+            drop_hdr10plus: false,
+            drop_sei: false,
+            validate_required_blocks: false,
+            reorder: true,
+            progress_json: false,
+            warnings: WarningSink::default(),
+        }
+    }
+}
 
-    if let Format::RawStdin = format {
-        pb = ProgressBar::hidden();
-    } else {
-        let file = File::open(input).expect("No file found");
+impl CliOptions {
+    /// Whether a SEI NAL unit, prefix or suffix, is dropped when writing the output HEVC.
+    /// HDR10+ messages are dropped with `drop_hdr10plus`, the others with `drop_sei`.
+    /// SEI messages that cannot be parsed are not HDR10+.
+    pub fn drop_sei_nal(&self, nal_type: u8, sei_payload: &[u8]) -> bool {
+        if !matches!(nal_type, NAL_SEI_PREFIX | NAL_SEI_SUFFIX) {
+            return false;
+        }
 
-        //Info for indicatif ProgressBar
-        let file_meta = file.metadata()?;
-        bytes_count = file_meta.len() / 100_000_000;
+        if !self.drop_hdr10plus && !self.drop_sei {
+            return false;
+        }
 
-        pb = ProgressBar::new(bytes_count);
-        pb.set_style(
-            ProgressStyle::default_bar().template("[{elapsed_precise}] {bar:60.cyan} {percent}%"),
-        );
+        if is_st2094_40_sei(sei_payload).unwrap_or(false) {
+            self.drop_hdr10plus
+        } else {
+            self.drop_sei
+        }
+    }
+}
+
+/// Progress of a stage in units of 100 MB of the input.
+/// With `progress_json`, JSON lines are written to stderr instead of the progress bar.
+pub fn initialize_progress(
+    format: &Format,
+    input: &Path,
+    stage: &'static str,
+    progress_json: bool,
+) -> Result<ProgressSink> {
+    if let Format::RawStdin = format {
+        return Ok(ProgressSink::hidden());
     }
 
-    Ok(pb)
+    let file = File::open(input).expect("No file found");
+
+    //Info for indicatif ProgressBar
+    let file_meta = file.metadata()?;
+    let bytes_count = file_meta.len() / 100_000_000;
+
+    Ok(ProgressSink::new(stage, bytes_count, progress_json))
 }
 
 impl std::fmt::Display for Format {
@@ -84,6 +140,10 @@ pub fn parse_rpu_file(input: &Path) -> Result<Option<Vec<DoviRpu>>> {
     println!("Parsing RPU file...");
     stdout().flush().ok();
 
+    if rpu_side_file::is_rpu_side_file(input) {
+        return rpu_side_file::parse_rpu_side_file(input).map(Some);
+    }
+
     let rpu_file = File::open(input)?;
     let metadata = rpu_file.metadata()?;
 
@@ -0,0 +1,84 @@
+use std::io::{stderr, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use indicatif::{ProgressBar, ProgressStyle};
+
+/// Progress of a processing stage, either as a progress bar or as JSON lines.
+pub enum ProgressSink {
+    Bar(ProgressBar),
+    JsonLines(JsonProgress),
+}
+
+/// Writes one `{"stage":"...","done":N,"total":M}` line per progress update.
+pub struct JsonProgress {
+    stage: &'static str,
+    done: AtomicU64,
+    total: u64,
+    out: Mutex<Box<dyn Write + Send>>,
+}
+
+impl ProgressSink {
+    /// JSON lines written to stderr with `json`, otherwise a progress bar
+    pub fn new(stage: &'static str, total: u64, json: bool) -> ProgressSink {
+        if json {
+            ProgressSink::json_lines(stage, total, Box::new(stderr()))
+        } else {
+            ProgressSink::bar(total)
+        }
+    }
+
+    pub fn bar(total: u64) -> ProgressSink {
+        let pb = ProgressBar::new(total);
+        pb.set_style(
+            ProgressStyle::default_bar().template("[{elapsed_precise}] {bar:60.cyan} {percent}%"),
+        );
+
+        ProgressSink::Bar(pb)
+    }
+
+    pub fn hidden() -> ProgressSink {
+        ProgressSink::Bar(ProgressBar::hidden())
+    }
+
+    pub fn json_lines(stage: &'static str, total: u64, out: Box<dyn Write + Send>) -> ProgressSink {
+        ProgressSink::JsonLines(JsonProgress {
+            stage,
+            done: AtomicU64::new(0),
+            total,
+            out: Mutex::new(out),
+        })
+    }
+
+    pub fn inc(&self, delta: u64) {
+        match self {
+            ProgressSink::Bar(pb) => pb.inc(delta),
+            ProgressSink::JsonLines(progress) => {
+                let done = progress.done.fetch_add(delta, Ordering::Relaxed) + delta;
+                progress.write_line(done);
+            }
+        }
+    }
+
+    /// The JSON progress always ends with a line where `done` is `total`
+    pub fn finish_and_clear(&self) {
+        match self {
+            ProgressSink::Bar(pb) => pb.finish_and_clear(),
+            ProgressSink::JsonLines(progress) => progress.write_line(progress.total),
+        }
+    }
+}
+
+impl JsonProgress {
+    fn write_line(&self, done: u64) {
+        if let Ok(mut out) = self.out.lock() {
+            writeln!(
+                out,
+                "{{\"stage\":\"{}\",\"done\":{},\"total\":{}}}",
+                self.stage, done, self.total
+            )
+            .ok();
+            out.flush().ok();
+        }
+    }
+}
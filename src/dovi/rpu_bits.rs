@@ -0,0 +1,25 @@
+use anyhow::{ensure, Result};
+use std::path::PathBuf;
+
+use dolby_vision::rpu::bit_layout::{bit_layout, format_bit_layout};
+
+use super::parse_rpu_file;
+
+pub struct RpuBits;
+
+impl RpuBits {
+    pub fn bits(input: PathBuf, frame: usize) -> Result<()> {
+        if let Some(rpus) = parse_rpu_file(&input)? {
+            ensure!(
+                frame < rpus.len(),
+                "bits: invalid frame number (out of range).\nNumber of valid RPUs parsed: {}",
+                rpus.len()
+            );
+
+            println!("{:>6} {:>5}  field = value", "offset", "width");
+            print!("{}", format_bit_layout(&bit_layout(&rpus[frame])?));
+        }
+
+        Ok(())
+    }
+}
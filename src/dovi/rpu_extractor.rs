@@ -1,8 +1,7 @@
 use anyhow::{bail, Result};
-use indicatif::ProgressBar;
 use std::path::PathBuf;
 
-use super::{input_format, io, CliOptions, Format};
+use super::{input_format, io, progress::ProgressSink, CliOptions, Format};
 use io::{DoviReader, DoviWriter};
 
 pub struct RpuExtractor {
@@ -46,7 +45,12 @@ impl RpuExtractor {
     }
 
     fn process_input(&self, options: CliOptions) -> Result<()> {
-        let pb = super::initialize_progress_bar(&self.format, &self.input)?;
+        let pb = super::initialize_progress(
+            &self.format,
+            &self.input,
+            "extract_rpu",
+            options.progress_json,
+        )?;
 
         match self.format {
             Format::Matroska => bail!("unsupported"),
@@ -54,7 +58,7 @@ impl RpuExtractor {
         }
     }
 
-    fn extract_rpu_from_el(&self, pb: Option<&ProgressBar>, options: CliOptions) -> Result<()> {
+    fn extract_rpu_from_el(&self, pb: Option<&ProgressSink>, options: CliOptions) -> Result<()> {
         let mut dovi_reader = DoviReader::new(options);
         let mut dovi_writer = DoviWriter::new(None, None, Some(&self.rpu_out), None);
 
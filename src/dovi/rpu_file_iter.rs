@@ -0,0 +1,130 @@
+use anyhow::{bail, Result};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+const NAL_START_CODE: &[u8] = &[0, 0, 0, 1];
+const CHUNK_SIZE: usize = 100_000;
+
+/// Lazily parses the RPUs of a binary RPU file, one NAL unit at a time.
+///
+/// Only the current NAL unit is kept in memory, unlike `parse_rpu_file`.
+/// The NAL units must be separated by 4 byte start codes, as written by `write_rpu_file`.
+pub struct RpuFileIter<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+
+    /// Position to resume the start code search from
+    search_start: usize,
+    started: bool,
+    done: bool,
+
+    frame: usize,
+}
+
+impl RpuFileIter<BufReader<File>> {
+    pub fn open(input: &Path) -> Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(input)?)))
+    }
+}
+
+impl<R: Read> RpuFileIter<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            buffer: Vec::new(),
+            search_start: 0,
+            started: false,
+            done: false,
+            frame: 0,
+        }
+    }
+
+    /// Reads the next chunk of the file, returns false at the end of the file
+    fn fill_buffer(&mut self) -> Result<bool> {
+        let mut chunk = vec![0; CHUNK_SIZE];
+        let read = self.reader.read(&mut chunk)?;
+
+        self.buffer.extend_from_slice(&chunk[..read]);
+
+        Ok(read > 0)
+    }
+
+    fn find_start_code(&self, from: usize) -> Option<usize> {
+        self.buffer
+            .get(from..)?
+            .windows(NAL_START_CODE.len())
+            .position(|w| w == NAL_START_CODE)
+            .map(|pos| from + pos)
+    }
+
+    /// Next NAL unit, with its start code
+    fn next_nal(&mut self) -> Result<Option<Vec<u8>>> {
+        // Skip the data before the first start code
+        while !self.started {
+            if let Some(pos) = self.find_start_code(0) {
+                self.buffer.drain(..pos);
+                self.started = true;
+            } else if !self.fill_buffer()? {
+                return Ok(None);
+            }
+        }
+
+        loop {
+            let from = self.search_start.max(NAL_START_CODE.len());
+
+            if let Some(pos) = self.find_start_code(from) {
+                self.search_start = 0;
+
+                return Ok(Some(self.buffer.drain(..pos).collect()));
+            }
+
+            // The start code could be split across chunks
+            self.search_start = self.buffer.len().saturating_sub(NAL_START_CODE.len() - 1);
+
+            if !self.fill_buffer()? {
+                if self.buffer.is_empty() {
+                    return Ok(None);
+                }
+
+                return Ok(Some(self.buffer.split_off(0)));
+            }
+        }
+    }
+
+    fn next_rpu(&mut self) -> Result<Option<DoviRpu>> {
+        let nal = match self.next_nal()? {
+            Some(nal) => nal,
+            None => return Ok(None),
+        };
+
+        let frame = self.frame;
+        self.frame += 1;
+
+        match DoviRpu::parse_unspec62_nalu(&nal) {
+            Ok(rpu) => Ok(Some(rpu)),
+            Err(e) => bail!("Error parsing frame {}: {}", frame, e),
+        }
+    }
+}
+
+impl<R: Read> Iterator for RpuFileIter<R> {
+    type Item = Result<DoviRpu>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        let res = self.next_rpu().transpose();
+
+        // Stop after the last RPU or an error
+        if !matches!(res, Some(Ok(_))) {
+            self.done = true;
+        }
+
+        res
+    }
+}
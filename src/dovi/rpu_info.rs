@@ -12,7 +12,7 @@ pub struct RpuInfo {
 }
 
 impl RpuInfo {
-    pub fn info(input: PathBuf, frame: Option<usize>) -> Result<()> {
+    pub fn info(input: PathBuf, frame: Option<usize>, conversions: bool) -> Result<()> {
         let mut info = RpuInfo {
             input,
             frame,
@@ -22,7 +22,12 @@ impl RpuInfo {
         info.rpus = parse_rpu_file(&info.input)?;
 
         if let Some(ref rpus) = info.rpus {
-            if let Some(f) = info.frame {
+            if conversions {
+                let f = info.frame.unwrap_or(0);
+                ensure!(f < rpus.len(), "info: invalid frame number (out of range)");
+
+                print_conversions(&rpus[f]);
+            } else if let Some(f) = info.frame {
                 ensure!(f < rpus.len(), format!("info: invalid frame number (out of range).\nNumber of valid RPUs parsed: {}", rpus.len()));
 
                 let rpu = &rpus[f];
@@ -38,3 +43,17 @@ impl RpuInfo {
         Ok(())
     }
 }
+
+fn print_conversions(rpu: &DoviRpu) {
+    let conversions = rpu.available_conversions();
+
+    println!("Profile {} RPU", rpu.dovi_profile);
+
+    if conversions.is_empty() {
+        println!("No conversion available, only mode 0 can be used");
+    }
+
+    for conversion in conversions {
+        println!("  Mode {}: {}", conversion.mode(), conversion.description());
+    }
+}
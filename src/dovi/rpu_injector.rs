@@ -1,9 +1,8 @@
 use std::fs::File;
 use std::io::{stdout, BufReader, BufWriter, Read, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use anyhow::{bail, ensure, Result};
-use indicatif::{ProgressBar, ProgressStyle};
 use rayon::prelude::*;
 
 use hevc_parser::hevc::*;
@@ -11,7 +10,8 @@ use hevc_parser::HevcParser;
 
 //use crate::dovi::get_aud;
 use super::{
-    input_format, is_st2094_40_sei, parse_rpu_file, CliOptions, DoviRpu, Format, OUT_NAL_HEADER,
+    input_format, parse_rpu_file, progress::ProgressSink, CliOptions, DoviRpu, Format,
+    OUT_NAL_HEADER,
 };
 
 pub struct RpuInjector {
@@ -41,7 +41,8 @@ impl RpuInjector {
             let mut injector = RpuInjector::new(input, rpu_in, output, cli_options)?;
             let mut parser = HevcParser::default();
 
-            injector.process_input(&mut parser, format)?;
+            let progress_json = injector.options.progress_json;
+            RpuInjector::process_input(&injector.input, &mut parser, format, progress_json)?;
             parser.finish();
 
             let frames = parser.ordered_frames();
@@ -53,14 +54,19 @@ impl RpuInjector {
         }
     }
 
-    fn process_input(&self, parser: &mut HevcParser, format: Format) -> Result<()> {
+    pub fn process_input(
+        input: &Path,
+        parser: &mut HevcParser,
+        format: Format,
+        progress_json: bool,
+    ) -> Result<()> {
         println!("Processing input video for frame order info...");
         stdout().flush().ok();
 
-        let pb = super::initialize_progress_bar(&format, &self.input)?;
+        let pb = super::initialize_progress(&format, input, "frame_order", progress_json)?;
 
         //BufReader & BufWriter
-        let file = File::open(&self.input)?;
+        let file = File::open(input)?;
         let mut reader = Box::new(BufReader::with_capacity(100_000, file));
 
         let chunk_size = 100_000;
@@ -147,16 +153,20 @@ impl RpuInjector {
     fn interleave_rpu_nals(&mut self, nals: &[NALUnit], frames: &[Frame]) -> Result<()> {
         if let Some(ref mut rpus) = self.rpus {
             let mismatched_length = if frames.len() != rpus.len() {
-                println!(
+                self.options.warnings.warn(&format!(
                     "\nWarning: mismatched lengths. video {}, RPU {}",
                     frames.len(),
                     rpus.len()
-                );
+                ));
 
                 if rpus.len() < frames.len() {
-                    println!("Metadata will be duplicated at the end to match video length\n");
+                    self.options
+                        .warnings
+                        .warn("Metadata will be duplicated at the end to match video length\n");
                 } else {
-                    println!("Metadata will be skipped at the end to match video length\n");
+                    self.options
+                        .warnings
+                        .warn("Metadata will be skipped at the end to match video length\n");
                 }
 
                 true
@@ -167,10 +177,10 @@ impl RpuInjector {
             println!("Computing frame indices..");
             stdout().flush().ok();
 
-            let pb_indices = ProgressBar::new(frames.len() as u64);
-            pb_indices.set_style(
-                ProgressStyle::default_bar()
-                    .template("[{elapsed_precise}] {bar:60.cyan} {percent}%"),
+            let pb_indices = ProgressSink::new(
+                "frame_indices",
+                frames.len() as u64,
+                self.options.progress_json,
             );
 
             let last_slice_indices: Vec<usize> = frames
@@ -191,7 +201,12 @@ impl RpuInjector {
             println!("Rewriting file with interleaved RPU NALs..");
             stdout().flush().ok();
 
-            let pb = super::initialize_progress_bar(&Format::Raw, &self.input)?;
+            let pb = super::initialize_progress(
+                &Format::Raw,
+                &self.input,
+                "inject_rpu",
+                self.options.progress_json,
+            )?;
             let mut parser = HevcParser::default();
 
             let chunk_size = 100_000;
@@ -252,9 +267,9 @@ impl RpuInjector {
                 let nals = parser.split_nals(&chunk, &offsets, last, true)?;
 
                 for (cur_index, nal) in nals.iter().enumerate() {
-                    if self.options.drop_hdr10plus
-                        && nal.nal_type == NAL_SEI_PREFIX
-                        && is_st2094_40_sei(&chunk[nal.start..nal.end])?
+                    if self
+                        .options
+                        .drop_sei_nal(nal.nal_type, &chunk[nal.start..nal.end])
                     {
                         continue;
                     }
@@ -0,0 +1,82 @@
+use anyhow::{bail, ensure, Result};
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+
+/// Side files use the JSON Lines extension
+pub const SIDE_FILE_EXTENSION: &str = "jsonl";
+
+#[derive(Debug, Deserialize)]
+struct SideFileEntry {
+    frame: usize,
+    rpu: String,
+}
+
+pub fn is_rpu_side_file(input: &Path) -> bool {
+    input
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map_or(false, |ext| ext.eq_ignore_ascii_case(SIDE_FILE_EXTENSION))
+}
+
+/// Parses a line-oriented RPU side file, as dumped by some decoders alongside y4m output.
+///
+/// Every non empty line is a JSON object describing one frame, in presentation order:
+/// `{"frame": 0, "rpu": "7c01190800..."}`
+/// - `frame`: the frame index, starting at 0 and increasing by 1 on every line.
+/// - `rpu`: the hex encoded RPU NAL unit, as accepted by `DoviRpu::parse_unspec62_nalu`.
+///   The start code is optional, and the emulation prevention bytes may be present or not.
+pub fn parse_rpu_side_file(input: &Path) -> Result<Vec<DoviRpu>> {
+    let reader = BufReader::new(File::open(input)?);
+    let mut rpus = Vec::new();
+
+    for (i, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let entry: SideFileEntry = match serde_json::from_str(line) {
+            Ok(entry) => entry,
+            Err(e) => bail!("Invalid side file entry on line {}: {}", i + 1, e),
+        };
+
+        ensure!(
+            entry.frame == rpus.len(),
+            "Side file line {}: expected frame {}, got {}",
+            i + 1,
+            rpus.len(),
+            entry.frame
+        );
+
+        let data = decode_hex(&entry.rpu)?;
+
+        match DoviRpu::parse_unspec62_nalu(&data) {
+            Ok(rpu) => rpus.push(rpu),
+            Err(e) => bail!("Error parsing frame {}: {}", entry.frame, e),
+        }
+    }
+
+    ensure!(!rpus.is_empty(), "No RPU found");
+
+    Ok(rpus)
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>> {
+    ensure!(s.len() % 2 == 0, "Invalid hex RPU data: odd length");
+
+    (0..s.len())
+        .step_by(2)
+        .map(
+            |i| match s.get(i..i + 2).map(|b| u8::from_str_radix(b, 16)) {
+                Some(Ok(byte)) => Ok(byte),
+                _ => bail!("Invalid hex RPU data at offset {}", i),
+            },
+        )
+        .collect()
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::fs::File;
+use std::path::PathBuf;
+
+use dolby_vision::rpu::dovi_rpu::DoviRpu;
+use dolby_vision::rpu::generate::GenerateConfig;
+use dolby_vision::rpu::stats::{
+    brightest_frame, darkest_frame, frame_markers, pq_histogram, trim_change_frames,
+    validate_l1_l6_consistency,
+};
+
+use super::parse_rpu_file;
+
+pub struct RpuStats;
+
+impl RpuStats {
+    pub fn stats(input: PathBuf, histogram: Option<usize>, config: Option<PathBuf>) -> Result<()> {
+        let markers = if let Some(config_path) = config {
+            let config: GenerateConfig = serde_json::from_reader(File::open(config_path)?)?;
+            config.markers
+        } else {
+            Vec::new()
+        };
+
+        if let Some(rpus) = parse_rpu_file(&input)? {
+            for line in Self::report(&rpus, histogram, &markers) {
+                println!("{}", line);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Lines of the statistics report
+    pub fn report(
+        rpus: &[DoviRpu],
+        histogram: Option<usize>,
+        markers: &[(usize, String)],
+    ) -> Vec<String> {
+        let mut lines = vec![format!("Frames: {}", rpus.len())];
+
+        if let Some(brightest) = brightest_frame(rpus) {
+            lines.push(format!("Brightest frame (L1 max PQ): {}", brightest));
+        }
+
+        if let Some(darkest) = darkest_frame(rpus) {
+            lines.push(format!("Darkest frame (L1 max PQ): {}", darkest));
+        }
+
+        let inconsistent_frames = validate_l1_l6_consistency(rpus);
+
+        if let Some(first) = inconsistent_frames.first() {
+            lines.push(format!(
+                "Frames with L1 max PQ above the L6 MaxCLL: {}, first at frame {}",
+                inconsistent_frames.len(),
+                first
+            ));
+        }
+
+        let trim_changes = trim_change_frames(rpus);
+
+        if let Some(first) = trim_changes.first() {
+            lines.push(format!(
+                "Frames with trim changes within a scene: {}, first at frame {}",
+                trim_changes.len(),
+                first
+            ));
+        }
+
+        if let Some(buckets) = histogram {
+            lines.push(String::from("L1 max PQ histogram:"));
+
+            let bucket_size = 4096.0 / buckets as f64;
+
+            for (i, count) in pq_histogram(rpus, buckets).iter().enumerate() {
+                let start = (i as f64 * bucket_size).ceil() as u16;
+                let end = ((i + 1) as f64 * bucket_size).ceil() as u16 - 1;
+
+                lines.push(format!("  {:>4} - {:>4}: {}", start, end, count));
+            }
+        }
+
+        let frame_markers = frame_markers(rpus, markers);
+
+        if !frame_markers.is_empty() {
+            lines.push(String::from("Markers:"));
+
+            for marker in frame_markers {
+                let line = match marker.max_pq {
+                    Some(max_pq) => format!(
+                        "  Frame {} (L1 max PQ {}): {}",
+                        marker.frame, max_pq, marker.note
+                    ),
+                    None => format!("  Frame {}: {}", marker.frame, marker.note),
+                };
+
+                lines.push(line);
+            }
+        }
+
+        lines
+    }
+}
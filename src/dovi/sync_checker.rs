@@ -0,0 +1,69 @@
+use anyhow::{bail, Result};
+use std::path::{Path, PathBuf};
+
+use hevc_parser::HevcParser;
+
+use super::rpu_file_iter::RpuFileIter;
+use super::rpu_injector::RpuInjector;
+use super::rpu_side_file::is_rpu_side_file;
+use super::{input_format, parse_rpu_file, Format};
+
+pub struct SyncChecker;
+
+#[derive(Debug, PartialEq)]
+pub struct SyncReport {
+    pub bl_frames: usize,
+    pub rpus: usize,
+}
+
+impl SyncChecker {
+    pub fn check_sync(input: PathBuf, rpu_in: PathBuf) -> Result<()> {
+        let report = SyncChecker::frame_counts(&input, &rpu_in)?;
+
+        println!("BL frames: {}, RPUs: {}", report.bl_frames, report.rpus);
+
+        if report.is_synced() {
+            println!("Frame counts match.");
+        } else {
+            println!(
+                "Frame counts mismatch, difference (RPUs - BL frames): {}",
+                report.difference()
+            );
+        }
+
+        Ok(())
+    }
+
+    pub fn frame_counts(input: &Path, rpu_in: &Path) -> Result<SyncReport> {
+        let format = input_format(input)?;
+
+        if let Format::Raw = format {
+            let mut parser = HevcParser::default();
+
+            RpuInjector::process_input(input, &mut parser, format, false)?;
+            parser.finish();
+
+            let bl_frames = parser.ordered_frames().len();
+            let rpus = if is_rpu_side_file(rpu_in) {
+                parse_rpu_file(rpu_in)?.map_or(0, |rpus| rpus.len())
+            } else {
+                // Only the count is needed, don't keep the RPUs in memory
+                RpuFileIter::open(rpu_in)?.try_fold(0, |count, rpu| rpu.map(|_| count + 1))?
+            };
+
+            Ok(SyncReport { bl_frames, rpus })
+        } else {
+            bail!("unsupported format")
+        }
+    }
+}
+
+impl SyncReport {
+    pub fn difference(&self) -> i64 {
+        self.rpus as i64 - self.bl_frames as i64
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.bl_frames == self.rpus
+    }
+}
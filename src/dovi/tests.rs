@@ -21,6 +21,11 @@ pub fn _parse_file(input: PathBuf) -> Result<(Vec<u8>, DoviRpu)> {
     Ok((original_data, dovi_rpu))
 }
 
+/// Path in the temp directory, unique to the test process
+pub fn _temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("dovi_tool_{}_{}", std::process::id(), name))
+}
+
 fn _debug(data: &[u8]) -> Result<()> {
     use crate::dovi::OUT_NAL_HEADER;
     use std::fs::OpenOptions;
@@ -58,6 +63,28 @@ fn profile4() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn profile4_conversion() -> Result<()> {
+    let (original_data, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile4.bin"))?;
+    assert_eq!(dovi_rpu.dovi_profile, 4);
+
+    let res = dovi_rpu.convert_with_mode(2);
+    assert!(res.is_err());
+    assert!(res
+        .unwrap_err()
+        .to_string()
+        .starts_with("Profile 4 RPUs cannot be converted (mode 2)"));
+
+    // Mode 0 is still allowed
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile4.bin"))?;
+    dovi_rpu.convert_with_mode(0)?;
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    assert_eq!(&original_data[4..], &parsed_data[2..]);
+
+    Ok(())
+}
+
 #[test]
 fn profile5() -> Result<()> {
     let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile5.bin"))?;
@@ -80,6 +107,67 @@ fn profile8() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn start_code_and_emulation_prevention_forms() -> Result<()> {
+    use dolby_vision::utils::clear_start_code_emulation_prevention_3_byte;
+
+    let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    let expected_data = dovi_rpu.write_rpu()?;
+
+    let cleared_payload = clear_start_code_emulation_prevention_3_byte(&original_data[4..]);
+    assert_ne!(cleared_payload.len(), original_data.len() - 4);
+
+    let mut cleared_4_bytes = vec![0, 0, 0, 1];
+    cleared_4_bytes.extend(&cleared_payload);
+
+    let forms = [
+        original_data.clone(),
+        original_data[1..].to_vec(),
+        cleared_4_bytes.clone(),
+        cleared_4_bytes[1..].to_vec(),
+    ];
+
+    for data in &forms {
+        let parsed_rpu = DoviRpu::parse_unspec62_nalu(data)?;
+        assert_eq!(parsed_rpu.dovi_profile, 8);
+        assert_eq!(parsed_rpu.write_rpu()?, expected_data);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_rpu_nalu_with_emulation_prevention() -> Result<()> {
+    use dolby_vision::utils::clear_start_code_emulation_prevention_3_byte;
+
+    let has_start_code_prefix = |data: &[u8], max: u8| {
+        data.windows(3)
+            .any(|w| w[0] == 0 && w[1] == 0 && w[2] <= max)
+    };
+
+    let (_, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+
+    let payload = dovi_rpu.write_rpu()?;
+    assert!(has_start_code_prefix(&payload, 3));
+
+    let nalu = dovi_rpu.write_rpu_nalu_with_emulation_prevention()?;
+    assert_eq!(&nalu[..6], &[0, 0, 0, 1, 0x7C, 0x01]);
+
+    // Only escaped sequences left
+    assert!(!has_start_code_prefix(&nalu[4..], 2));
+    assert!(nalu[4..].windows(3).any(|w| w == [0, 0, 3]));
+
+    assert_eq!(
+        clear_start_code_emulation_prevention_3_byte(&nalu[6..]),
+        payload
+    );
+
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&nalu)?;
+    assert_eq!(reparsed_rpu.write_rpu()?, payload);
+
+    Ok(())
+}
+
 #[test]
 fn fel() -> Result<()> {
     let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_rpu.bin"))?;
@@ -198,6 +286,31 @@ fn fix_se_write() -> Result<()> {
 
     assert_eq!(&original_data[4..], &parsed_data[2..]);
 
+    // Strict and passthrough writes are identical for a canonical stream
+    let passthrough = dovi_rpu.write_rpu()?;
+    let strict = dovi_rpu.write_rpu_strict()?;
+    assert_eq!(passthrough, strict);
+
+    // Negative coefficients are written with the canonical se(v) encoding
+    let mut modified_rpu = dovi_rpu.clone();
+    modified_rpu.modified = true;
+
+    let mapping = modified_rpu.rpu_data_mapping.as_mut().unwrap();
+    let coef = mapping.poly_coef_int[0]
+        .iter_mut()
+        .flatten()
+        .next()
+        .unwrap();
+    *coef = -1234;
+
+    let strict = modified_rpu.write_rpu_strict()?;
+    let reparsed_rpu = DoviRpu::parse_rpu(&strict)?;
+    let reparsed_mapping = reparsed_rpu.rpu_data_mapping.as_ref().unwrap();
+    assert!(reparsed_mapping.poly_coef_int[0]
+        .iter()
+        .flatten()
+        .any(|c| *c == -1234));
+
     Ok(())
 }
 
@@ -250,6 +363,34 @@ fn sets_offsets_to_zero() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn level5_from_detected_bars() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
+
+    let level5 = ExtMetadataBlockLevel5::from_detected_bars((3840, 2160), 276, 280, 0, 2)?;
+    assert_eq!(level5.get_offsets(), (0, 2, 276, 280));
+
+    assert!(ExtMetadataBlockLevel5::from_detected_bars((1920, 1080), 540, 540, 0, 0).is_err());
+    assert!(ExtMetadataBlockLevel5::from_detected_bars((1920, 1080), 0, 0, 1920, 0).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn level5_max_offset() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::{ExtMetadataBlockLevel5, MAX_13_BIT_VALUE};
+
+    assert_eq!(MAX_13_BIT_VALUE, 8191);
+
+    let level5 = ExtMetadataBlockLevel5::from_offsets(0, 0, MAX_13_BIT_VALUE, 0);
+    assert!(level5.validate().is_ok());
+
+    let level5 = ExtMetadataBlockLevel5::from_offsets(0, 0, MAX_13_BIT_VALUE + 1, 0);
+    assert!(level5.validate().is_err());
+
+    Ok(())
+}
+
 #[test]
 fn profile8_001_end_crc32() -> Result<()> {
     use crate::dovi::parse_rpu_file;
@@ -472,6 +613,439 @@ fn cmv40_full_rpu() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn level_blocks_iter_mut() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let config = GenerateConfig {
+        length: 1,
+        default_metadata_blocks: vec![
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(600)),
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(1000)),
+        ],
+        shots: vec![VideoShot {
+            start: 0,
+            duration: 1,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let mut rpus = config.generate_rpu_list()?;
+    let encoded_rpus = GenerateConfig::encode_rpus(&mut rpus);
+
+    let mut dovi_rpu = DoviRpu::parse_unspec62_nalu(&encoded_rpus[0])?;
+    assert!(!dovi_rpu.modified);
+
+    dovi_rpu.level_blocks_iter_mut(2).for_each(|block| {
+        if let ExtMetadataBlock::Level2(level2) = block {
+            level2.ms_weight += 1;
+        }
+    });
+    assert!(dovi_rpu.modified);
+
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&dovi_rpu.write_hevc_unspec62_nalu()?)?;
+    let vdr_dm_data = reparsed_rpu.vdr_dm_data.as_ref().unwrap();
+
+    assert_eq!(vdr_dm_data.level_blocks_iter(2).count(), 2);
+    for block in vdr_dm_data.level_blocks_iter(2) {
+        if let ExtMetadataBlock::Level2(level2) = block {
+            assert_eq!(level2.ms_weight, 2049);
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn write_validated_required_blocks() -> Result<()> {
+    use bitvec_helpers::bitvec_writer::BitVecWriter;
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::extension_metadata::{CmV29DmData, WithExtMetadataBlocks};
+
+    let mut cmv29 = CmV29DmData::default();
+    cmv29.add_block(ExtMetadataBlock::Level1(
+        ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+    ))?;
+    cmv29.add_block(ExtMetadataBlock::Level5(ExtMetadataBlockLevel5::default()))?;
+
+    let res = cmv29.write_validated(&mut BitVecWriter::new(), true);
+    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "CM v2.9: Missing required L6 metadata block"
+    );
+
+    // Validation disabled
+    assert!(cmv29
+        .write_validated(&mut BitVecWriter::new(), false)
+        .is_ok());
+
+    cmv29.add_block(ExtMetadataBlock::Level6(ExtMetadataBlockLevel6::default()))?;
+    assert!(cmv29
+        .write_validated(&mut BitVecWriter::new(), true)
+        .is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn write_rpu_required_blocks() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let config = GenerateConfig::constant(
+        1,
+        ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+        ExtMetadataBlockLevel5::default(),
+        ExtMetadataBlockLevel6::from_nits(1000, 0.0001, 1000, 400),
+    );
+
+    let mut rpu = config.generate_rpu_list()?.remove(0);
+    rpu.vdr_dm_data.as_mut().unwrap().remove_metadata_level(6);
+
+    let res = rpu.write_hevc_unspec62_nalu();
+    assert!(res.is_err());
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "CM v2.9: Missing required L6 metadata block"
+    );
+
+    // Opt-out for generated RPUs
+    rpu.validate_required_blocks = false;
+    assert!(rpu.write_hevc_unspec62_nalu().is_ok());
+
+    Ok(())
+}
+
+#[test]
+fn dm_data_sanitize() -> Result<()> {
+    use bitvec_helpers::bitvec_writer::BitVecWriter;
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::extension_metadata::{
+        CmV29DmData, CmV40DmData, DmData, WithExtMetadataBlocks,
+    };
+
+    let mut cmv29 = CmV29DmData::default();
+    cmv29.add_block(ExtMetadataBlock::Level1(ExtMetadataBlockLevel1 {
+        min_pq: 100,
+        max_pq: 50,
+        avg_pq: 5000,
+    }))?;
+    cmv29.add_block(ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+        trim_slope: 5000,
+        ms_weight: -5,
+        ..Default::default()
+    }))?;
+    cmv29.add_block(ExtMetadataBlock::Level5(ExtMetadataBlockLevel5 {
+        active_area_left_offset: 9000,
+        ..Default::default()
+    }))?;
+    cmv29.add_block(ExtMetadataBlock::Level6(ExtMetadataBlockLevel6 {
+        max_display_mastering_luminance: 1000,
+        min_display_mastering_luminance: 1,
+        max_content_light_level: 1000,
+        max_frame_average_light_level: 2000,
+    }))?;
+
+    let mut dm_data = DmData::V29(cmv29);
+    assert!(dm_data.write(&mut BitVecWriter::new()).is_err());
+
+    let corrections = dm_data.sanitize();
+    assert_eq!(
+        corrections,
+        vec![
+            "L1: avg_pq 5000 clamped to 4095",
+            "L1: min_pq 100 and max_pq 50 swapped",
+            "L1: avg_pq 4095 clamped to 100",
+            "L2: trim_slope 5000 clamped to 4095",
+            "L2: ms_weight -5 clamped to -1",
+            "L5: active_area_left_offset 9000 clamped to 8191",
+            "L6: max_frame_average_light_level 2000 clamped to 1000",
+        ]
+    );
+
+    assert!(dm_data.write(&mut BitVecWriter::new()).is_ok());
+
+    // Nothing left to correct
+    assert!(dm_data.sanitize().is_empty());
+
+    let mut cmv40 = CmV40DmData::default();
+    cmv40.add_block(ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+        trim_power: 5000,
+        clip_trim: 4096,
+        ..Default::default()
+    }))?;
+    cmv40.add_block(ExtMetadataBlock::Level254(
+        ExtMetadataBlockLevel254::cmv40_default(),
+    ))?;
+
+    let mut dm_data = DmData::V40(cmv40);
+    assert!(dm_data.write(&mut BitVecWriter::new()).is_err());
+
+    let corrections = dm_data.sanitize();
+    assert_eq!(
+        corrections,
+        vec![
+            "L8: trim_power 5000 clamped to 4095",
+            "L8: clip_trim 4096 clamped to 4095",
+        ]
+    );
+
+    assert!(dm_data.write(&mut BitVecWriter::new()).is_ok());
+    assert!(dm_data.sanitize().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn reserved_block_parser() -> Result<()> {
+    use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
+    use dolby_vision::rpu::dovi_rpu::RpuParseOpts;
+    use dolby_vision::rpu::extension_metadata::blocks::reserved::*;
+    use dolby_vision::rpu::extension_metadata::{CmV40DmData, DmData, WithExtMetadataBlocks};
+    use std::any::Any;
+    use std::sync::Arc;
+
+    #[derive(Debug, PartialEq)]
+    struct VendorData {
+        version: u8,
+        value: u16,
+    }
+
+    impl ReservedBlockData for VendorData {
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+    }
+
+    struct VendorParser;
+
+    impl ReservedBlockParser for VendorParser {
+        fn matches(&self, data: &[u8]) -> bool {
+            data.starts_with(b"DV")
+        }
+
+        fn parse(&self, data: &[u8]) -> Result<Arc<dyn ReservedBlockData>> {
+            Ok(Arc::new(VendorData {
+                version: data[2],
+                value: u16::from_be_bytes([data[3], data[4]]),
+            }))
+        }
+    }
+
+    let mut writer = BitVecWriter::new();
+    writer.write_ue(2);
+
+    while !writer.is_aligned() {
+        writer.write(false);
+    }
+
+    // Signed block, and unknown payload for the same level
+    for payload in [&b"DV\x01\x12\x34"[..], &b"XX"[..]].iter() {
+        writer.write_ue(payload.len() as u64);
+        writer.write_n(&200_u8.to_be_bytes(), 8);
+        payload.iter().for_each(|b| writer.write_n(&[*b], 8));
+    }
+
+    let data = writer.as_slice().to_vec();
+
+    // Unregistered reserved levels are rejected
+    let res = DmData::parse::<CmV40DmData>(&mut BitVecReader::new(data.clone()));
+    assert!(res.is_err());
+
+    let mut opts = RpuParseOpts::default();
+    assert!(opts
+        .reserved_block_parsers
+        .register(2, VendorParser)
+        .is_err());
+    opts.reserved_block_parsers.register(200, VendorParser)?;
+
+    let mut reader = BitVecReader::new(data.clone());
+    let cmv40 = DmData::parse_with_opts::<CmV40DmData>(&mut reader, &opts)?.unwrap();
+    let blocks = cmv40.blocks_ref();
+    assert_eq!(blocks.len(), 2);
+
+    if let ExtMetadataBlock::Reserved(block) = &blocks[0] {
+        assert_eq!(block.ext_block_level, 200);
+        assert_eq!(
+            block.parsed_data::<VendorData>(),
+            Some(&VendorData {
+                version: 1,
+                value: 0x1234
+            })
+        );
+    } else {
+        panic!("Expected reserved block");
+    }
+
+    // Raw data only
+    if let ExtMetadataBlock::Reserved(block) = &blocks[1] {
+        assert!(block.parsed.is_none());
+        assert_eq!(block.data.len(), 16);
+    } else {
+        panic!("Expected reserved block");
+    }
+
+    // Blocks are preserved when writing
+    let mut writer = BitVecWriter::new();
+    cmv40.write(&mut writer)?;
+    assert_eq!(writer.as_slice(), &data[..]);
+
+    // Truncated block
+    let mut reader = BitVecReader::new(data[..data.len() - 1].to_vec());
+    assert!(DmData::parse_with_opts::<CmV40DmData>(&mut reader, &opts).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn dm_data_conversions() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::{
+        CmV29DmData, CmV40DmData, DmData, WithExtMetadataBlocks,
+    };
+    use std::convert::TryFrom;
+
+    let dm_data = DmData::from(CmV29DmData::default());
+    assert!(dm_data.as_v29().is_some());
+    assert!(dm_data.as_v40().is_none());
+
+    let cmv29 = CmV29DmData::try_from(dm_data.clone())?;
+    assert_eq!(DmData::from(cmv29).as_v29().unwrap().blocks_ref().len(), 0);
+
+    let res = CmV40DmData::try_from(dm_data);
+    assert_eq!(
+        res.unwrap_err().to_string(),
+        "Expected CM v4.0 DM data, found CM v2.9"
+    );
+
+    let dm_data: DmData = CmV40DmData::new_with_l254().into();
+    assert!(dm_data.as_v29().is_none());
+    assert!(dm_data.as_v40().is_some());
+
+    let cmv40 = CmV40DmData::try_from(dm_data.clone())?;
+    assert_eq!(cmv40.blocks_ref().len(), 1);
+    assert!(CmV29DmData::try_from(dm_data).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn effective_trim_for_nits() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let mut config = GenerateConfig::constant(
+        1,
+        ExtMetadataBlockLevel1::from_stats(0, 3079, 1229),
+        ExtMetadataBlockLevel5::default(),
+        GenerateConfig::default().level6,
+    );
+
+    config.default_metadata_blocks.extend(vec![
+        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+            trim_slope: 2000,
+            ..ExtMetadataBlockLevel2::from_nits(600)
+        }),
+        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+            trim_slope: 2100,
+            ..ExtMetadataBlockLevel2::from_nits(1000)
+        }),
+    ]);
+
+    let rpus = config.generate_rpu_list()?;
+
+    // 600 nits = 2851, 800 nits = 2979, 1000 nits = 3079
+    let trim = rpus[0].effective_trim_for_nits(800).unwrap();
+    assert_eq!(trim.target_max_pq, 2979);
+    assert_eq!(trim.trim_slope, 2056);
+    assert_eq!(trim.trim_offset, 2048);
+
+    // Exact and out of range targets
+    assert_eq!(
+        rpus[0].effective_trim_for_nits(600).unwrap().trim_slope,
+        2000
+    );
+    assert_eq!(
+        rpus[0].effective_trim_for_nits(100).unwrap().trim_slope,
+        2000
+    );
+    assert_eq!(
+        rpus[0].effective_trim_for_nits(4000).unwrap().trim_slope,
+        2100
+    );
+
+    Ok(())
+}
+
+#[test]
+fn ext_metadata_blocks_self_check() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let blocks = vec![
+        ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::default()),
+        ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2828, 1120)),
+        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::default()),
+        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+            ms_weight: -1,
+            ..ExtMetadataBlockLevel2::from_nits(600)
+        }),
+        ExtMetadataBlock::Level3(ExtMetadataBlockLevel3::default()),
+        ExtMetadataBlock::Level3(ExtMetadataBlockLevel3 {
+            min_pq_offset: 2048,
+            max_pq_offset: 1871,
+            avg_pq_offset: 2048,
+        }),
+        ExtMetadataBlock::Level4(ExtMetadataBlockLevel4::default()),
+        ExtMetadataBlock::Level4(ExtMetadataBlockLevel4 {
+            anchor_pq: 1000,
+            anchor_power: 2000,
+        }),
+        ExtMetadataBlock::Level5(ExtMetadataBlockLevel5::default()),
+        ExtMetadataBlock::Level5(ExtMetadataBlockLevel5::from_offsets(0, 0, 276, 276)),
+        ExtMetadataBlock::Level6(ExtMetadataBlockLevel6::default()),
+        ExtMetadataBlock::Level6(GenerateConfig::default().level6),
+        ExtMetadataBlock::Level8(ExtMetadataBlockLevel8::default()),
+        ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+            target_mid_contrast: 2000,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+            clip_trim: 2000,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+            saturation_vector_field2: 100,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+            hue_vector_field5: 100,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level9(ExtMetadataBlockLevel9::default()),
+        ExtMetadataBlock::Level9(ExtMetadataBlockLevel9 {
+            source_primary_index: 255,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level10(ExtMetadataBlockLevel10::default()),
+        ExtMetadataBlock::Level10(ExtMetadataBlockLevel10 {
+            target_display_index: 20,
+            target_primary_index: 255,
+            ..Default::default()
+        }),
+        ExtMetadataBlock::Level11(ExtMetadataBlockLevel11::default()),
+        ExtMetadataBlock::Level11(ExtMetadataBlockLevel11::default_reference_cinema()),
+        ExtMetadataBlock::Level254(ExtMetadataBlockLevel254::default()),
+        ExtMetadataBlock::Level254(ExtMetadataBlockLevel254::cmv40_default()),
+        ExtMetadataBlock::Reserved(ReservedExtMetadataBlock::default()),
+    ];
+
+    for block in &blocks {
+        block.self_check()?;
+    }
+
+    Ok(())
+}
+
 #[test]
 fn profile8_unordered_l8_blocks() -> Result<()> {
     let (original_data, dovi_rpu) =
@@ -529,10 +1103,20 @@ fn generate_default_cmv29() -> Result<()> {
         rpu_out: Some(PathBuf::from("/dev/null")),
         hdr10plus_json: None,
         xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
         canvas_width: None,
         canvas_height: None,
+        strict: false,
+        length: None,
         madvr_file: None,
         use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
     };
 
     let mut generator = Generator::from_command(cmd)?;
@@ -578,10 +1162,20 @@ fn generate_default_cmv40() -> Result<()> {
         rpu_out: Some(PathBuf::from("/dev/null")),
         hdr10plus_json: None,
         xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
         canvas_width: None,
         canvas_height: None,
+        strict: false,
+        length: None,
         madvr_file: None,
         use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
     };
 
     let mut generator = Generator::from_command(cmd)?;
@@ -633,10 +1227,20 @@ fn generate_full() -> Result<()> {
         rpu_out: Some(PathBuf::from("/dev/null")),
         hdr10plus_json: None,
         xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
         canvas_width: None,
         canvas_height: None,
+        strict: false,
+        length: None,
         madvr_file: None,
         use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
     };
 
     let mut generator = Generator::from_command(cmd)?;
@@ -718,10 +1322,20 @@ fn generate_full_hdr10plus() -> Result<()> {
         rpu_out: Some(PathBuf::from("/dev/null")),
         hdr10plus_json: Some(PathBuf::from("./assets/tests/hdr10plus_metadata.json")),
         xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
         canvas_width: None,
         canvas_height: None,
+        strict: false,
+        length: None,
         madvr_file: None,
         use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
     };
 
     let mut generator = Generator::from_command(cmd)?;
@@ -748,7 +1362,7 @@ fn generate_full_hdr10plus() -> Result<()> {
     if let ExtMetadataBlock::Level1(level1) = shot1_vdr_dm_data.get_block(1).unwrap() {
         assert_eq!(level1.min_pq, 0);
         assert_eq!(level1.max_pq, 3337);
-        assert_eq!(level1.avg_pq, 2097);
+        assert_eq!(level1.avg_pq, 2096);
     }
 
     // From shot blocks
@@ -782,7 +1396,7 @@ fn generate_full_hdr10plus() -> Result<()> {
     if let ExtMetadataBlock::Level1(level1) = shot2_vdr_dm_data.get_block(1).unwrap() {
         assert_eq!(level1.min_pq, 0);
         assert_eq!(level1.max_pq, 3401);
-        assert_eq!(level1.avg_pq, 1609);
+        assert_eq!(level1.avg_pq, 1605);
     }
 
     // From shot blocks
@@ -824,7 +1438,7 @@ fn generate_full_hdr10plus() -> Result<()> {
     if let ExtMetadataBlock::Level1(level1) = edit_vdr_dm_data.get_block(1).unwrap() {
         assert_eq!(level1.min_pq, 0);
         assert_eq!(level1.max_pq, 3401);
-        assert_eq!(level1.avg_pq, 1609);
+        assert_eq!(level1.avg_pq, 1605);
     }
 
     // From edit blocks
@@ -854,3 +1468,2561 @@ fn generate_full_hdr10plus() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn generate_from_xml_dir() -> Result<()> {
+    use std::fs;
+
+    let xml_dir = _temp_path("generate_xml_dir");
+    if xml_dir.exists() {
+        fs::remove_dir_all(&xml_dir)?;
+    }
+    fs::create_dir_all(&xml_dir)?;
+
+    fs::copy("./assets/tests/cmv4_0_2.xml", xml_dir.join("reel1.xml"))?;
+    fs::copy("./assets/tests/cmv4_0_2.xml", xml_dir.join("reel2.xml"))?;
+
+    let out_dir = xml_dir.join("out");
+
+    let cmd = |concat: bool, rpu_out: PathBuf| Command::Generate {
+        json_file: None,
+        rpu_out: Some(rpu_out),
+        hdr10plus_json: None,
+        xml: None,
+        xml_dir: Some(xml_dir.clone()),
+        concat,
+        cut_list: None,
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        strict: false,
+        length: None,
+        madvr_file: None,
+        use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
+    };
+
+    // Written in the output directory, not next to the XMLs
+    Generator::from_command(cmd(false, out_dir.clone()))?.generate()?;
+
+    assert!(out_dir.join("reel1.bin").is_file());
+    assert!(out_dir.join("reel2.bin").is_file());
+    assert!(!xml_dir.join("reel1.bin").exists());
+    assert!(!xml_dir.join("reel2.bin").exists());
+
+    let concat_path = xml_dir.join("concat.bin");
+    Generator::from_command(cmd(true, concat_path.clone()))?.generate()?;
+    assert!(concat_path.is_file());
+
+    // Gzipped XMLs are included
+    fs::copy("./assets/tests/cmv2_9.xml.gz", xml_dir.join("reel3.xml.gz"))?;
+
+    Generator::from_command(cmd(false, out_dir.clone()))?.generate()?;
+    assert!(out_dir.join("reel3.bin").is_file());
+
+    // Mixed CM versions can't be concatenated
+    assert!(Generator::from_command(cmd(true, concat_path))?
+        .generate()
+        .is_err());
+
+    fs::remove_dir_all(&xml_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn check_sync_frame_counts() -> Result<()> {
+    use crate::dovi::sync_checker::{SyncChecker, SyncReport};
+
+    let bl_path = PathBuf::from("./assets/tests/sync_bl_3_frames.hevc");
+
+    let report =
+        SyncChecker::frame_counts(&bl_path, &PathBuf::from("./assets/tests/sync_rpu_3.bin"))?;
+    assert_eq!(
+        report,
+        SyncReport {
+            bl_frames: 3,
+            rpus: 3
+        }
+    );
+    assert!(report.is_synced());
+    assert_eq!(report.difference(), 0);
+
+    let report =
+        SyncChecker::frame_counts(&bl_path, &PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert_eq!(
+        report,
+        SyncReport {
+            bl_frames: 3,
+            rpus: 1
+        }
+    );
+    assert!(!report.is_synced());
+    assert_eq!(report.difference(), -2);
+
+    Ok(())
+}
+
+fn _generate_l1_max_pq_rpus(shots_max_pq: &[(usize, u16)]) -> Result<Vec<DoviRpu>> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel1;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let mut config = GenerateConfig::default();
+
+    for (duration, max_pq) in shots_max_pq {
+        config.shots.push(VideoShot {
+            start: config.length,
+            duration: *duration,
+            metadata_blocks: vec![ExtMetadataBlock::Level1(ExtMetadataBlockLevel1 {
+                min_pq: 0,
+                max_pq: *max_pq,
+                avg_pq: max_pq / 2,
+            })],
+            ..Default::default()
+        });
+
+        config.length += duration;
+    }
+
+    config.generate_rpu_list()
+}
+
+#[test]
+fn pq_histogram() -> Result<()> {
+    use dolby_vision::rpu::stats::pq_histogram;
+
+    let rpus = _generate_l1_max_pq_rpus(&[(10, 1000), (5, 2500), (2, 3072), (3, 4095)])?;
+    assert_eq!(rpus.len(), 20);
+
+    assert_eq!(pq_histogram(&rpus, 4), vec![10, 0, 5, 5]);
+    assert_eq!(pq_histogram(&rpus, 1), vec![20]);
+    assert!(pq_histogram(&rpus, 0).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn brightest_darkest_frames() -> Result<()> {
+    use dolby_vision::rpu::stats::{brightest_frame, darkest_frame};
+
+    let rpus = _generate_l1_max_pq_rpus(&[(3, 2500), (2, 3500), (4, 1200), (2, 3500), (1, 1200)])?;
+
+    assert_eq!(brightest_frame(&rpus), Some(3));
+    assert_eq!(darkest_frame(&rpus), Some(5));
+
+    assert_eq!(brightest_frame(&[]), None);
+    assert_eq!(darkest_frame(&[]), None);
+
+    Ok(())
+}
+
+#[test]
+fn extract_rpu_no_reorder() -> Result<()> {
+    use crate::dovi::{rpu_extractor::RpuExtractor, CliOptions};
+    use std::fs;
+
+    let input = PathBuf::from("./assets/tests/rpu_order_3_frames.hevc");
+    let expected = fs::read("./assets/tests/sync_rpu_3.bin")?;
+
+    for &reorder in &[true, false] {
+        let rpu_out = _temp_path(&format!("rpu_reorder_{}.bin", reorder));
+
+        let options = CliOptions {
+            mode: None,
+            crop: false,
+            discard_el: false,
+            drop_hdr10plus: false,
+            drop_sei: false,
+            validate_required_blocks: false,
+            reorder,
+            progress_json: false,
+            ..Default::default()
+        };
+
+        RpuExtractor::extract_rpu(Some(input.clone()), None, Some(rpu_out.clone()), options)?;
+
+        assert_eq!(fs::read(&rpu_out)?, expected);
+        fs::remove_file(&rpu_out)?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn detect_dv_in_mp4() -> Result<()> {
+    use crate::dovi::container_info::{detect_dv_in_mp4, DvContainerInfo};
+
+    let info = detect_dv_in_mp4(&PathBuf::from("./assets/tests/dv_profile8.mp4"))?;
+    assert_eq!(
+        info,
+        DvContainerInfo {
+            version_major: 1,
+            version_minor: 0,
+            profile: 8,
+            level: 6,
+            rpu_present: true,
+            el_present: false,
+            bl_present: true,
+            bl_signal_compatibility_id: 1,
+        }
+    );
+
+    assert!(detect_dv_in_mp4(&PathBuf::from("./assets/tests/profile8.bin")).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn generate_12_bit_bl() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlockLevel1, ExtMetadataBlockLevel5, ExtMetadataBlockLevel6,
+    };
+
+    let mut config = GenerateConfig {
+        bl_bit_depth: 12,
+        ..GenerateConfig::constant(
+            1,
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+            ExtMetadataBlockLevel5::default(),
+            ExtMetadataBlockLevel6::default(),
+        )
+    };
+
+    let rpus = config.generate_rpu_list()?;
+    assert_eq!(rpus[0].header.bl_bit_depth_minus8, 4);
+    assert_eq!(rpus[0].header.el_bit_depth_minus8, 2);
+
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&rpus[0].write_hevc_unspec62_nalu()?)?;
+    assert_eq!(reparsed_rpu.dovi_profile, 8);
+    assert_eq!(reparsed_rpu.header.bl_bit_depth_minus8, 4);
+    assert_eq!(reparsed_rpu.header.el_bit_depth_minus8, 2);
+    assert!(reparsed_rpu
+        .header
+        .pred_pivot_value
+        .iter()
+        .all(|pivots| pivots == &[0, 4095]));
+
+    config.bl_bit_depth = 14;
+    assert!(config.generate_rpu_list().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn generate_estimate_sizes() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let config = GenerateConfig {
+        length: 10,
+        shots: vec![VideoShot {
+            start: 0,
+            duration: 10,
+            metadata_blocks: vec![
+                ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819)),
+                ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(600)),
+                ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+                    target_display_index: 1,
+                    ..Default::default()
+                }),
+                ExtMetadataBlock::Level9(ExtMetadataBlockLevel9::default()),
+                ExtMetadataBlock::Level11(ExtMetadataBlockLevel11::default_reference_cinema()),
+            ],
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let (cmv29_size, cmv40_size) = config.estimate_sizes()?;
+    assert!(cmv29_size > 0);
+    assert!(cmv40_size > cmv29_size);
+
+    // Same size as the written file
+    let rpu_out = _temp_path("estimate_sizes.bin");
+    config.write_rpus(&rpu_out)?;
+
+    assert_eq!(std::fs::metadata(&rpu_out)?.len() as usize, cmv40_size);
+    std::fs::remove_file(&rpu_out)?;
+
+    Ok(())
+}
+
+#[test]
+fn generate_single_frame_shots() -> Result<()> {
+    let rpus = _generate_l1_max_pq_rpus(&[(1, 2081), (1, 2500), (1, 3000), (3, 2081), (1, 2500)])?;
+    assert_eq!(rpus.len(), 7);
+
+    let scene_refresh_flags: Vec<u64> = rpus
+        .iter()
+        .map(|rpu| {
+            let data = rpu.write_hevc_unspec62_nalu().unwrap();
+            let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&data).unwrap();
+
+            reparsed_rpu.vdr_dm_data.unwrap().scene_refresh_flag
+        })
+        .collect();
+
+    assert_eq!(scene_refresh_flags, vec![1, 1, 1, 1, 0, 0, 1]);
+
+    Ok(())
+}
+
+#[test]
+fn snap_targets_to_standard() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+    use dolby_vision::utils::{nearest_standard_target, WarningSink};
+
+    assert_eq!(nearest_standard_target(950), 1000);
+    assert_eq!(nearest_standard_target(600), 600);
+    assert_eq!(nearest_standard_target(50), 100);
+    assert_eq!(nearest_standard_target(3000), 2000);
+    assert_eq!(nearest_standard_target(12000), 10000);
+
+    let (sink, warnings) = WarningSink::capture();
+
+    let mut config = GenerateConfig {
+        default_metadata_blocks: vec![ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(
+            600,
+        ))],
+        shots: vec![VideoShot {
+            metadata_blocks: vec![
+                ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(950)),
+                ExtMetadataBlock::Level10(ExtMetadataBlockLevel10 {
+                    target_display_index: 20,
+                    target_max_pq: ExtMetadataBlockLevel2::from_nits(1800).target_max_pq,
+                    ..Default::default()
+                }),
+            ],
+            ..Default::default()
+        }],
+        warnings: sink,
+        ..Default::default()
+    };
+
+    config.snap_targets();
+
+    let target_max_pqs: Vec<u16> = config
+        .default_metadata_blocks
+        .iter()
+        .chain(config.shots[0].metadata_blocks.iter())
+        .map(|block| match block {
+            ExtMetadataBlock::Level2(b) => b.target_max_pq,
+            ExtMetadataBlock::Level10(b) => b.target_max_pq,
+            _ => unreachable!(),
+        })
+        .collect();
+
+    let expected: Vec<u16> = [600, 1000, 2000]
+        .iter()
+        .map(|&nits| ExtMetadataBlockLevel2::from_nits(nits).target_max_pq)
+        .collect();
+    assert_eq!(target_max_pqs, expected);
+
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec![
+            "L2 target display of 950 nits snapped to 1000 nits",
+            "L10 target display of 1799 nits snapped to 2000 nits",
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn rpu_bit_layout() -> Result<()> {
+    use dolby_vision::rpu::bit_layout::{bit_layout, format_bit_layout};
+
+    let (_, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert_eq!(dovi_rpu.dovi_profile, 8);
+
+    let fields = bit_layout(&dovi_rpu)?;
+
+    // Contiguous fields covering the whole payload
+    assert_eq!(fields[0].offset, 0);
+    assert!(fields
+        .windows(2)
+        .all(|w| w[1].offset == w[0].offset + w[0].width));
+
+    let last = fields.last().unwrap();
+    assert_eq!(last.offset + last.width, dovi_rpu.write_rpu()?.len() * 8);
+
+    let prefix = &fields[0];
+    assert_eq!(prefix.name, "rpu_nal_prefix");
+    assert_eq!((prefix.width, prefix.value), (8, Some(25)));
+
+    let dump = format_bit_layout(&fields);
+    for name in &[
+        "vdr_rpu_profile = 1",
+        "bl_bit_depth_minus8 = 2",
+        "disable_residual_flag = 1",
+        "rpu_data_mapping = -",
+        "scene_refresh_flag",
+        "source_max_pq",
+        "cmv29.num_ext_blocks",
+        "rpu_data_crc32",
+    ] {
+        assert!(dump.contains(name), "missing {}", name);
+    }
+
+    assert!(fields.iter().any(|f| {
+        f.name.starts_with("cmv29.ext_metadata_block[")
+            && f.name.ends_with("].level1_payload")
+            && f.width == 5 * 8
+    }));
+
+    Ok(())
+}
+
+#[test]
+fn generate_from_cut_list() -> Result<()> {
+    use crate::dovi::generator::{generate_shots_from_cut_list, parse_cut_list};
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let cut_list_path = _temp_path("cut_list.txt");
+    std::fs::write(&cut_list_path, "# Scene starts\n0\n24\n48\n")?;
+
+    let cuts = parse_cut_list(&cut_list_path)?;
+    assert_eq!(cuts, vec![0, 24, 48]);
+
+    let shots = VideoShot::from_cut_list(&cuts, 72)?;
+    assert_eq!(shots.len(), 3);
+    assert!(shots.iter().all(|s| s.duration == 24));
+    assert_eq!(
+        shots.iter().map(|s| s.start).collect::<Vec<_>>(),
+        vec![0, 24, 48]
+    );
+
+    // Unsorted, without the implied first cut
+    let shots = VideoShot::from_cut_list(&[48, 24], 72)?;
+    assert_eq!(shots.len(), 3);
+    assert!(VideoShot::from_cut_list(&[72], 72).is_err());
+
+    let mut config = GenerateConfig {
+        length: 72,
+        default_metadata_blocks: vec![ExtMetadataBlock::Level1(
+            ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+        )],
+        ..Default::default()
+    };
+
+    generate_shots_from_cut_list(&cut_list_path, &mut config)?;
+    std::fs::remove_file(&cut_list_path)?;
+
+    let rpus = config.generate_rpu_list()?;
+    assert_eq!(rpus.len(), 72);
+
+    let scene_cuts: Vec<usize> = rpus
+        .iter()
+        .enumerate()
+        .filter(|(_, rpu)| rpu.vdr_dm_data.as_ref().unwrap().scene_refresh_flag == 1)
+        .map(|(i, _)| i)
+        .collect();
+    assert_eq!(scene_cuts, vec![0, 24, 48]);
+
+    std::fs::write(&cut_list_path, "0, 24, abc")?;
+    assert!(parse_cut_list(&cut_list_path).is_err());
+    std::fs::remove_file(&cut_list_path)?;
+
+    Ok(())
+}
+
+#[test]
+fn l1_l6_consistency() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel6;
+    use dolby_vision::rpu::stats::validate_l1_l6_consistency;
+
+    // MaxCLL 1000 nits is PQ 3079
+    let mut rpus = _generate_l1_max_pq_rpus(&[(2, 3000), (2, 3200), (1, 3079)])?;
+    assert!(validate_l1_l6_consistency(&rpus).is_empty());
+
+    for rpu in rpus.iter_mut() {
+        let vdr_dm_data = rpu.vdr_dm_data.as_mut().unwrap();
+
+        vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level6(ExtMetadataBlockLevel6 {
+            max_display_mastering_luminance: 1000,
+            min_display_mastering_luminance: 1,
+            max_content_light_level: 1000,
+            max_frame_average_light_level: 400,
+        }))?;
+    }
+
+    assert_eq!(validate_l1_l6_consistency(&rpus), vec![2, 3]);
+
+    Ok(())
+}
+
+#[test]
+fn generate_sdr_fallback_trim() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::{ShotFrameEdit, VideoShot};
+
+    let sdr_trim_pq = ExtMetadataBlockLevel2::from_nits(100).target_max_pq;
+
+    let mut config = GenerateConfig {
+        length: 6,
+        default_metadata_blocks: vec![
+            ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819)),
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(600)),
+        ],
+        shots: vec![
+            VideoShot {
+                start: 0,
+                duration: 3,
+                // 1000 nits shot
+                metadata_blocks: vec![ExtMetadataBlock::Level1(
+                    ExtMetadataBlockLevel1::from_stats(0, 3079, 1000),
+                )],
+                ..Default::default()
+            },
+            VideoShot {
+                start: 3,
+                duration: 3,
+                frame_edits: vec![ShotFrameEdit {
+                    edit_offset: 1,
+                    metadata_blocks: vec![ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+                        trim_slope: 2100,
+                        ..ExtMetadataBlockLevel2::from_nits(100)
+                    })],
+                }],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let sdr_trim_slopes = |config: &GenerateConfig| -> Result<Vec<Option<u16>>> {
+        Ok(config
+            .generate_rpu_list()?
+            .iter()
+            .map(|rpu| {
+                let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+                vdr_dm_data
+                    .level_blocks_iter(2)
+                    .find_map(|block| match block {
+                        ExtMetadataBlock::Level2(b) if b.target_max_pq == sdr_trim_pq => {
+                            Some(b.trim_slope)
+                        }
+                        _ => None,
+                    })
+            })
+            .collect())
+    };
+
+    assert_eq!(
+        sdr_trim_slopes(&config)?,
+        vec![None, None, None, None, Some(2100), None]
+    );
+
+    // Existing 100 nits trims are kept.
+    // The slope maps the 1000 nits shot to 100 nits, the 100 nits shot is left neutral.
+    config.sdr_fallback_trim = true;
+    assert_eq!(
+        sdr_trim_slopes(&config)?,
+        vec![
+            Some(720),
+            Some(720),
+            Some(720),
+            Some(2048),
+            Some(2100),
+            Some(2048)
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn rpu_side_file_round_trip() -> Result<()> {
+    use crate::dovi::parse_rpu_file;
+    use crate::dovi::rpu_side_file::parse_rpu_side_file;
+
+    let rpus = parse_rpu_file(&PathBuf::from("./assets/tests/sync_rpu_3.bin"))?.unwrap();
+
+    let mut contents = String::new();
+
+    for (frame, rpu) in rpus.iter().enumerate() {
+        let hex: String = rpu
+            .write_hevc_unspec62_nalu()?
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        contents.push_str(&format!("{{\"frame\": {}, \"rpu\": \"{}\"}}\n", frame, hex));
+    }
+
+    assert!(contents.starts_with("{\"frame\": 0, \"rpu\": \"7c0119"));
+
+    let side_file = _temp_path("rpu_side_file.jsonl");
+    std::fs::write(&side_file, &contents)?;
+
+    let parsed = parse_rpu_side_file(&side_file)?;
+    assert_eq!(parsed.len(), rpus.len());
+
+    for (rpu, parsed_rpu) in rpus.iter().zip(parsed.iter()) {
+        assert_eq!(
+            rpu.write_hevc_unspec62_nalu()?,
+            parsed_rpu.write_hevc_unspec62_nalu()?
+        );
+    }
+
+    // Also accepted as an RPU input
+    assert_eq!(parse_rpu_file(&side_file)?.unwrap().len(), 3);
+
+    // Frames must be consecutive
+    let first_line = contents.lines().next().unwrap();
+    std::fs::write(&side_file, format!("{}\n{}\n", first_line, first_line))?;
+    assert!(parse_rpu_side_file(&side_file).is_err());
+
+    std::fs::remove_file(&side_file)?;
+
+    Ok(())
+}
+
+#[test]
+fn level10_preset_target_rejected() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel10;
+    use dolby_vision::rpu::extension_metadata::{CmV40DmData, WithExtMetadataBlocks};
+
+    let preset_block = ExtMetadataBlockLevel10 {
+        target_display_index: 16,
+        ..Default::default()
+    };
+
+    let mut cmv40 = CmV40DmData::default();
+    let err = cmv40
+        .add_block(ExtMetadataBlock::Level10(preset_block.clone()))
+        .unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "L10: target display index 16 is a preset target display, it cannot be redefined"
+    );
+    assert!(cmv40.blocks_ref().is_empty());
+
+    // Same through the generation path
+    let mut rpu = DoviRpu::profile81_config(&GenerateConfig::default())?;
+    let vdr_dm_data = rpu.vdr_dm_data.as_mut().unwrap();
+
+    assert!(vdr_dm_data
+        .replace_metadata_block(ExtMetadataBlock::Level10(preset_block.clone()))
+        .is_err());
+    assert!(vdr_dm_data
+        .add_metadata_block(ExtMetadataBlock::Level10(preset_block))
+        .is_err());
+
+    Ok(())
+}
+
+#[test]
+fn diff_shots_l2_trim_slope() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+    use dolby_vision::rpu::shot_diff::{diff_shots, BlockDiff};
+
+    use crate::dovi::xml_diff::{diff_shots_by_id, ShotDiff};
+
+    let level2 = ExtMetadataBlockLevel2::from_nits(600);
+    let target_max_pq = level2.target_max_pq;
+
+    let old_shot = VideoShot {
+        id: "shot-1".to_string(),
+        duration: 24,
+        metadata_blocks: vec![
+            ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2000, 1000)),
+            ExtMetadataBlock::Level2(level2.clone()),
+        ],
+        ..Default::default()
+    };
+
+    let mut new_shot = old_shot.clone();
+    new_shot.metadata_blocks[1] = ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+        trim_slope: 2100,
+        ..level2
+    });
+
+    let expected = vec![BlockDiff::Changed {
+        key: (2, target_max_pq),
+        field: "trim_slope".to_string(),
+        old: "2048".to_string(),
+        new: "2100".to_string(),
+    }];
+
+    assert!(diff_shots(&old_shot, &old_shot).is_empty());
+    assert_eq!(diff_shots(&old_shot, &new_shot), expected);
+
+    // Matched by UniqueID
+    let added_shot = VideoShot {
+        id: "shot-2".to_string(),
+        ..old_shot.clone()
+    };
+
+    assert_eq!(
+        diff_shots_by_id(&[old_shot], &[new_shot, added_shot]),
+        vec![
+            ("shot-1".to_string(), ShotDiff::Changed(expected)),
+            ("shot-2".to_string(), ShotDiff::Added),
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn effective_l1_with_l3_offsets() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let mut rpu = DoviRpu::profile81_config(&GenerateConfig::default())?;
+    let vdr_dm_data = rpu.vdr_dm_data.as_mut().unwrap();
+
+    let level1 = ExtMetadataBlockLevel1 {
+        min_pq: 5,
+        max_pq: 3000,
+        avg_pq: 1500,
+    };
+    vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level1(level1))?;
+
+    // Unchanged without L3
+    let effective = vdr_dm_data.effective_l1().unwrap();
+    assert_eq!(
+        (effective.min_pq, effective.max_pq, effective.avg_pq),
+        (5, 3000, 1500)
+    );
+
+    // Offsets of 0, -0.125 and +0.25
+    vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level3(ExtMetadataBlockLevel3 {
+        min_pq_offset: 2048,
+        max_pq_offset: 1792,
+        avg_pq_offset: 2560,
+    }))?;
+
+    let effective = vdr_dm_data.effective_l1().unwrap();
+    assert_eq!(
+        (effective.min_pq, effective.max_pq, effective.avg_pq),
+        (5, 2488, 2524)
+    );
+
+    // Clamped to the PQ range
+    vdr_dm_data.replace_metadata_block(ExtMetadataBlock::Level3(ExtMetadataBlockLevel3 {
+        min_pq_offset: 0,
+        max_pq_offset: 4095,
+        avg_pq_offset: 2048,
+    }))?;
+
+    let effective = vdr_dm_data.effective_l1().unwrap();
+    assert_eq!(
+        (effective.min_pq, effective.max_pq, effective.avg_pq),
+        (0, 4095, 1500)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn convert_fel_to_81() -> Result<()> {
+    use crate::dovi::{
+        converter::Converter, parse_rpu_file, rpu_extractor::RpuExtractor, CliOptions,
+    };
+    use hevc_parser::hevc::NAL_UNSPEC63;
+    use std::fs;
+
+    let options = || CliOptions {
+        mode: None,
+        crop: false,
+        discard_el: false,
+        drop_hdr10plus: false,
+        drop_sei: false,
+        validate_required_blocks: false,
+        reorder: true,
+        progress_json: false,
+        ..Default::default()
+    };
+
+    let output = _temp_path("to_81.hevc");
+    let rpu_out = _temp_path("to_81_rpu.bin");
+
+    Converter::convert_to_81(
+        PathBuf::from("./assets/tests/fel_3_frames.hevc"),
+        Some(output.clone()),
+        options(),
+    )?;
+
+    // No EL NAL units left
+    let data = fs::read(&output)?;
+    let nal_types: Vec<u8> = data
+        .windows(4)
+        .filter(|w| w[..3] == [0, 0, 1])
+        .map(|w| (w[3] & 0x7E) >> 1)
+        .collect();
+
+    assert_eq!(nal_types.len(), 2 + 3 * 2);
+    assert!(!nal_types.contains(&NAL_UNSPEC63));
+
+    RpuExtractor::extract_rpu(Some(output.clone()), None, Some(rpu_out.clone()), options())?;
+
+    let rpus = parse_rpu_file(&rpu_out)?.unwrap();
+    assert_eq!(rpus.len(), 3);
+    assert!(rpus.iter().all(|rpu| rpu.dovi_profile == 8));
+
+    let p81_data = fs::read("./assets/tests/fel_to_81.bin")?;
+    assert_eq!(fs::read(&rpu_out)?, p81_data.repeat(3));
+
+    fs::remove_file(&output)?;
+    fs::remove_file(&rpu_out)?;
+
+    Ok(())
+}
+
+#[test]
+fn to_81_positional_args() -> Result<()> {
+    use structopt::StructOpt;
+
+    let cmd = Command::from_iter_safe(vec!["dovi_tool", "to-81", "in.hevc", "out.hevc"])?;
+    match cmd {
+        Command::To81 { input, output } => {
+            assert_eq!(input, PathBuf::from("in.hevc"));
+            assert_eq!(output, Some(PathBuf::from("out.hevc")));
+        }
+        _ => panic!("Expected the to-81 command"),
+    }
+
+    // Output defaults to BL_RPU.hevc
+    let cmd = Command::from_iter_safe(vec!["dovi_tool", "to-81", "in.hevc"])?;
+    assert!(matches!(cmd, Command::To81 { output: None, .. }));
+
+    Ok(())
+}
+
+#[test]
+fn level5_offsets_delta() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
+
+    let letterbox = ExtMetadataBlockLevel5::from_offsets(0, 0, 276, 276);
+    let pillarbox = ExtMetadataBlockLevel5::from_offsets(240, 240, 0, 0);
+
+    assert_eq!(letterbox.get_offsets_signed(), (0, 0, 276, 276));
+    assert_eq!(letterbox.offsets_delta(&pillarbox), (240, 240, -276, -276));
+    assert_eq!(pillarbox.offsets_delta(&letterbox), (-240, -240, 276, 276));
+    assert_eq!(letterbox.offsets_delta(&letterbox), (0, 0, 0, 0));
+
+    Ok(())
+}
+
+#[test]
+fn generate_single_target_display() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let target_max_pq = ExtMetadataBlockLevel2::from_nits(600).target_max_pq;
+
+    let config = GenerateConfig {
+        length: 2,
+        default_metadata_blocks: vec![
+            ExtMetadataBlock::Level1(ExtMetadataBlockLevel1::from_stats(0, 2081, 819)),
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(100)),
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2 {
+                trim_slope: 2100,
+                ..ExtMetadataBlockLevel2::from_nits(600)
+            }),
+            ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(1000)),
+            ExtMetadataBlock::Level3(ExtMetadataBlockLevel3 {
+                min_pq_offset: 2048,
+                max_pq_offset: 2100,
+                avg_pq_offset: 2048,
+            }),
+        ],
+        shots: vec![VideoShot {
+            start: 0,
+            duration: 2,
+            ..Default::default()
+        }],
+        single_target_nits: Some(600),
+        ..Default::default()
+    };
+
+    for rpu in config.generate_rpu_list()? {
+        let vdr_dm_data = rpu.vdr_dm_data.as_ref().unwrap();
+
+        let level2_targets: Vec<(u16, u16)> = vdr_dm_data
+            .level_blocks_iter(2)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level2(b) => Some((b.target_max_pq, b.trim_slope)),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(level2_targets, vec![(target_max_pq, 2100)]);
+
+        assert!(vdr_dm_data.get_block(1).is_some());
+        assert!(vdr_dm_data.get_block(3).is_none());
+        assert!(vdr_dm_data.get_block(5).is_some());
+        assert!(vdr_dm_data.get_block(6).is_some());
+
+        rpu.write_hevc_unspec62_nalu()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn computed_crc32() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let (_, mut rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    assert_eq!(rpu.computed_crc32()?, rpu.rpu_data_crc32);
+
+    let (_, cmv40_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+    assert_eq!(cmv40_rpu.computed_crc32()?, cmv40_rpu.rpu_data_crc32);
+
+    // Modified metadata changes the CRC32
+    let level5 = ExtMetadataBlockLevel5::from_offsets(1, 2, 3, 4);
+    rpu.vdr_dm_data
+        .as_mut()
+        .unwrap()
+        .replace_metadata_block(ExtMetadataBlock::Level5(level5))?;
+    rpu.modified = true;
+
+    assert_ne!(rpu.computed_crc32()?, rpu.rpu_data_crc32);
+
+    Ok(())
+}
+
+#[test]
+fn level9_from_primaries() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let level9 = ExtMetadataBlockLevel9::from_named_gamut(ColorPrimaries::Bt2020);
+    assert_eq!(level9.source_primary_index, 2);
+    assert_eq!(level9.bytes_size(), 1);
+
+    // Rounded BT.2020 coordinates, as found in ICC profiles
+    let level9 = ExtMetadataBlockLevel9::from_primaries(&[
+        0.7079, 0.2920, 0.1702, 0.7968, 0.1310, 0.0460, 0.3127, 0.3290,
+    ]);
+    assert_eq!(level9.source_primary_index, 2);
+
+    let level9 = ExtMetadataBlockLevel9::from_primaries(&ColorPrimaries::Bt709.primaries());
+    assert_eq!(level9.source_primary_index, 1);
+
+    // Real device primaries
+    let level9 = ExtMetadataBlockLevel9::from_primaries(&[
+        0.693, 0.304, 0.208, 0.761, 0.1467, 0.0527, 0.3127, 0.329,
+    ]);
+    assert_eq!(level9.source_primary_index, 9);
+
+    let level9 =
+        ExtMetadataBlockLevel9::from_primaries(&[0.66, 0.33, 0.3, 0.6, 0.15, -0.06, 0.3127, 0.329]);
+    assert_eq!(level9.source_primary_index, 255);
+    assert_eq!(level9.source_primary_red_x, 21626);
+    assert_eq!(level9.source_primary_blue_y, (-1966i16) as u16);
+    assert_eq!(level9.bytes_size(), 17);
+
+    Ok(())
+}
+
+#[test]
+fn rpu_file_iter() -> Result<()> {
+    use crate::dovi::parse_rpu_file;
+    use crate::dovi::rpu_file_iter::RpuFileIter;
+
+    let input = PathBuf::from("./assets/tests/sync_rpu_3.bin");
+    let rpus = parse_rpu_file(&input)?.unwrap();
+
+    let iter_rpus = RpuFileIter::open(&input)?.collect::<Result<Vec<_>>>()?;
+    assert_eq!(iter_rpus.len(), rpus.len());
+
+    // Start codes split across reads
+    struct SmallReads(File);
+
+    impl Read for SmallReads {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let len = buf.len().min(3);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    let iter_rpus =
+        RpuFileIter::new(SmallReads(File::open(&input)?)).collect::<Result<Vec<_>>>()?;
+    assert_eq!(iter_rpus.len(), rpus.len());
+
+    for (rpu, iter_rpu) in rpus.iter().zip(iter_rpus.iter()) {
+        assert_eq!(rpu.write_rpu()?, iter_rpu.write_rpu()?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn level11_reference_mode_validation() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel11;
+    use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+    let reference_cinema = ExtMetadataBlockLevel11::default_reference_cinema();
+    assert!(reference_cinema.validate().is_ok());
+
+    let reference_d93 = ExtMetadataBlockLevel11 {
+        whitepoint: 7,
+        ..ExtMetadataBlockLevel11::default_reference_cinema()
+    };
+    assert!(reference_d93.validate().is_ok());
+
+    // Reference mode without a defined content type, only rejected when generating
+    let unknown_reference = ExtMetadataBlockLevel11 {
+        content_type: 0,
+        ..ExtMetadataBlockLevel11::default_reference_cinema()
+    };
+    assert!(unknown_reference.validate().is_ok());
+    assert_eq!(
+        unknown_reference
+            .validate_reference_mode()
+            .unwrap_err()
+            .to_string(),
+        "L11: reference mode is invalid for content type 0"
+    );
+
+    let reserved_reference = ExtMetadataBlockLevel11 {
+        content_type: 5,
+        ..ExtMetadataBlockLevel11::default_reference_cinema()
+    };
+    assert!(reserved_reference.validate_reference_mode().is_err());
+
+    // Valid without the reference mode
+    let reserved = ExtMetadataBlockLevel11 {
+        reference_mode_flag: false,
+        ..reserved_reference
+    };
+    assert!(reserved.validate_reference_mode().is_ok());
+
+    let mut config = GenerateConfig {
+        cm_version: CmVersion::V40,
+        ..Default::default()
+    };
+    config
+        .default_metadata_blocks
+        .push(ExtMetadataBlock::Level11(unknown_reference));
+    assert_eq!(
+        config.generate_rpu_list().unwrap_err().to_string(),
+        "L11: reference mode is invalid for content type 0"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn available_conversions() -> Result<()> {
+    use dolby_vision::rpu::dovi_rpu::ConversionMode;
+
+    let (_, mut p5_rpu) = _parse_file(PathBuf::from("./assets/tests/profile5.bin"))?;
+    assert_eq!(p5_rpu.available_conversions(), vec![ConversionMode::P5To81]);
+
+    let (_, fel_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    assert_eq!(
+        fel_rpu.available_conversions(),
+        vec![ConversionMode::ToMel, ConversionMode::To81]
+    );
+
+    // Every listed mode converts
+    for conversion in p5_rpu.available_conversions() {
+        p5_rpu.convert_with_mode(conversion.mode())?;
+    }
+
+    assert_eq!(p5_rpu.dovi_profile, 8);
+    assert_eq!(p5_rpu.available_conversions(), vec![ConversionMode::ToMel]);
+
+    Ok(())
+}
+
+#[test]
+fn mmr_mapping_method() -> Result<()> {
+    use dolby_vision::rpu::rpu_data_mapping::MappingMethod;
+
+    let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    assert_eq!(dovi_rpu.mapping_method(), Some(MappingMethod::Mmr));
+
+    // Chroma only
+    let mmr_pivots = dovi_rpu.rpu_data_mapping.as_ref().unwrap().mmr_pivots();
+    assert_eq!(mmr_pivots.len(), 2);
+    assert_eq!(mmr_pivots[0].component, 1);
+    assert_eq!(mmr_pivots[1].component, 2);
+    assert!(mmr_pivots.iter().all(|p| p.pivot_idx == 0 && p.order == 3));
+    assert!(mmr_pivots.iter().all(|p| p.coef_int.len() == 4));
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    assert_eq!(&original_data[4..], &parsed_data[2..]);
+
+    let (_, p81_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert_eq!(p81_rpu.mapping_method(), Some(MappingMethod::Polynomial));
+
+    Ok(())
+}
+
+#[test]
+fn reorder_rpus_by_frames() -> Result<()> {
+    use super::io::reorder_rpus_by_frames;
+    use hevc_parser::hevc::Frame;
+
+    // Decoded order I P B B, presented as I B B P
+    let frames: Vec<Frame> = [(0, 0), (1, 3), (2, 1), (3, 2)]
+        .iter()
+        .map(|&(decoded_number, presentation_number)| Frame {
+            decoded_number,
+            presentation_number,
+            ..Default::default()
+        })
+        .collect();
+
+    assert_eq!(
+        reorder_rpus_by_frames(&[0, 1, 2, 3], &frames)?,
+        vec![0, 2, 3, 1]
+    );
+
+    // RPUs not in decoded order
+    assert_eq!(
+        reorder_rpus_by_frames(&[3, 0, 1, 2], &frames)?,
+        vec![1, 3, 0, 2]
+    );
+
+    // No frame for the RPU
+    assert!(reorder_rpus_by_frames(&[0, 4], &frames).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn strip_el_residual() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+    assert_eq!(dovi_rpu.dovi_profile, 7);
+    assert!(dovi_rpu.has_nlq());
+
+    dovi_rpu.strip_el_residual()?;
+    assert_eq!(dovi_rpu.dovi_profile, 7);
+    assert!(!dovi_rpu.has_nlq());
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&parsed_data)?;
+    assert_eq!(reparsed_rpu.dovi_profile, 7);
+    assert!(!reparsed_rpu.has_nlq());
+
+    // No residual in profile 8.1
+    let (_, mut p81_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert!(!p81_rpu.has_nlq());
+    assert!(p81_rpu.strip_el_residual().is_err());
+
+    Ok(())
+}
+
+#[test]
+fn minimal_hdr() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+    assert!(dovi_rpu
+        .vdr_dm_data
+        .as_ref()
+        .unwrap()
+        .cmv40_metadata
+        .is_some());
+
+    dovi_rpu.minimal_hdr();
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_ref().unwrap();
+    assert!(vdr_dm_data.cmv40_metadata.is_none());
+
+    let mut levels: Vec<u8> = vdr_dm_data
+        .metadata_blocks(1)
+        .unwrap()
+        .iter()
+        .map(|block| block.level())
+        .collect();
+    levels.sort_unstable();
+    assert_eq!(levels, vec![1, 5, 6]);
+
+    let parsed_data = dovi_rpu.write_hevc_unspec62_nalu()?;
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&parsed_data)?;
+    let reparsed_dm_data = reparsed_rpu.vdr_dm_data.as_ref().unwrap();
+
+    assert!(reparsed_dm_data.cmv40_metadata.is_none());
+    assert_eq!(reparsed_dm_data.metadata_blocks(1).unwrap().len(), 3);
+
+    Ok(())
+}
+
+#[test]
+fn trim_power_gamma_conversion() {
+    use dolby_vision::rpu::trims::{gamma_to_trim_power, trim_power_to_gamma};
+
+    assert_eq!(gamma_to_trim_power(0.0), 2048);
+    assert_eq!(gamma_to_trim_power(1.0), 683);
+
+    // Saturated 12 bit value
+    assert_eq!(gamma_to_trim_power(-1.0), 4095);
+    assert_eq!(gamma_to_trim_power(-2.0), 4095);
+
+    assert!(trim_power_to_gamma(2048).abs() < f32::EPSILON);
+    assert!((trim_power_to_gamma(683) - 1.0).abs() < 0.001);
+
+    for &gamma in &[-0.5, -0.25, 0.0, 0.25, 0.5, 1.0] {
+        let trim_power = gamma_to_trim_power(gamma);
+        assert!((trim_power_to_gamma(trim_power) - gamma).abs() < 0.001);
+    }
+}
+
+#[test]
+fn stats_markers() -> Result<()> {
+    use crate::dovi::rpu_stats::RpuStats;
+
+    let rpus = _generate_l1_max_pq_rpus(&[(3, 1000), (2, 3000)])?;
+
+    let markers = vec![
+        (4, String::from("Clipped highlights")),
+        (0, String::from("Flicker")),
+        (10, String::from("After the last frame")),
+    ];
+
+    let lines = RpuStats::report(&rpus, None, &markers);
+    let markers_start = lines.iter().position(|l| l == "Markers:").unwrap();
+
+    assert_eq!(
+        &lines[markers_start + 1..],
+        &[
+            "  Frame 0 (L1 max PQ 1000): Flicker",
+            "  Frame 4 (L1 max PQ 3000): Clipped highlights",
+        ]
+    );
+
+    // No markers section without markers
+    let lines = RpuStats::report(&rpus, None, &[]);
+    assert!(!lines.iter().any(|l| l == "Markers:"));
+
+    Ok(())
+}
+
+#[test]
+fn level2_from_eotf_samples() {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let inputs: Vec<f32> = (0..=20).map(|i| i as f32 / 20.0).collect();
+
+    // Identity response
+    let samples: Vec<(f32, f32)> = inputs.iter().map(|&x| (x, x)).collect();
+    let level2 = ExtMetadataBlockLevel2::from_eotf_samples(600, &samples);
+
+    assert_eq!(level2.target_max_pq, 2851);
+    assert_eq!(level2.trim_slope, 2048);
+    assert_eq!(level2.trim_offset, 2048);
+    assert_eq!(level2.trim_power, 2048);
+
+    // Near identity response
+    let samples: Vec<(f32, f32)> = inputs.iter().map(|&x| (x, x.powf(1.01))).collect();
+    let level2 = ExtMetadataBlockLevel2::from_eotf_samples(600, &samples);
+
+    assert!((level2.trim_slope as i32 - 2048).abs() <= 16);
+    assert!((level2.trim_offset as i32 - 2048).abs() <= 16);
+    assert!((level2.trim_power as i32 - 2048).abs() <= 64);
+
+    // Response following the trim model: slope 1.1, offset -0.05, power 0.9
+    let samples: Vec<(f32, f32)> = inputs[2..19]
+        .iter()
+        .map(|&x| (x, (x * 1.1 - 0.05).powf(0.9)))
+        .collect();
+    let level2 = ExtMetadataBlockLevel2::from_eotf_samples(600, &samples);
+
+    assert!((level2.trim_slope as i32 - 2458).abs() <= 2);
+    assert!((level2.trim_offset as i32 - 1843).abs() <= 2);
+    assert!((level2.trim_power as i32 - 1638).abs() <= 2);
+
+    // Not enough samples, neutral trim
+    let level2 = ExtMetadataBlockLevel2::from_eotf_samples(600, &[(0.5, 0.6)]);
+    assert_eq!(level2.trim_slope, 2048);
+    assert_eq!(level2.trim_power, 2048);
+}
+
+#[test]
+fn block_length_issues() -> Result<()> {
+    use bitvec_helpers::bitvec_reader::BitVecReader;
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::extension_metadata::{DmData, WithExtMetadataBlocks};
+
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+    assert!(dovi_rpu.block_length_issues().is_empty());
+
+    // 3 bytes of data, declared as 4 bytes
+    let mut reader = BitVecReader::new(vec![1, 2, 3]);
+    let mut block = ReservedExtMetadataBlock::parse(3, 200, &mut reader)?;
+
+    if let ExtMetadataBlock::Reserved(ref mut reserved) = block {
+        reserved.ext_block_length = 4;
+    }
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_mut().unwrap();
+    if let Some(DmData::V40(cmv40)) = &mut vdr_dm_data.cmv40_metadata {
+        cmv40.blocks_mut().push(block);
+    }
+
+    assert_eq!(dovi_rpu.block_length_issues(), vec![(200, 4, 3)]);
+
+    Ok(())
+}
+
+#[test]
+fn write_x265_rpu() -> Result<()> {
+    let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+
+    let x265_rpu = dovi_rpu.write_x265_rpu()?;
+
+    // Start code, then rpu_nal_prefix
+    assert_eq!(&x265_rpu[..5], &[0, 0, 0, 1, 25]);
+    assert_eq!(x265_rpu, original_data);
+
+    // Payload without the start code
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu(&x265_rpu[4..])?;
+    assert_eq!(reparsed_rpu.dovi_profile, 7);
+    assert_eq!(reparsed_rpu.write_x265_rpu()?, x265_rpu);
+
+    Ok(())
+}
+
+#[test]
+fn level3_from_level8() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let level1 = ExtMetadataBlockLevel1::from_stats(0, 2081, 819);
+
+    // Lift by 0.1
+    let level8 = ExtMetadataBlockLevel8 {
+        trim_offset: 2458,
+        ..Default::default()
+    };
+    let level3 = ExtMetadataBlockLevel3::from_level1_trim(&level1, &level8);
+    assert_eq!(level3.min_pq_offset, 2253);
+    assert_eq!(level3.max_pq_offset, 2253);
+    assert_eq!(level3.avg_pq_offset, 2253);
+
+    // Power of 0.9
+    let level8 = ExtMetadataBlockLevel8 {
+        trim_power: 1638,
+        ..Default::default()
+    };
+    let level3 = ExtMetadataBlockLevel3::from_level1_trim(&level1, &level8);
+    assert_eq!(level3.min_pq_offset, 2048);
+    assert_eq!(level3.max_pq_offset, 2121);
+    assert_eq!(level3.avg_pq_offset, 2120);
+
+    let neutral_level3 = ExtMetadataBlockLevel3::from_level1_trim(&level1, &Default::default());
+    assert_eq!(neutral_level3.max_pq_offset, 2048);
+
+    // Generated for the shots without L3 metadata
+    let mut config = GenerateConfig {
+        length: 2,
+        level3_from_level8: true,
+        ..Default::default()
+    };
+
+    for &(start, level3_offset) in &[(0, None), (1, Some(2000))] {
+        let mut metadata_blocks = vec![
+            ExtMetadataBlock::Level1(level1.clone()),
+            ExtMetadataBlock::Level8(level8.clone()),
+        ];
+
+        if let Some(offset) = level3_offset {
+            metadata_blocks.push(ExtMetadataBlock::Level3(ExtMetadataBlockLevel3 {
+                min_pq_offset: offset,
+                max_pq_offset: offset,
+                avg_pq_offset: offset,
+            }));
+        }
+
+        config.shots.push(VideoShot {
+            start,
+            duration: 1,
+            metadata_blocks,
+            ..Default::default()
+        });
+    }
+
+    let rpus = config.generate_rpu_list()?;
+
+    let max_pq_offsets: Vec<u16> = rpus
+        .iter()
+        .map(|rpu| match rpu.vdr_dm_data.as_ref().unwrap().get_block(3) {
+            Some(ExtMetadataBlock::Level3(level3)) => level3.max_pq_offset,
+            _ => panic!("No L3 block"),
+        })
+        .collect();
+    assert_eq!(max_pq_offsets, vec![2121, 2000]);
+
+    Ok(())
+}
+
+#[test]
+fn split_dm_versions() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::WithExtMetadataBlocks;
+    use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+    let (_, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+
+    let (cmv29, cmv40) = dovi_rpu.split_dm_versions();
+    let (cmv29, cmv40) = (cmv29.unwrap(), cmv40.unwrap());
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_ref().unwrap();
+    assert_eq!(
+        cmv29.blocks_ref().len(),
+        vdr_dm_data.metadata_blocks(1).unwrap().len()
+    );
+    assert_eq!(
+        cmv40.blocks_ref().len(),
+        vdr_dm_data.metadata_blocks(3).unwrap().len()
+    );
+
+    // CM v2.9 only
+    let config = GenerateConfig {
+        cm_version: CmVersion::V29,
+        ..Default::default()
+    };
+    let cmv29_rpu = DoviRpu::profile81_config(&config)?;
+    let (cmv29, cmv40) = cmv29_rpu.split_dm_versions();
+    assert!(cmv29.is_some());
+    assert!(cmv40.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn convert_format_round_trip() -> Result<()> {
+    use crate::dovi::format_converter::FormatConverter;
+    use std::fs;
+
+    let json_out = _temp_path("convert_format.json");
+    let rpu_out = _temp_path("convert_format.bin");
+
+    for &name in &[
+        "sync_rpu_3",
+        "fel_orig",
+        "cmv40_full_rpu",
+        "data_before_crc32",
+        "eof_rpu",
+    ] {
+        let input = PathBuf::from(format!("./assets/tests/{}.bin", name));
+
+        FormatConverter::convert_format(input.clone(), json_out.clone())?;
+        FormatConverter::convert_format(json_out.clone(), rpu_out.clone())?;
+
+        assert_eq!(fs::read(&rpu_out)?, fs::read(&input)?, "{}", name);
+    }
+
+    // The direction must be known from the extensions
+    assert!(FormatConverter::convert_format(rpu_out.clone(), rpu_out.clone()).is_err());
+    assert!(FormatConverter::convert_format(json_out.clone(), json_out.clone()).is_err());
+
+    fs::remove_file(&json_out)?;
+    fs::remove_file(&rpu_out)?;
+
+    Ok(())
+}
+
+#[test]
+fn profile5_to_81_validation() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile5-02.bin"))?;
+
+    // Not converted yet
+    let issues = dovi_rpu.p81_conversion_issues();
+    assert!(issues.iter().any(|issue| issue.contains("expected 8.1")));
+
+    dovi_rpu.convert_with_mode(3)?;
+    assert!(dovi_rpu.p81_conversion_issues().is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn peak_target_nits() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::{
+        ExtMetadataBlockLevel1, ExtMetadataBlockLevel2,
+    };
+    use dolby_vision::rpu::generate::VideoShot;
+    use dolby_vision::rpu::stats::list_peak_target_nits;
+
+    let level2 = |nits| ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(nits));
+    let level1 = ExtMetadataBlockLevel1::from_stats(0, 3079, 1200);
+
+    let config = GenerateConfig {
+        length: 2,
+        default_metadata_blocks: vec![ExtMetadataBlock::Level1(level1)],
+        shots: vec![
+            VideoShot {
+                start: 0,
+                duration: 1,
+                metadata_blocks: vec![level2(600)],
+                ..Default::default()
+            },
+            VideoShot {
+                start: 1,
+                duration: 1,
+                metadata_blocks: vec![level2(600), level2(1000)],
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let rpus = config.generate_rpu_list()?;
+
+    assert_eq!(rpus[0].peak_target_nits(), 600);
+    assert_eq!(rpus[1].peak_target_nits(), 1000);
+    assert_eq!(list_peak_target_nits(&rpus), 1000);
+
+    assert_eq!(list_peak_target_nits(&[]), 0);
+
+    Ok(())
+}
+
+#[test]
+fn generate_inject_drop_hdr10plus() -> Result<()> {
+    use crate::dovi::{is_st2094_40_sei, parse_rpu_file};
+    use hevc_parser::hevc::{NAL_SEI_PREFIX, NAL_UNSPEC62};
+    use std::fs;
+
+    // NAL units of a stream with 4 bytes start codes, without the start codes
+    fn split_nals(data: &[u8]) -> Vec<&[u8]> {
+        let starts: Vec<usize> = data
+            .windows(4)
+            .enumerate()
+            .filter(|(_, w)| *w == [0, 0, 0, 1])
+            .map(|(i, _)| i + 4)
+            .collect();
+
+        starts
+            .iter()
+            .enumerate()
+            .map(|(i, &start)| {
+                let end = starts.get(i + 1).map_or(data.len(), |next| next - 4);
+                &data[start..end]
+            })
+            .collect()
+    }
+
+    // ST2094-40 SEI message, with a zeroed payload
+    let mut hdr10plus_sei = vec![0x4E, 0x01, 0x04, 0x10];
+    hdr10plus_sei.extend(&[0xB5, 0x00, 0x3C, 0x00, 0x01, 0x04, 0x01]);
+    hdr10plus_sei.extend(&[0x40; 9]);
+    hdr10plus_sei.push(0x80);
+    assert!(is_st2094_40_sei(&hdr10plus_sei)?);
+
+    // Base layer with a HDR10+ SEI before every slice
+    let bl_data = fs::read("./assets/tests/sync_bl_3_frames.hevc")?;
+
+    let mut hdr10plus_bl = Vec::new();
+    for nal in split_nals(&bl_data) {
+        // IDR slices
+        if nal[0] >> 1 == 20 {
+            hdr10plus_bl.extend(&[0, 0, 0, 1]);
+            hdr10plus_bl.extend(&hdr10plus_sei);
+        }
+
+        hdr10plus_bl.extend(&[0, 0, 0, 1]);
+        hdr10plus_bl.extend(nal);
+    }
+
+    let input = _temp_path("hdr10plus_bl.hevc");
+    let rpu_out = _temp_path("hdr10plus_rpu.bin");
+    let output = _temp_path("hdr10plus_injected.hevc");
+    fs::write(&input, &hdr10plus_bl)?;
+
+    let cmd = Command::Generate {
+        json_file: Some(PathBuf::from(
+            "./assets/generator_examples/no_duration.json",
+        )),
+        rpu_out: Some(rpu_out.clone()),
+        hdr10plus_json: Some(PathBuf::from("./assets/tests/hdr10plus_metadata.json")),
+        xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
+        canvas_width: None,
+        canvas_height: None,
+        strict: false,
+        length: None,
+        madvr_file: None,
+        use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: Some(input.clone()),
+        inject_output: Some(output.clone()),
+    };
+
+    let mut generator = Generator::from_command(cmd)?;
+    generator.cli_options.drop_hdr10plus = true;
+    generator.generate()?;
+
+    let generated_rpus = parse_rpu_file(&rpu_out)?.unwrap();
+
+    let output_data = fs::read(&output)?;
+
+    let mut rpu_nals = Vec::new();
+    for nal in split_nals(&output_data) {
+        match nal[0] >> 1 {
+            NAL_SEI_PREFIX => assert!(!is_st2094_40_sei(nal)?),
+            NAL_UNSPEC62 => rpu_nals.push(nal.to_vec()),
+            _ => (),
+        }
+    }
+
+    // One RPU per frame, the first ones of the generated RPUs
+    assert_eq!(rpu_nals.len(), 3);
+    for (nal, rpu) in rpu_nals.iter().zip(generated_rpus.iter()) {
+        assert_eq!(nal, &rpu.write_hevc_unspec62_nalu()?);
+    }
+
+    fs::remove_file(&input)?;
+    fs::remove_file(&rpu_out)?;
+    fs::remove_file(&output)?;
+
+    Ok(())
+}
+
+#[test]
+fn trim_change_frames() -> Result<()> {
+    use crate::dovi::rpu_stats::RpuStats;
+    use dolby_vision::rpu::stats::trim_change_frames;
+
+    let rpu_out = _temp_path("trim_changes.bin");
+
+    let cmd = Command::Generate {
+        json_file: Some(PathBuf::from(
+            "./assets/generator_examples/no_duration.json",
+        )),
+        rpu_out: Some(rpu_out.clone()),
+        hdr10plus_json: Some(PathBuf::from("./assets/tests/hdr10plus_metadata.json")),
+        xml: None,
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
+        canvas_width: None,
+        canvas_height: None,
+        strict: false,
+        length: None,
+        madvr_file: None,
+        use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
+    };
+
+    let mut generator = Generator::from_command(cmd)?;
+    generator.generate()?;
+    std::fs::remove_file(&rpu_out)?;
+
+    let rpus = generator.config.unwrap().generate_rpu_list()?;
+    assert_eq!(rpus.len(), 9);
+
+    // Frame edit of the second shot, at offset 2
+    assert_eq!(trim_change_frames(&rpus), vec![5]);
+
+    let lines = RpuStats::report(&rpus, None, &[]);
+    assert!(lines
+        .iter()
+        .any(|l| l == "Frames with trim changes within a scene: 1, first at frame 5"));
+
+    // No change reported across scene cuts
+    assert!(trim_change_frames(&rpus[..5]).is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn export_l5_csv() -> Result<()> {
+    use crate::dovi::exporter::Exporter;
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
+    use dolby_vision::rpu::generate::VideoShot;
+
+    let mut config = GenerateConfig::default();
+
+    // 2.40:1 then 1.85:1 crops for a 1920x1080 canvas
+    for &(duration, top_bottom) in &[(3, 138), (2, 21)] {
+        config.shots.push(VideoShot {
+            start: config.length,
+            duration,
+            metadata_blocks: vec![ExtMetadataBlock::Level5(
+                ExtMetadataBlockLevel5::from_offsets(0, 0, top_bottom, top_bottom),
+            )],
+            ..Default::default()
+        });
+
+        config.length += duration;
+    }
+
+    let rpus = config.generate_rpu_list()?;
+    assert_eq!(rpus.len(), 5);
+
+    let lines = Exporter::l5_csv_lines(&rpus, None);
+    assert_eq!(lines.len(), 6);
+    assert_eq!(lines[0], "frame,top,bottom,left,right");
+    assert_eq!(lines[1], "0,138,138,0,0");
+    assert_eq!(lines[5], "4,21,21,0,0");
+
+    let lines = Exporter::l5_csv_lines(&rpus, Some((1920, 1080)));
+    assert_eq!(lines[1], "0,0.127778,0.127778,0.000000,0.000000");
+    assert_eq!(lines[5], "4,0.019444,0.019444,0.000000,0.000000");
+
+    Ok(())
+}
+
+#[test]
+fn parse_rpu_payload() -> Result<()> {
+    use dolby_vision::utils::clear_start_code_emulation_prevention_3_byte;
+
+    let names = [
+        "profile8.bin",
+        "fel_orig.bin",
+        "cmv40_full_rpu.bin",
+        "eof_rpu.bin",
+    ];
+
+    for &name in &names {
+        let (original_data, dovi_rpu) = _parse_file(PathBuf::from("./assets/tests").join(name))?;
+
+        // Without the start code and the 0x7C01 NAL header
+        let nalu = dovi_rpu.write_hevc_unspec62_nalu()?;
+        let payload = clear_start_code_emulation_prevention_3_byte(&nalu[2..]);
+
+        let payload_rpu = DoviRpu::parse_rpu_payload(&payload)?;
+        assert_eq!(payload_rpu.dovi_profile, dovi_rpu.dovi_profile);
+        assert_eq!(payload_rpu.rpu_data_crc32, dovi_rpu.rpu_data_crc32);
+        assert_eq!(payload_rpu.write_rpu()?, dovi_rpu.write_rpu()?);
+        assert_eq!(payload_rpu.write_hevc_unspec62_nalu()?, nalu);
+
+        // NAL wrapped data is rejected
+        assert!(DoviRpu::parse_rpu_payload(&original_data).is_err());
+    }
+
+    assert!(DoviRpu::parse_rpu_payload(&[25, 8, 9]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn demux_summary() -> Result<()> {
+    use crate::dovi::demuxer::{DemuxSummary, Demuxer};
+    use crate::dovi::CliOptions;
+
+    let bl_out = _temp_path("demux_bl.hevc");
+    let el_out = _temp_path("demux_el.hevc");
+
+    let options = CliOptions {
+        mode: None,
+        crop: false,
+        discard_el: false,
+        drop_hdr10plus: false,
+        drop_sei: false,
+        validate_required_blocks: false,
+        reorder: false,
+        progress_json: false,
+        ..Default::default()
+    };
+
+    let summary = Demuxer::demux(
+        Some(PathBuf::from("./assets/tests/fel_3_frames.hevc")),
+        None,
+        Some(bl_out.clone()),
+        Some(el_out.clone()),
+        false,
+        options,
+    )?;
+
+    assert_eq!(
+        summary,
+        DemuxSummary {
+            bl_bytes: 89,
+            el_bytes: 63,
+            rpu_count: 3,
+        }
+    );
+    assert!((summary.el_bl_ratio().unwrap() - 63.0 / 89.0).abs() < f64::EPSILON);
+
+    std::fs::remove_file(&bl_out)?;
+    std::fs::remove_file(&el_out)?;
+
+    Ok(())
+}
+
+#[test]
+fn adjust_l2_saturation() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::trims::adjust_l2;
+
+    let mut config = GenerateConfig::constant(
+        2,
+        ExtMetadataBlockLevel1::from_stats(0, 2081, 819),
+        ExtMetadataBlockLevel5::default(),
+        ExtMetadataBlockLevel6::default(),
+    );
+
+    let mut level2_100 = ExtMetadataBlockLevel2::from_nits(100);
+    level2_100.trim_saturation_gain = 4000;
+    level2_100.trim_chroma_weight = 100;
+
+    config.default_metadata_blocks.extend(vec![
+        ExtMetadataBlock::Level2(level2_100),
+        ExtMetadataBlock::Level2(ExtMetadataBlockLevel2::from_nits(600)),
+    ]);
+
+    let mut rpus = config.generate_rpu_list()?;
+    adjust_l2(&mut rpus, 200, -300);
+
+    for rpu in &rpus {
+        assert!(rpu.modified);
+
+        let values: Vec<(u16, u16)> = rpu
+            .vdr_dm_data
+            .as_ref()
+            .unwrap()
+            .level_blocks_iter(2)
+            .filter_map(|block| match block {
+                ExtMetadataBlock::Level2(b) => Some((b.trim_saturation_gain, b.trim_chroma_weight)),
+                _ => None,
+            })
+            .collect();
+
+        // Clamped to the 12 bit range, then offset from the neutral trim
+        assert_eq!(values, vec![(4095, 0), (2248, 1748)]);
+
+        // Still valid when written
+        rpu.write_hevc_unspec62_nalu()?;
+    }
+
+    Ok(())
+}
+
+#[test]
+fn cm_version_consistency() -> Result<()> {
+    use dolby_vision::rpu::stats::check_cm_version_consistency;
+    use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+    let (_, cmv40_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+
+    let mut cmv29_rpu = cmv40_rpu.clone();
+    cmv29_rpu.minimal_hdr();
+
+    let rpus = vec![cmv40_rpu.clone(), cmv40_rpu.clone()];
+    assert_eq!(check_cm_version_consistency(&rpus)?, CmVersion::V40);
+
+    let rpus = vec![cmv29_rpu.clone()];
+    assert_eq!(check_cm_version_consistency(&rpus)?, CmVersion::V29);
+
+    // Bad concat of a CM v4.0 and a CM v2.9 file
+    let rpus = vec![cmv40_rpu.clone(), cmv40_rpu, cmv29_rpu.clone(), cmv29_rpu];
+    let err = check_cm_version_consistency(&rpus).unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Inconsistent CM version: V40 at frame 0, but not for frames [2, 3]"
+    );
+
+    assert!(check_cm_version_consistency(&[]).is_err());
+
+    Ok(())
+}
+
+#[test]
+fn tolerate_misplaced_blocks() -> Result<()> {
+    use bitvec_helpers::bitvec_reader::BitVecReader;
+    use dolby_vision::rpu::dovi_rpu::RpuParseOpts;
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::extension_metadata::{DmData, WithExtMetadataBlocks};
+    use dolby_vision::utils::WarningSink;
+
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/cmv40_full_rpu.bin"))?;
+
+    // L1 block written in the CM v4.0 DM data
+    let mut reader = BitVecReader::new(vec![0, 16, 0, 32, 1]);
+    let block = ReservedExtMetadataBlock::parse(5, 1, &mut reader)?;
+
+    let vdr_dm_data = dovi_rpu.vdr_dm_data.as_mut().unwrap();
+    if let Some(DmData::V40(cmv40)) = &mut vdr_dm_data.cmv40_metadata {
+        cmv40.blocks_mut().push(block);
+        cmv40.update_extension_block_info();
+    }
+
+    dovi_rpu.modified = true;
+    let data = dovi_rpu.write_hevc_unspec62_nalu()?;
+
+    // Strict by default
+    assert!(DoviRpu::parse_unspec62_nalu(&data).is_err());
+
+    let (sink, warnings) = WarningSink::capture();
+    let opts = RpuParseOpts {
+        tolerate_misplaced_blocks: true,
+        warnings: sink,
+        ..Default::default()
+    };
+
+    let reparsed_rpu = DoviRpu::parse_unspec62_nalu_with_opts(&data, &opts)?;
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec!["CM v4.0: Invalid block level 1, kept as a reserved block"]
+    );
+
+    let vdr_dm_data = reparsed_rpu.vdr_dm_data.as_ref().unwrap();
+    let cmv40_blocks = match &vdr_dm_data.cmv40_metadata {
+        Some(DmData::V40(cmv40)) => cmv40.blocks_ref(),
+        _ => panic!("Expected CM v4.0 metadata"),
+    };
+
+    let blocks: Vec<&ExtMetadataBlock> = cmv40_blocks.iter().filter(|b| b.level() == 1).collect();
+    assert_eq!(blocks.len(), 1);
+
+    if let ExtMetadataBlock::Reserved(block) = blocks[0] {
+        assert_eq!(block.ext_block_level, 1);
+        assert_eq!(block.data.as_raw_slice(), &[0, 16, 0, 32, 1]);
+    } else {
+        panic!("Expected reserved block");
+    }
+
+    // Payload kept untouched
+    assert_eq!(reparsed_rpu.write_hevc_unspec62_nalu()?, data);
+
+    Ok(())
+}
+
+#[test]
+fn ms_weight_signedness() -> Result<()> {
+    use bitvec_helpers::{bitvec_reader::BitVecReader, bitvec_writer::BitVecWriter};
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::trims::weight_to_ms_weight;
+
+    assert_eq!(weight_to_ms_weight(0.0), 2048);
+    assert_eq!(weight_to_ms_weight(-1.0), 0);
+    assert_eq!(weight_to_ms_weight(-2.0), -1);
+    assert_eq!(weight_to_ms_weight(2.0), 4095);
+
+    let level2 = ExtMetadataBlockLevel2 {
+        ms_weight: -1,
+        ..ExtMetadataBlockLevel2::from_nits(600)
+    };
+
+    let mut writer = BitVecWriter::new();
+    level2.write(&mut writer)?;
+
+    let mut reader = BitVecReader::new(writer.as_slice().to_vec());
+    if let ExtMetadataBlock::Level2(parsed) = ExtMetadataBlockLevel2::parse(&mut reader) {
+        assert_eq!(parsed, level2);
+    } else {
+        panic!("Expected L2 block");
+    }
+
+    // Out of the 13 bit signed range
+    let invalid_level2 = ExtMetadataBlockLevel2 {
+        ms_weight: -2,
+        ..Default::default()
+    };
+    assert!(invalid_level2.validate().is_err());
+
+    let level8 = ExtMetadataBlockLevel8 {
+        ms_weight: 4095,
+        ..Default::default()
+    };
+
+    let mut writer = BitVecWriter::new();
+    level8.write(&mut writer)?;
+
+    let mut reader = BitVecReader::new(writer.as_slice().to_vec());
+    let length = level8.bytes_size();
+    if let ExtMetadataBlock::Level8(parsed) = ExtMetadataBlockLevel8::parse(length, &mut reader) {
+        assert_eq!(parsed, level8);
+    } else {
+        panic!("Expected L8 block");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn conflicting_level8_target_display() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::extension_metadata::{CmV40DmData, DmData, WithExtMetadataBlocks};
+
+    let level10 = ExtMetadataBlockLevel10 {
+        target_display_index: 20,
+        target_max_pq: 3079,
+        target_min_pq: 0,
+        target_primary_index: 2,
+        ..Default::default()
+    };
+
+    let mut cmv40 = CmV40DmData::new_with_l254();
+    cmv40.add_block(ExtMetadataBlock::Level8(ExtMetadataBlockLevel8 {
+        target_display_index: 20,
+        ..Default::default()
+    }))?;
+    cmv40.add_block(ExtMetadataBlock::Level10(level10.clone()))?;
+
+    // Same target display, defined twice
+    cmv40.add_block(ExtMetadataBlock::Level10(level10.clone()))?;
+    assert!(DmData::V40(cmv40.clone()).validate().is_ok());
+
+    cmv40.add_block(ExtMetadataBlock::Level10(ExtMetadataBlockLevel10 {
+        target_max_pq: 3338,
+        ..level10
+    }))?;
+
+    let err = DmData::V40(cmv40).validate().unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "CM v4.0: L8 target display index 20 is defined by conflicting L10 blocks"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn progress_json_lines() -> Result<()> {
+    use super::io::{DoviReader, DoviWriter};
+    use crate::dovi::progress::ProgressSink;
+    use crate::dovi::{CliOptions, Format};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let lines = |buffer: &SharedBuffer| -> Vec<String> {
+        let data = buffer.0.lock().unwrap();
+        String::from_utf8_lossy(&data)
+            .lines()
+            .map(str::to_string)
+            .collect()
+    };
+
+    let buffer = SharedBuffer::default();
+    let pb = ProgressSink::json_lines("parse", 2, Box::new(buffer.clone()));
+    pb.inc(1);
+    pb.inc(1);
+    pb.finish_and_clear();
+
+    assert_eq!(
+        lines(&buffer),
+        vec![
+            r#"{"stage":"parse","done":1,"total":2}"#,
+            r#"{"stage":"parse","done":2,"total":2}"#,
+            r#"{"stage":"parse","done":2,"total":2}"#,
+        ]
+    );
+
+    // Less than 100 MB, only the final line
+    let rpu_out = _temp_path("progress_json_rpu.bin");
+    let options = CliOptions {
+        mode: None,
+        crop: false,
+        discard_el: false,
+        drop_hdr10plus: false,
+        drop_sei: false,
+        validate_required_blocks: false,
+        reorder: true,
+        progress_json: true,
+        ..Default::default()
+    };
+
+    let buffer = SharedBuffer::default();
+    let pb = ProgressSink::json_lines("extract_rpu", 0, Box::new(buffer.clone()));
+
+    let mut dovi_reader = DoviReader::new(options);
+    let mut dovi_writer = DoviWriter::new(None, None, Some(&rpu_out), None);
+    dovi_reader.read_write_from_io(
+        &Format::Raw,
+        &PathBuf::from("./assets/tests/fel_3_frames.hevc"),
+        Some(&pb),
+        &mut dovi_writer,
+    )?;
+
+    std::fs::remove_file(&rpu_out)?;
+
+    assert_eq!(
+        lines(&buffer),
+        vec![r#"{"stage":"extract_rpu","done":0,"total":0}"#]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn metadata_footprint() -> Result<()> {
+    use dolby_vision::rpu::profiles::metadata_footprint;
+    use dolby_vision::rpu::vdr_dm_data::CmVersion;
+
+    let (_, fel_rpu) = _parse_file(PathBuf::from("./assets/tests/fel_orig.bin"))?;
+
+    let p7 = metadata_footprint(7);
+    assert!(p7.el_mapping);
+    assert_eq!(p7.block_levels, vec![1, 2, 3, 4, 5, 6, 8, 9, 10, 11, 254]);
+
+    let footprint = fel_rpu.footprint();
+    assert_eq!(footprint.profile, 7);
+    assert!(footprint.el_mapping);
+    assert!(footprint.backwards_compatible);
+    assert!(footprint.cm_versions.contains(&CmVersion::V29));
+
+    let levels = &footprint.block_levels;
+    assert!(levels.iter().all(|level| p7.block_levels.contains(level)));
+
+    // Profile 7 to 8.1 drops the EL
+    assert_eq!(p7.diff(&metadata_footprint(8)), vec!["EL mapping removed"]);
+    assert_eq!(
+        metadata_footprint(5).diff(&metadata_footprint(8)),
+        vec!["Backwards compatibility added"]
+    );
+
+    let mut p81_rpu = fel_rpu;
+    p81_rpu.convert_with_mode(2)?;
+    assert!(!p81_rpu.footprint().el_mapping);
+
+    Ok(())
+}
+
+#[test]
+fn clamp_to_display() -> Result<()> {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+    use dolby_vision::rpu::trims::clamp_to_display;
+
+    // Frame brighter than the 600 nits display
+    let mut config = GenerateConfig::constant(
+        1,
+        ExtMetadataBlockLevel1::from_stats(0, 3696, 1500),
+        ExtMetadataBlockLevel5::default(),
+        ExtMetadataBlockLevel6::from_nits(4000, 0.005, 4000, 400),
+    );
+
+    config.default_metadata_blocks.extend(vec![
+        ExtMetadataBlockLevel2::from_nits(100).into(),
+        ExtMetadataBlockLevel2::from_nits(600).into(),
+        ExtMetadataBlockLevel2::from_nits(1000).into(),
+        ExtMetadataBlockLevel8 {
+            target_display_index: 20,
+            ..Default::default()
+        }
+        .into(),
+        ExtMetadataBlockLevel10 {
+            target_display_index: 20,
+            target_max_pq: ExtMetadataBlockLevel2::from_nits(1000).target_max_pq,
+            target_primary_index: 2,
+            ..Default::default()
+        }
+        .into(),
+    ]);
+
+    let mut rpus = config.generate_rpu_list()?;
+    clamp_to_display(&mut rpus, 600);
+
+    let peak_pq = ExtMetadataBlockLevel2::from_nits(600).target_max_pq;
+    let vdr_dm_data = rpus[0].vdr_dm_data.as_ref().unwrap();
+
+    if let Some(ExtMetadataBlock::Level1(level1)) = vdr_dm_data.get_block(1) {
+        assert_eq!(level1.max_pq, peak_pq);
+        assert_eq!(level1.avg_pq, 1500);
+    } else {
+        panic!("No L1 block");
+    }
+
+    if let Some(ExtMetadataBlock::Level6(level6)) = vdr_dm_data.get_block(6) {
+        assert_eq!(level6.max_content_light_level, 600);
+        assert_eq!(level6.max_frame_average_light_level, 400);
+        assert_eq!(level6.max_display_mastering_luminance, 4000);
+    } else {
+        panic!("No L6 block");
+    }
+
+    // Trims up to the display peak brightness only
+    let level2_targets: Vec<u16> = vdr_dm_data
+        .level_blocks_iter(2)
+        .filter_map(|block| match block {
+            ExtMetadataBlock::Level2(b) => Some(b.target_max_pq),
+            _ => None,
+        })
+        .collect();
+    let min_target = ExtMetadataBlockLevel2::from_nits(100).target_max_pq;
+    assert_eq!(level2_targets, vec![min_target, peak_pq]);
+
+    assert_eq!(vdr_dm_data.level_blocks_iter(8).count(), 0);
+    assert_eq!(vdr_dm_data.level_blocks_iter(10).count(), 0);
+
+    // Still valid when written
+    rpus[0].write_hevc_unspec62_nalu()?;
+
+    Ok(())
+}
+
+#[test]
+fn ext_metadata_block_from() {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let blocks: Vec<ExtMetadataBlock> = vec![
+        ExtMetadataBlockLevel1::from_stats(0, 2081, 1000).into(),
+        ExtMetadataBlockLevel2::from_nits(600).into(),
+        ExtMetadataBlockLevel5::default().into(),
+        ExtMetadataBlockLevel6::default().into(),
+        ExtMetadataBlockLevel9::default().into(),
+        ExtMetadataBlockLevel11::default_reference_cinema().into(),
+        ExtMetadataBlockLevel254::cmv40_default().into(),
+    ];
+
+    let levels: Vec<u8> = blocks.iter().map(|b| b.level()).collect();
+    assert_eq!(levels, vec![1, 2, 5, 6, 9, 11, 254]);
+
+    if let ExtMetadataBlock::Level2(level2) = &blocks[1] {
+        assert_eq!(
+            level2.target_max_pq,
+            ExtMetadataBlockLevel2::from_nits(600).target_max_pq
+        );
+    } else {
+        panic!("Expected a L2 block");
+    }
+}
+
+#[test]
+fn level5_symmetrize() {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
+
+    assert!(ExtMetadataBlockLevel5::from_offsets(240, 240, 276, 276).is_symmetric());
+
+    // Swapped offsets for a centered 2.39:1 letterbox
+    let mut level5 = ExtMetadataBlockLevel5::from_offsets(0, 0, 0, 552);
+    assert!(!level5.is_symmetric());
+
+    level5.symmetrize();
+    assert!(level5.is_symmetric());
+    assert_eq!(level5.get_offsets(), (0, 0, 276, 276));
+
+    // Odd total, the active area size is kept
+    let mut level5 = ExtMetadataBlockLevel5::from_offsets(3, 0, 270, 281);
+    level5.symmetrize();
+    assert_eq!(level5.get_offsets(), (1, 2, 275, 276));
+}
+
+#[test]
+fn level5_active_rect() {
+    use dolby_vision::rpu::extension_metadata::blocks::ExtMetadataBlockLevel5;
+
+    let resolution = (3840, 2160);
+
+    let level5 = ExtMetadataBlockLevel5::from_offsets(0, 0, 276, 276);
+    assert_eq!(level5.active_rect(resolution), (0, 276, 3840, 1608));
+
+    let level5 = ExtMetadataBlockLevel5::from_offsets(480, 480, 0, 0);
+    assert_eq!(level5.active_rect(resolution), (480, 0, 2880, 2160));
+
+    assert_eq!(
+        ExtMetadataBlockLevel5::default().active_rect(resolution),
+        (0, 0, 3840, 2160)
+    );
+
+    // Offsets larger than the frame
+    let level5 = ExtMetadataBlockLevel5::from_offsets(4000, 0, 1080, 1080);
+    assert_eq!(level5.active_rect(resolution), (3840, 1080, 0, 0));
+}
+
+#[test]
+fn level9_real_device_index() {
+    use dolby_vision::rpu::extension_metadata::blocks::*;
+
+    let real_device = [0.7042, 0.294, 0.2271, 0.725, 0.1416, 0.0516, 0.3127, 0.329];
+    assert_eq!(ColorPrimaries::real_device_index(&real_device), Some(13));
+
+    let level9 = ExtMetadataBlockLevel9::from_primaries(&real_device);
+    assert_eq!(level9.source_primary_index, 13);
+
+    // Also a real device, but signaled as the DCI-P3 D65 color space
+    let p3_d65 = ColorPrimaries::DciP3D65.primaries();
+    assert_eq!(ColorPrimaries::real_device_index(&p3_d65), Some(12));
+    let level9 = ExtMetadataBlockLevel9::from_primaries(&p3_d65);
+    assert_eq!(level9.source_primary_index, 0);
+
+    let bt709 = ColorPrimaries::Bt709.primaries();
+    assert_eq!(ColorPrimaries::real_device_index(&bt709), None);
+}
+
+#[test]
+fn convert_drop_sei() -> Result<()> {
+    use crate::dovi::{converter::Converter, is_st2094_40_sei, CliOptions};
+    use std::fs;
+
+    // Mastering display colour volume SEI message, with a filler payload
+    let mut mdcv_sei = vec![0x4E, 0x01, 0x89, 0x18];
+    mdcv_sei.extend(&[0x40; 24]);
+    mdcv_sei.push(0x80);
+    assert!(!is_st2094_40_sei(&mdcv_sei)?);
+
+    // ST2094-40 SEI message, with a zeroed payload
+    let mut hdr10plus_sei = vec![0x4E, 0x01, 0x04, 0x10];
+    hdr10plus_sei.extend(&[0xB5, 0x00, 0x3C, 0x00, 0x01, 0x04, 0x01]);
+    hdr10plus_sei.extend(&[0x40; 9]);
+    hdr10plus_sei.push(0x80);
+
+    // User data unregistered SEI message, with a payload size larger than the NAL unit
+    let malformed_sei = vec![0x4E, 0x01, 0x05, 0x40, 0x40, 0x80];
+    assert!(!is_st2094_40_sei(&malformed_sei).unwrap_or(false));
+
+    // Decoded picture hash suffix SEI message, with a filler payload
+    let mut hash_sei = vec![0x50, 0x01, 0x84, 0x11];
+    hash_sei.extend(&[0x40; 17]);
+    hash_sei.push(0x80);
+
+    // Base layer with the prefix SEI messages before the first IDR slice,
+    // and the suffix SEI message after it
+    let bl_data = fs::read("./assets/tests/sync_bl_3_frames.hevc")?;
+    let nal_start = |from: usize, nal_type: Option<u8>| {
+        bl_data[from..]
+            .windows(5)
+            .position(|w| w[..4] == [0, 0, 0, 1] && nal_type.map_or(true, |t| w[4] >> 1 == t))
+            .map(|pos| from + pos)
+            .unwrap()
+    };
+    let first_slice = nal_start(0, Some(20));
+    let after_first_slice = nal_start(first_slice + 4, None);
+
+    let mut input_data = bl_data[..first_slice].to_vec();
+    for sei in &[&mdcv_sei, &malformed_sei, &hdr10plus_sei] {
+        input_data.extend(&[0, 0, 0, 1]);
+        input_data.extend(sei.iter());
+    }
+    input_data.extend(&bl_data[first_slice..after_first_slice]);
+    input_data.extend(&[0, 0, 0, 1]);
+    input_data.extend(&hash_sei);
+    input_data.extend(&bl_data[after_first_slice..]);
+
+    let input = _temp_path("sei_bl.hevc");
+    let output = _temp_path("sei_converted.hevc");
+    fs::write(&input, &input_data)?;
+
+    // Payload types of the SEI messages in the output
+    let convert = |drop_sei: bool| -> Result<Vec<u8>> {
+        let options = CliOptions {
+            mode: None,
+            crop: false,
+            discard_el: false,
+            drop_hdr10plus: false,
+            drop_sei,
+            validate_required_blocks: false,
+            reorder: true,
+            progress_json: false,
+            ..Default::default()
+        };
+
+        Converter::convert(Some(input.clone()), None, Some(output.clone()), options)?;
+
+        let data = fs::read(&output)?;
+        let payload_types = data
+            .windows(6)
+            .filter(|w| w[..3] == [0, 0, 1] && (w[3] == 0x4E || w[3] == 0x50) && w[4] == 0x01)
+            .map(|w| w[5])
+            .collect();
+
+        Ok(payload_types)
+    };
+
+    // Preserved by default
+    assert_eq!(convert(false)?, vec![0x89, 0x05, 0x04, 0x84]);
+
+    // Only the HDR10+ SEI message is left
+    assert_eq!(convert(true)?, vec![0x04]);
+
+    fs::remove_file(&input)?;
+    fs::remove_file(&output)?;
+
+    Ok(())
+}
+
+#[test]
+fn capture_duplicate_rpu_warning() -> Result<()> {
+    use crate::dovi::{rpu_injector::RpuInjector, CliOptions};
+    use dolby_vision::utils::WarningSink;
+    use std::fs;
+
+    let output = _temp_path("capture_duplicate_rpu.hevc");
+    let (sink, warnings) = WarningSink::capture();
+
+    let options = CliOptions {
+        mode: None,
+        crop: false,
+        discard_el: false,
+        drop_hdr10plus: false,
+        drop_sei: false,
+        validate_required_blocks: false,
+        reorder: false,
+        progress_json: false,
+        warnings: sink,
+    };
+
+    // Single RPU for 3 frames
+    RpuInjector::inject_rpu(
+        PathBuf::from("./assets/tests/sync_bl_3_frames.hevc"),
+        PathBuf::from("./assets/tests/profile8.bin"),
+        Some(output.clone()),
+        options,
+    )?;
+    fs::remove_file(&output)?;
+
+    assert_eq!(
+        *warnings.lock().unwrap(),
+        vec![
+            "\nWarning: mismatched lengths. video 3, RPU 1",
+            "Metadata will be duplicated at the end to match video length\n"
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn generate_xml_strict_and_length() -> Result<()> {
+    use std::fs;
+
+    let xml_path = _temp_path("generate_xml_strict.xml");
+
+    // Duplicate shot ID, and last shot without Duration
+    let xml = fs::read_to_string("./assets/tests/cmv4_0_2.xml")?
+        .replacen(
+            "06dbea53-d78e-4031-8a5b-e9a87d6fc2c9",
+            "7b4b3d42-81c8-4767-aa38-f08a6f35994b",
+            1,
+        )
+        .replacen("<Duration>40</Duration>", "", 1);
+    fs::write(&xml_path, xml)?;
+
+    let cmd = |strict: bool| Command::Generate {
+        json_file: None,
+        rpu_out: Some(PathBuf::from("/dev/null")),
+        hdr10plus_json: None,
+        xml: Some(xml_path.clone()),
+        xml_dir: None,
+        concat: false,
+        cut_list: None,
+        canvas_width: Some(3840),
+        canvas_height: Some(2160),
+        strict,
+        length: Some(259),
+        madvr_file: None,
+        use_custom_targets: false,
+        snap_targets: false,
+        sdr_fallback_trim: false,
+        single_target_nits: None,
+        inject_bl: None,
+        inject_output: None,
+    };
+
+    let mut generator = Generator::from_command(cmd(false))?;
+    let res = generator.generate();
+
+    let strict_res = Generator::from_command(cmd(true))?.generate();
+    fs::remove_file(&xml_path)?;
+
+    res?;
+    assert_eq!(generator.config.unwrap().length, 259);
+    assert!(strict_res.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn nonzero_vdr_rpu_level() -> Result<()> {
+    let (_, mut dovi_rpu) = _parse_file(PathBuf::from("./assets/tests/profile8.bin"))?;
+    assert_eq!(dovi_rpu.header.vdr_rpu_level, 0);
+
+    let mut data = dovi_rpu.write_rpu()?;
+
+    // Rejected when writing
+    dovi_rpu.header.vdr_rpu_level = 1;
+    let err = dovi_rpu.write_rpu().unwrap_err();
+    assert_eq!(err.to_string(), "vdr_rpu_level should be 0");
+
+    // vdr_rpu_level is 4 bits at offset 29, after the prefix, rpu_type, rpu_format and profile.
+    // The header is validated before the CRC32 is checked.
+    assert_eq!(data[4] & 0x80, 0);
+    data[4] |= 0x80;
+
+    let err = DoviRpu::parse_rpu(&data).unwrap_err();
+    assert_eq!(err.to_string(), "vdr_rpu_level should be 0");
+
+    Ok(())
+}
+
+#[test]
+fn generate_xml_dir_args() {
+    use structopt::StructOpt;
+
+    let parse = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.insert(0, "dovi_tool");
+        args.insert(1, "generate");
+
+        Command::from_iter_safe(args)
+    };
+
+    assert!(parse(&["--xml-dir", "xmls", "--concat"]).is_ok());
+    assert!(parse(&["--concat"]).is_err());
+    assert!(parse(&["--xml-dir", "xmls", "-j", "config.json"]).is_err());
+    assert!(parse(&["--xml-dir", "xmls", "--xml", "file.xml"]).is_err());
+    assert!(parse(&["--xml-dir", "xmls", "--length", "100"]).is_err());
+
+    assert!(parse(&[
+        "-j",
+        "config.json",
+        "--inject-bl",
+        "bl.hevc",
+        "--inject-output",
+        "out.hevc"
+    ])
+    .is_ok());
+    assert!(parse(&["-j", "config.json", "--inject-output", "out.hevc"]).is_err());
+    assert!(parse(&["--xml-dir", "xmls", "--inject-bl", "bl.hevc"]).is_err());
+}
+
+#[test]
+fn generate_cut_list_args() {
+    use structopt::StructOpt;
+
+    let parse = |args: &[&str]| {
+        let mut args = args.to_vec();
+        args.insert(0, "dovi_tool");
+        args.insert(1, "generate");
+
+        Command::from_iter_safe(args)
+    };
+
+    assert!(parse(&["-j", "config.json", "--cut-list", "cuts.txt"]).is_ok());
+    assert!(parse(&["--cut-list", "cuts.txt"]).is_err());
+    assert!(parse(&[
+        "-j",
+        "config.json",
+        "--cut-list",
+        "cuts.txt",
+        "--hdr10plus-json",
+        "hdr10plus.json"
+    ])
+    .is_err());
+    assert!(parse(&[
+        "-j",
+        "config.json",
+        "--cut-list",
+        "cuts.txt",
+        "--madvr-file",
+        "measurements.bin"
+    ])
+    .is_err());
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+
+use dolby_vision::rpu::generate::VideoShot;
+use dolby_vision::rpu::shot_diff::{diff_shots, BlockDiff};
+use dolby_vision::xml::{CmXmlParser, XmlParserOpts};
+
+pub struct XmlDiff;
+
+/// Difference between the shots with the same UniqueID
+#[derive(Debug, PartialEq)]
+pub enum ShotDiff {
+    /// Shot only present in the original XML
+    Removed,
+
+    /// Shot only present in the modified XML
+    Added,
+
+    Changed(Vec<BlockDiff>),
+}
+
+impl XmlDiff {
+    pub fn diff(
+        old: PathBuf,
+        new: PathBuf,
+        canvas_width: Option<u16>,
+        canvas_height: Option<u16>,
+    ) -> Result<()> {
+        let old_shots = parse_xml_shots(&old, canvas_width, canvas_height)?;
+        let new_shots = parse_xml_shots(&new, canvas_width, canvas_height)?;
+
+        let diffs = diff_shots_by_id(&old_shots, &new_shots);
+
+        if diffs.is_empty() {
+            println!("No metadata differences.");
+        }
+
+        for (id, diff) in diffs {
+            match diff {
+                ShotDiff::Removed => println!("Shot {}: removed", id),
+                ShotDiff::Added => println!("Shot {}: added", id),
+                ShotDiff::Changed(block_diffs) => {
+                    println!("Shot {}:", id);
+
+                    for block_diff in block_diffs {
+                        println!("  {}", format_block_diff(&block_diff));
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_xml_shots(
+    path: &Path,
+    canvas_width: Option<u16>,
+    canvas_height: Option<u16>,
+) -> Result<Vec<VideoShot>> {
+    let opts = XmlParserOpts::default().with_canvas(canvas_width, canvas_height);
+
+    Ok(CmXmlParser::parse_file(path, opts)?.config.shots)
+}
+
+/// Compares the shots with the same UniqueID.
+/// Unchanged shots are omitted, in the order of the original shots then the added ones.
+pub fn diff_shots_by_id(old: &[VideoShot], new: &[VideoShot]) -> Vec<(String, ShotDiff)> {
+    let mut diffs: Vec<(String, ShotDiff)> = old
+        .iter()
+        .filter_map(|old_shot| {
+            let diff = match new.iter().find(|s| s.id == old_shot.id) {
+                Some(new_shot) => {
+                    let block_diffs = diff_shots(old_shot, new_shot);
+
+                    if block_diffs.is_empty() {
+                        return None;
+                    }
+
+                    ShotDiff::Changed(block_diffs)
+                }
+                None => ShotDiff::Removed,
+            };
+
+            Some((old_shot.id.clone(), diff))
+        })
+        .collect();
+
+    diffs.extend(
+        new.iter()
+            .filter(|new_shot| !old.iter().any(|s| s.id == new_shot.id))
+            .map(|new_shot| (new_shot.id.clone(), ShotDiff::Added)),
+    );
+
+    diffs
+}
+
+fn format_block_key(key: (u8, u16)) -> String {
+    match key {
+        (level @ 2, target) | (level @ 8, target) | (level @ 10, target) => {
+            format!("L{} (target {})", level, target)
+        }
+        (level, _) => format!("L{}", level),
+    }
+}
+
+fn format_block_diff(diff: &BlockDiff) -> String {
+    match diff {
+        BlockDiff::Removed { key } => format!("{} removed", format_block_key(*key)),
+        BlockDiff::Added { key } => format!("{} added", format_block_key(*key)),
+        BlockDiff::Changed {
+            key,
+            field,
+            old,
+            new,
+        } => format!("{} {}: {} -> {}", format_block_key(*key), field, old, new),
+    }
+}
@@ -10,9 +10,10 @@ use commands::Command;
 
 mod dovi;
 use dovi::{
-    converter::Converter, demuxer::Demuxer, editor::Editor, exporter::Exporter,
-    generator::Generator, rpu_extractor::RpuExtractor, rpu_info::RpuInfo,
-    rpu_injector::RpuInjector, CliOptions, Format,
+    container_info::ContainerInfo, converter::Converter, demuxer::Demuxer, editor::Editor,
+    exporter::Exporter, format_converter::FormatConverter, generator::Generator, rpu_bits::RpuBits,
+    rpu_extractor::RpuExtractor, rpu_info::RpuInfo, rpu_injector::RpuInjector, rpu_stats::RpuStats,
+    sync_checker::SyncChecker, xml_diff::XmlDiff, CliOptions, Format,
 };
 
 #[derive(StructOpt, Debug)]
@@ -36,6 +37,30 @@ struct Opt {
     #[structopt(long, help = "Ignore HDR10+ metadata when writing the output HEVC.")]
     drop_hdr10plus: bool,
 
+    #[structopt(
+        long,
+        help = "Ignore SEI messages other than HDR10+ when writing the output HEVC."
+    )]
+    drop_sei: bool,
+
+    #[structopt(
+        long,
+        help = "Fail when the processed RPUs are missing required metadata blocks (L5, L6)"
+    )]
+    validate_required_blocks: bool,
+
+    #[structopt(
+        long,
+        help = "Write the extracted RPUs in decoded order, instead of reordering by POC"
+    )]
+    no_reorder: bool,
+
+    #[structopt(
+        long,
+        help = "Write the progress as JSON lines to stderr, instead of the progress bar"
+    )]
+    progress_json: bool,
+
     #[structopt(subcommand)]
     cmd: Command,
 }
@@ -48,6 +73,11 @@ fn main() -> Result<()> {
         crop: opt.crop,
         discard_el: false,
         drop_hdr10plus: opt.drop_hdr10plus,
+        drop_sei: opt.drop_sei,
+        validate_required_blocks: opt.validate_required_blocks,
+        reorder: !opt.no_reorder,
+        progress_json: opt.progress_json,
+        ..Default::default()
     };
 
     // Set mode 0 by default if cropping, otherwise it has no effect
@@ -62,7 +92,7 @@ fn main() -> Result<()> {
             bl_out,
             el_out,
             el_only,
-        } => Demuxer::demux(input, stdin, bl_out, el_out, el_only, cli_options),
+        } => Demuxer::demux(input, stdin, bl_out, el_out, el_only, cli_options).map(|_| ()),
         Command::Editor {
             input,
             json_file,
@@ -77,6 +107,7 @@ fn main() -> Result<()> {
             cli_options.discard_el = discard;
             Converter::convert(input, stdin, output, cli_options)
         }
+        Command::To81 { input, output } => Converter::convert_to_81(input, output, cli_options),
         Command::ExtractRpu {
             input,
             stdin,
@@ -87,12 +118,44 @@ fn main() -> Result<()> {
             rpu_in,
             output,
         } => RpuInjector::inject_rpu(input, rpu_in, output, cli_options),
-        Command::Info { input, frame } => RpuInfo::info(input, frame),
+        Command::Stats {
+            input,
+            histogram,
+            config,
+        } => RpuStats::stats(input, histogram, config),
+        Command::CheckSync { input, rpu_in } => SyncChecker::check_sync(input, rpu_in),
+        Command::ContainerInfo { input } => ContainerInfo::info(input),
+        Command::Info {
+            input,
+            frame,
+            conversions,
+        } => RpuInfo::info(input, frame, conversions),
+        Command::Bits { input, frame } => RpuBits::bits(input, frame),
         Command::Generate { .. } => {
             let mut generator = Generator::from_command(opt.cmd)?;
+            generator.cli_options = cli_options;
             generator.generate()
         }
-        Command::Export { input, output } => Exporter::export(input, output),
+        Command::XmlDiff {
+            old,
+            new,
+            canvas_width,
+            canvas_height,
+        } => XmlDiff::diff(old, new, canvas_width, canvas_height),
+        Command::Export {
+            input,
+            output,
+            l5_csv,
+            canvas_width,
+            canvas_height,
+        } => {
+            if l5_csv {
+                Exporter::export_l5_csv(input, output, canvas_width.zip(canvas_height))
+            } else {
+                Exporter::export(input, output)
+            }
+        }
+        Command::ConvertFormat { input, output } => FormatConverter::convert_format(input, output),
     };
 
     if let Err(e) = res {